@@ -0,0 +1,172 @@
+//! Plays the runner against a local fake engine and measures it, for the table in the README.
+//!
+//! ```text
+//! cargo run --release --example runner_bench -- [rounds] [engine delay per line, in ms]
+//! ```
+//!
+//! Both players only ever check or call, so the engine knows exactly what the bot should answer.
+//! Latency is the time from the engine writing a line to reading the bot's answer. An answer that
+//! doesn't come within 100ms counts as missed, and the engine carries on as if the bot had answered.
+//! The harness only uses what the runner had before it was reworked, so it can be dropped into an
+//! older checkout to measure that too.
+
+use pbrust_skeleton::actions::{Action, ActionType};
+use pbrust_skeleton::bot::PokerBot;
+use pbrust_skeleton::runner::Runner;
+use pbrust_skeleton::states::{GameState, RoundState, TerminalState};
+use std::convert::Infallible;
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+
+const RESPONSE_TIMEOUT: Duration = Duration::from_millis(100);
+
+struct CheckCall;
+
+impl PokerBot for CheckCall {
+    type Error = Infallible;
+
+    fn handle_new_round(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn handle_round_over(&mut self, _gs: &GameState, _ts: &TerminalState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn get_action(&mut self, _gs: &GameState, rs: &RoundState, _player_index: usize) -> Result<Action, Infallible> {
+        if rs.legal_actions().contains(ActionType::CHECK) {
+            Ok(Action::Check)
+        } else {
+            Ok(Action::Call)
+        }
+    }
+}
+
+#[derive(Default)]
+struct Stats {
+    latencies: Vec<Duration>,
+    missed: u32,
+    unexpected: u32,
+}
+
+struct Engine {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    delay: Duration,
+    clock: f32,
+    stats: Stats,
+}
+
+impl Engine {
+    /// Sends a line, and checks the bot answers it with the expected action
+    fn ask(&mut self, line: &str, expected: &str) -> bool {
+        if self.delay > Duration::from_millis(0) {
+            thread::sleep(self.delay);
+        }
+        let start = Instant::now();
+        if writeln!(self.writer, "T{:.3} {}", self.clock, line).is_err() {
+            return false;
+        }
+        let mut answer = String::new();
+        match self.reader.read_line(&mut answer) {
+            Ok(0) => return false,
+            Ok(_) => {
+                self.stats.latencies.push(start.elapsed());
+                if answer.trim() != expected {
+                    self.stats.unexpected += 1;
+                }
+            },
+            Err(_) => self.stats.missed += 1,
+        }
+        self.clock -= start.elapsed().as_secs_f32();
+        true
+    }
+
+    /// Plays a round where everyone checks it down. Postflop, the big blind acts first.
+    fn play_round(&mut self, seat: usize) -> bool {
+        let flop = "B2c,3d,Kh";
+        let turn = "B2c,3d,Kh,9s";
+        let river = "B2c,3d,Kh,9s,4c";
+        let lines: Vec<(String, &str)> = if seat == 0 {
+            vec![
+                ("P0 HAh,Kd".into(), "C"),
+                (format!("C K {} K", flop), "K"),
+                (format!("K {} K", turn), "K"),
+                (format!("K {} K", river), "K"),
+            ]
+        } else {
+            vec![
+                ("P1 HAh,Kd C".into(), "K"),
+                (format!("K {}", flop), "K"),
+                (format!("K K {}", turn), "K"),
+                (format!("K K {}", river), "K"),
+            ]
+        };
+        for (line, expected) in lines.iter() {
+            if !self.ask(line, expected) {
+                return false;
+            }
+        }
+        // Both players check the river, and split the pot at showdown. The runner answers every line,
+        // even with no round going, and then it checks.
+        let ending = if seat == 0 { "K O7c,7s D0" } else { "K K O7c,7s D0" };
+        self.ask(ending, "K")
+    }
+}
+
+/// CPU time this process has used, where /proc has it
+fn cpu_time() -> Option<Duration> {
+    let stat = fs::read_to_string("/proc/self/stat").ok()?;
+    let fields: Vec<&str> = stat.rsplit(')').next()?.split_whitespace().collect();
+    let ticks = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    // The kernel reports in clock ticks, which are 1/100 of a second on every Linux worth benchmarking on
+    Some(Duration::from_millis(ticks * 10))
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let rounds: u32 = args.next().map(|arg| arg.parse().expect("rounds should be a number")).unwrap_or(200);
+    let delay = Duration::from_millis(args.next().map(|arg| arg.parse().expect("delay should be a number of ms")).unwrap_or(0));
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("failed to listen");
+    let addr = listener.local_addr().expect("listener has an address");
+    let engine = thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("runner never connected");
+        stream.set_nodelay(true).expect("set_nodelay call failed");
+        stream.set_read_timeout(Some(RESPONSE_TIMEOUT)).expect("set_read_timeout call failed");
+        let mut engine = Engine {
+            reader: BufReader::new(stream.try_clone().expect("failed to clone stream")),
+            writer: stream,
+            delay,
+            clock: 1000.0,
+            stats: Stats::default(),
+        };
+        let mut played = 0;
+        while played < rounds && engine.play_round(played as usize % 2) {
+            played += 1;
+        }
+        let _ = writeln!(engine.writer, "Q");
+        (played, engine.stats)
+    });
+
+    let (cpu_start, start) = (cpu_time(), Instant::now());
+    if let Err(e) = Runner::run_bot(Box::new(CheckCall), addr, 1) {
+        eprintln!("runner failed: {}", e);
+    }
+    let (played, stats) = engine.join().expect("engine panicked");
+    let wall = start.elapsed();
+
+    let mut latencies = stats.latencies;
+    latencies.sort();
+    let percentile = |p: f64| latencies.get(((latencies.len().max(1) - 1) as f64 * p) as usize).copied().unwrap_or_default();
+    let cpu = match (cpu_start, cpu_time()) {
+        (Some(before), Some(after)) => format!("{:.0}% of a core", 100.0 * (after - before).as_secs_f64() / wall.as_secs_f64()),
+        _ => "unknown".into(),
+    };
+    println!("{} rounds in {:.3}s: {:.1} rounds/s, CPU {}, latency p50 {:?} p99 {:?}, {} missed, {} unexpected responses",
+        played, wall.as_secs_f64(), played as f64 / wall.as_secs_f64(), cpu, percentile(0.5), percentile(0.99), stats.missed, stats.unexpected);
+}
@@ -26,9 +26,6 @@ impl Action {
     }
 
     pub fn is_raise(&self) -> bool {
-        match self {
-            Action::Raise(_) => true,
-            _ => false,
-        }
+        matches!(self, Action::Raise(_))
     }
 }
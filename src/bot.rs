@@ -1,5 +1,10 @@
 use super::actions::Action;
+use super::cards::HoleCards;
+use super::clock::{DecisionBudget, Deadline};
+use super::config::RunnerConfig;
+use super::sanitizer::Correction;
 use super::states::{GameState, RoundState, TerminalState};
+use super::summary::MatchSummary;
 use std::error::Error;
 
 pub trait PokerBot {
@@ -8,4 +13,42 @@ pub trait PokerBot {
     fn handle_new_round(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<(), Self::Error>;
     fn handle_round_over(&mut self, gs: &GameState, ts: &TerminalState, player_index: usize) -> Result<(), Self::Error>;
     fn get_action(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<Action, Self::Error>;
+
+    /// Called right before the bot is asked for an action, with the time the runner allows for the decision.
+    /// If the bot overruns it, the runner plays a safe default action instead.
+    fn handle_decision_budget(&mut self, _gs: &GameState, _budget: &DecisionBudget) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Deadline-aware variant of `get_action`, and the one the runner actually calls.
+    /// Anytime bots should override this, publish their best action so far with `Deadline::suggest`,
+    /// and return once `Deadline::expired` is true. If they run out of time, the runner plays the latest suggestion.
+    fn get_action_by(&mut self, gs: &GameState, rs: &RoundState, player_index: usize, _deadline: &Deadline) -> Result<Action, Self::Error> {
+        self.get_action(gs, rs, player_index)
+    }
+
+    /// Called whenever the runner had to correct an illegal action returned by the bot
+    fn handle_correction(&mut self, _gs: &GameState, _rs: &RoundState, _correction: &Correction) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called once, before the first round, as soon as the runner knows which seat the bot is in
+    fn handle_game_start(&mut self, _player_index: usize, _config: &RunnerConfig) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called once the match is over, with the final game state and the runner's summary of the match
+    fn handle_game_end(&mut self, _gs: &GameState, _summary: &MatchSummary) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called for every action the opponent makes, with the round state it was applied to
+    fn handle_opponent_action(&mut self, _gs: &GameState, _rs: &RoundState, _action: Action, _player_index: usize) -> Result<(), Self::Error> {
+        Ok(())
+    }
+
+    /// Called when the engine reveals the opponent's hand at showdown
+    fn handle_reveal(&mut self, _gs: &GameState, _hand: &HoleCards, _player_index: usize) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
@@ -0,0 +1,296 @@
+//! Ready made bots, for sparring, examples, and falling back on inside other bots
+//!
+//! None of these can fail, so they all use `Infallible` as their error.
+
+use crate::actions::{Action, ActionType};
+use crate::bot::PokerBot;
+use crate::cards::{CardHand, CardValue};
+use crate::preflop::chen_score;
+use crate::rng::SeededRng;
+use crate::states::{GameState, RoundState, TerminalState, BIG_BLIND};
+use std::convert::Infallible;
+
+/// Plays by sampled showdown equity, which needs the showdown engine
+#[cfg(feature = "showdown")]
+pub use crate::equity::EquityBot;
+
+/// What it costs the player to act to stay in the hand
+pub(crate) fn continue_cost(rs: &RoundState) -> u32 {
+    let active = rs.button as usize % 2;
+    rs.pips[1 - active] - rs.pips[active]
+}
+
+/// Raises to the given amount (clamped to the legal bounds) if raising is allowed
+pub(crate) fn raise_to(rs: &RoundState, amount: u32) -> Option<Action> {
+    if rs.legal_actions().contains(ActionType::RAISE) {
+        let [min, max] = rs.raise_bounds();
+        Some(Action::Raise(amount.max(min).min(max)))
+    } else {
+        None
+    }
+}
+
+/// Checks if that is free, and calls otherwise
+pub(crate) fn check_or_call(rs: &RoundState) -> Action {
+    if rs.legal_actions().contains(ActionType::CHECK) {
+        Action::Check
+    } else {
+        Action::Call
+    }
+}
+
+/// Checks if that is free, and folds otherwise
+pub(crate) fn check_or_fold(rs: &RoundState) -> Action {
+    if rs.legal_actions().contains(ActionType::CHECK) {
+        Action::Check
+    } else {
+        Action::Fold
+    }
+}
+
+/// Never folds and never raises
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CheckCall;
+
+impl PokerBot for CheckCall {
+    type Error = Infallible;
+
+    fn handle_new_round(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn handle_round_over(&mut self, _gs: &GameState, _ts: &TerminalState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn get_action(&mut self, _gs: &GameState, rs: &RoundState, _player_index: usize) -> Result<Action, Infallible> {
+        Ok(check_or_call(rs))
+    }
+}
+
+/// Picks a legal action at random, with every kind of action equally likely.
+/// Raises are uniform over the legal bounds. The same seed always plays the same way.
+#[derive(Debug, Clone)]
+pub struct RandomBot {
+    rng: SeededRng,
+}
+
+impl RandomBot {
+    pub fn new(seed: u64) -> RandomBot {
+        RandomBot {
+            rng: SeededRng::new(seed),
+        }
+    }
+}
+
+impl PokerBot for RandomBot {
+    type Error = Infallible;
+
+    fn handle_new_round(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn handle_round_over(&mut self, _gs: &GameState, _ts: &TerminalState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn get_action(&mut self, _gs: &GameState, rs: &RoundState, _player_index: usize) -> Result<Action, Infallible> {
+        let legal_actions = rs.legal_actions();
+        let choices: Vec<ActionType> = [ActionType::FOLD, ActionType::CALL, ActionType::CHECK, ActionType::RAISE].iter()
+            .copied()
+            .filter(|&action| legal_actions.contains(action))
+            .collect();
+        if choices.is_empty() {
+            return Ok(check_or_fold(rs));
+        }
+        Ok(match choices[self.rng.below(choices.len())] {
+            ActionType::FOLD => Action::Fold,
+            ActionType::CALL => Action::Call,
+            ActionType::CHECK => Action::Check,
+            _ => {
+                let [min, max] = rs.raise_bounds();
+                Action::Raise(self.rng.between(min, max))
+            },
+        })
+    }
+}
+
+/// Plays a tight-aggressive preflop chart, scoring hands with the Chen formula.
+/// It raises good hands, calls playable ones when it isn't facing a raise, and folds the rest.
+/// After the flop it keeps calling with hands it raised, and gives up on everything else.
+#[derive(Debug, Clone)]
+pub struct TightAggressive {
+    ordering: [CardValue; 13],
+    raise_at: f32,
+    call_at: f32,
+    // Whether we liked our hand enough to raise with it before the flop
+    committed: bool,
+}
+
+impl TightAggressive {
+    /// Scores hands with the given value ordering (lowest first)
+    pub fn new(ordering: [CardValue; 13]) -> TightAggressive {
+        TightAggressive {
+            ordering,
+            raise_at: 9.0,
+            call_at: 6.0,
+            committed: false,
+        }
+    }
+
+    /// Changes the Chen scores we need to raise and to call (9 and 6 by default)
+    pub fn with_thresholds(mut self, raise_at: f32, call_at: f32) -> TightAggressive {
+        self.raise_at = raise_at;
+        self.call_at = call_at;
+        self
+    }
+
+    /// Scores a starting hand with the Chen formula, where 20 is the best hand (aces)
+    pub fn chen_score(&self, hand: &CardHand) -> f32 {
+        chen_score(&self.ordering, hand)
+    }
+}
+
+impl Default for TightAggressive {
+    fn default() -> TightAggressive {
+        TightAggressive::new(CardValue::ALL)
+    }
+}
+
+impl PokerBot for TightAggressive {
+    type Error = Infallible;
+
+    fn handle_new_round(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<(), Infallible> {
+        self.committed = false;
+        Ok(())
+    }
+
+    fn handle_round_over(&mut self, _gs: &GameState, _ts: &TerminalState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn get_action(&mut self, _gs: &GameState, rs: &RoundState, player_index: usize) -> Result<Action, Infallible> {
+        // The chart only knows two card hands
+        let hand = match rs.hand_pair(player_index) {
+            Some(hand) => hand,
+            None => return Ok(check_or_call(rs)),
+        };
+
+        if rs.street > 0 {
+            return Ok(if self.committed { check_or_call(rs) } else { check_or_fold(rs) });
+        }
+
+        let score = self.chen_score(&hand);
+        let cost = continue_cost(rs);
+        if score >= self.raise_at {
+            self.committed = true;
+            // Raise to three times whatever the opponent has in
+            let opponent_pip = rs.pips[1 - rs.button as usize % 2];
+            Ok(raise_to(rs, 3 * opponent_pip).unwrap_or_else(|| check_or_call(rs)))
+        } else if score >= self.call_at && cost <= BIG_BLIND {
+            Ok(check_or_call(rs))
+        } else {
+            Ok(check_or_fold(rs))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{Card, CardDeck, HoleCards};
+    use crate::sanitizer::Violation;
+    use crate::states::{StateResult, SMALL_BLIND, STARTING_STACK};
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn game() -> GameState {
+        GameState { bankroll: 0, game_clock: 20.0, round_num: 1 }
+    }
+
+    /// The small blind's first decision, holding `ours` against `theirs`
+    fn preflop(ours: &str, theirs: &str) -> RoundState {
+        RoundState {
+            button: 0,
+            street: 0,
+            pips: [SMALL_BLIND, BIG_BLIND],
+            stacks: [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [HoleCards::new(&cards(ours)), HoleCards::new(&cards(theirs))],
+            deck: CardDeck(vec![]),
+            previous: None,
+        }
+    }
+
+    /// Plays a round with the bot in both seats, dealing the board the way the engine does,
+    /// and checks every action it takes is legal. Returns the actions in order.
+    fn self_play<B: PokerBot<Error=Infallible>>(bot: &mut B, ours: &str, theirs: &str) -> Vec<Action> {
+        let board = cards("2c 3d Kh 5s 9c");
+        let mut rs = preflop(ours, theirs);
+        bot.handle_new_round(&game(), &rs, 0).unwrap();
+        let mut actions = vec![];
+        loop {
+            let player = rs.button as usize % 2;
+            let action = bot.get_action(&game(), &rs, player).unwrap();
+            assert_eq!(Violation::find(action, &rs), None, "{:?} is illegal in {:?}", action, rs);
+            actions.push(action);
+            rs = match rs.proceed(action) {
+                StateResult::Round(mut next) => {
+                    // A new street starts with the big blind to act and nothing in
+                    if next.button == 1 && next.pips == [0, 0] {
+                        let dealt = if rs.street == 0 { 3 } else { rs.street as usize + 1 };
+                        next.deck = CardDeck(board[..dealt].to_vec());
+                        next.street = dealt as u32;
+                    }
+                    next
+                },
+                StateResult::Terminal(_) => return actions,
+            };
+        }
+    }
+
+    #[test]
+    fn bots_only_play_legal_actions() {
+        for (ours, theirs) in [("As Ah", "Kd Kc"), ("7h 2c", "Qs Jd"), ("9s 8s", "Ad 4c")] {
+            self_play(&mut CheckCall, ours, theirs);
+            self_play(&mut TightAggressive::default(), ours, theirs);
+            for seed in 0..50 {
+                self_play(&mut RandomBot::new(seed), ours, theirs);
+            }
+        }
+    }
+
+    #[test]
+    fn random_bots_replay_their_seed() {
+        let mut first = RandomBot::new(7);
+        let mut second = RandomBot::new(7);
+        let played: Vec<Vec<Action>> = (0..20).map(|_| self_play(&mut first, "As Ah", "Kd Kc")).collect();
+        let replayed: Vec<Vec<Action>> = (0..20).map(|_| self_play(&mut second, "As Ah", "Kd Kc")).collect();
+        assert_eq!(played, replayed);
+        let other: Vec<Vec<Action>> = (0..20).map(|_| self_play(&mut RandomBot::new(8), "As Ah", "Kd Kc")).collect();
+        assert_ne!(played, other);
+    }
+
+    #[test]
+    fn tight_aggressive_folds_trash_and_raises_premiums() {
+        let mut bot = TightAggressive::default();
+        let rs = preflop("7h 2c", "As Ah");
+        bot.handle_new_round(&game(), &rs, 0).unwrap();
+        assert_eq!(bot.get_action(&game(), &rs, 0).unwrap(), Action::Fold);
+
+        let rs = preflop("As Ah", "7h 2c");
+        bot.handle_new_round(&game(), &rs, 0).unwrap();
+        assert_eq!(bot.get_action(&game(), &rs, 0).unwrap(), Action::Raise(3 * BIG_BLIND));
+
+        // Facing a raise as the big blind
+        let raised = match preflop("As Ah", "Kd Kc").proceed(Action::Raise(6)) {
+            StateResult::Round(rs) => rs,
+            StateResult::Terminal(_) => unreachable!(),
+        };
+        bot.handle_new_round(&game(), &raised, 1).unwrap();
+        assert_eq!(bot.get_action(&game(), &raised, 1).unwrap(), Action::Raise(18));
+        let raised = RoundState { hands: [None, HoleCards::new(&cards("7h 2c"))], ..raised };
+        assert_eq!(bot.get_action(&game(), &raised, 1).unwrap(), Action::Fold);
+    }
+}
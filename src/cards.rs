@@ -5,7 +5,6 @@
 use std::fmt;
 use std::str::FromStr;
 use std::error::Error;
-use itertools::Itertools;
 
 /// Encodes card suit
 #[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, PartialOrd, Ord)]
@@ -16,6 +15,11 @@ pub enum CardSuit {
     Clubs,
 }
 
+impl CardSuit {
+    /// Every suit
+    pub const ALL: [CardSuit; 4] = [CardSuit::Spades, CardSuit::Hearts, CardSuit::Diamonds, CardSuit::Clubs];
+}
+
 impl FromStr for CardSuit {
     type Err = CardConversionError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -67,6 +71,14 @@ pub enum CardValue {
     Ace
 }
 
+impl CardValue {
+    /// Every value, from lowest to highest in the standard ordering
+    pub const ALL: [CardValue; 13] = [
+        CardValue::Two, CardValue::Three, CardValue::Four, CardValue::Five, CardValue::Six, CardValue::Seven, CardValue::Eight,
+        CardValue::Nine, CardValue::Ten, CardValue::Jack, CardValue::Queen, CardValue::King, CardValue::Ace
+    ];
+}
+
 impl FromStr for CardValue {
     type Err = CardConversionError;
     fn from_str(s: &str) -> Result<Self, Self::Err> {
@@ -137,6 +149,21 @@ impl Card {
     pub fn value(&self) -> CardValue {
         self.value
     }
+
+    /// Position of the card in `CardDeck::full`, from 0 to 51
+    pub fn index(&self) -> usize {
+        let suit = CardSuit::ALL.iter().position(|&suit| suit == self.suit).unwrap_or(0);
+        suit * 13 + self.value as usize
+    }
+
+    /// The card at the given position in `CardDeck::full`, if there is one
+    pub fn from_index(index: usize) -> Option<Card> {
+        if index < 52 {
+            Some(Card::new(CardSuit::ALL[index / 13], CardValue::ALL[index % 13]))
+        } else {
+            None
+        }
+    }
 }
 
 impl FromStr for Card {
@@ -161,22 +188,100 @@ impl fmt::Display for Card {
     }
 }
 
+/// Which cards the game is played with
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub enum Variant {
+    /// The full 52 card deck
+    #[default]
+    Holdem,
+    /// Short deck (6+) hold'em: 36 cards, without the four lowest values of the ordering (2s to 5s in the usual one)
+    ShortDeck,
+}
+
+impl Variant {
+    /// How many of the lowest values of the ordering are out of the deck
+    pub fn removed(&self) -> usize {
+        match self {
+            Variant::Holdem => 0,
+            Variant::ShortDeck => 4,
+        }
+    }
+
+    /// The values in play under the ordering, lowest first
+    pub fn values<'a>(&self, ordering: &'a [CardValue; 13]) -> &'a [CardValue] {
+        &ordering[self.removed()..]
+    }
+}
+
+impl FromStr for Variant {
+    type Err = CardConversionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "holdem" => Ok(Variant::Holdem),
+            "shortdeck" => Ok(Variant::ShortDeck),
+            "" => Err(CardConversionError::Empty),
+            s => Err(CardConversionError::UnknownVariant(s.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Variant {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Variant::Holdem => write!(fmt, "holdem"),
+            Variant::ShortDeck => write!(fmt, "shortdeck"),
+        }
+    }
+}
+
 /// Wraps a deck and makes it printable
 #[derive(Debug, Clone)]
 pub struct CardDeck(pub Vec<Card>);
 
+impl CardDeck {
+    /// All 52 cards, in a fixed order
+    pub fn full() -> CardDeck {
+        CardDeck(CardSuit::ALL.iter().flat_map(|&suit| CardValue::ALL.iter().map(move |&value| Card::new(suit, value))).collect())
+    }
+
+    /// All the cards except the given ones, in the same order as `full`
+    pub fn without(excluded: &[Card]) -> CardDeck {
+        let CardDeck(cards) = CardDeck::full();
+        CardDeck(cards.into_iter().filter(|card| !excluded.contains(card)).collect())
+    }
+
+    /// Every card in play in the variant under the ordering, in the same order as `full`
+    pub fn of(variant: Variant, ordering: &[CardValue; 13]) -> CardDeck {
+        let values = variant.values(ordering);
+        let CardDeck(cards) = CardDeck::full();
+        CardDeck(cards.into_iter().filter(|card| values.contains(&card.value())).collect())
+    }
+}
+
+/// Writes cards as a bracketed, comma separated list
+fn write_cards(fmt: &mut fmt::Formatter, cards: &[Card]) -> fmt::Result {
+    write!(fmt, "[")?;
+    for (i, card) in cards.iter().enumerate() {
+        if i > 0 {
+            write!(fmt, ", ")?;
+        }
+        write!(fmt, "{}", card)?;
+    }
+    write!(fmt, "]")
+}
+
 impl fmt::Display for CardDeck {
     fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
         if self.0.is_empty() {
             write!(fmt, "<empty>")
         } else {
-            write!(fmt, "[{}]", self.0.iter().format(", "))
+            write_cards(fmt, &self.0)
         }
     }
 }
 
 /// Wraps a hand and makes it printable
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
 pub struct CardHand(pub [Card; 2]);
 
 impl fmt::Display for CardHand {
@@ -185,6 +290,61 @@ impl fmt::Display for CardHand {
     }
 }
 
+/// Most hole cards a player can hold, as in five card Omaha
+pub const MAX_HOLE_CARDS: usize = 5;
+
+/// A player's hole cards, however many the game deals. Two card hands convert to and from `CardHand`.
+#[derive(Debug, Clone, Copy, Hash, PartialEq, Eq)]
+pub struct HoleCards {
+    // The slots past `len` always hold the same filler, so equal hands compare equal
+    cards: [Card; MAX_HOLE_CARDS],
+    len: usize,
+}
+
+impl HoleCards {
+    /// Takes the cards in the order given, or `None` if there are more than `MAX_HOLE_CARDS`
+    pub fn new(cards: &[Card]) -> Option<HoleCards> {
+        if cards.len() > MAX_HOLE_CARDS {
+            return None;
+        }
+        let mut slots = [Card::new(CardSuit::Spades, CardValue::Two); MAX_HOLE_CARDS];
+        slots[..cards.len()].copy_from_slice(cards);
+        Some(HoleCards { cards: slots, len: cards.len() })
+    }
+
+    pub fn cards(&self) -> &[Card] {
+        &self.cards[..self.len]
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The hand as a `CardHand`, if it has exactly two cards
+    pub fn pair(&self) -> Option<CardHand> {
+        match self.cards() {
+            &[first, second] => Some(CardHand([first, second])),
+            _ => None,
+        }
+    }
+}
+
+impl From<CardHand> for HoleCards {
+    fn from(hand: CardHand) -> HoleCards {
+        HoleCards::new(&hand.0).expect("Two cards fit in any hand")
+    }
+}
+
+impl fmt::Display for HoleCards {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write_cards(fmt, self.cards())
+    }
+}
+
 /// Allows for Option<CardHand> to be easily printed
 pub trait CardHandExt {
     fn print(&self) -> String;
@@ -196,6 +356,12 @@ impl CardHandExt for Option<CardHand> {
     }
 }
 
+impl CardHandExt for Option<HoleCards> {
+    fn print(&self) -> String {
+        self.map(|x| format!("{}", x)).unwrap_or("<empty>".into())
+    }
+}
+
 /// Converts a string into a Vec<Card>
 #[macro_export]
 macro_rules! into_cards {
@@ -221,6 +387,7 @@ pub enum CardConversionError {
     Empty,
     TooLong(String),
     NotACard(String),
+    UnknownVariant(String),
 }
 
 impl Error for CardConversionError {}
@@ -233,6 +400,7 @@ impl fmt::Display for CardConversionError {
             CardConversionError::Empty => write!(fmt, "Unexpected empty string"),
             CardConversionError::TooLong(s) => write!(fmt, "String too long: {}", s),
             CardConversionError::NotACard(s) => write!(fmt, "String too short for card: {}", s),
+            CardConversionError::UnknownVariant(s) => write!(fmt, "Unknown variant: {}", s),
         }
     }
 }
@@ -0,0 +1,174 @@
+//! Budgeting of the game clock across decisions
+
+use super::actions::Action;
+use super::states::{GameState, NUM_ROUNDS};
+use std::time::{Duration, Instant};
+use std::sync::{Arc, Mutex};
+
+/// How long a single decision is allowed to take
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecisionBudget {
+    started: Instant,
+    budget: Duration,
+}
+
+impl DecisionBudget {
+    /// Starts a budget that runs out `budget` from now
+    pub fn new(budget: Duration) -> DecisionBudget {
+        DecisionBudget {
+            started: Instant::now(),
+            budget,
+        }
+    }
+
+    /// The total time allowed for the decision
+    pub fn budget(&self) -> Duration {
+        self.budget
+    }
+
+    /// The instant the decision has to be made by
+    pub fn deadline(&self) -> Instant {
+        self.started + self.budget
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// Time left before the deadline, or zero if it has passed
+    pub fn remaining(&self) -> Duration {
+        self.budget.checked_sub(self.elapsed()).unwrap_or_default()
+    }
+
+    pub fn expired(&self) -> bool {
+        self.elapsed() >= self.budget
+    }
+}
+
+/// Handed to anytime bots, so they can publish their best action so far while they keep searching
+#[derive(Debug, Clone)]
+pub struct Deadline {
+    budget: DecisionBudget,
+    best: Arc<Mutex<Option<Action>>>,
+}
+
+impl Deadline {
+    pub fn new(budget: DecisionBudget) -> Deadline {
+        Deadline {
+            budget,
+            best: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    pub fn budget(&self) -> &DecisionBudget {
+        &self.budget
+    }
+
+    pub fn remaining(&self) -> Duration {
+        self.budget.remaining()
+    }
+
+    pub fn expired(&self) -> bool {
+        self.budget.expired()
+    }
+
+    /// Publishes the best action found so far. If time runs out, the latest suggestion is played.
+    pub fn suggest(&self, action: Action) {
+        if let Ok(mut best) = self.best.lock() {
+            *best = Some(action);
+        }
+    }
+
+    /// The latest suggestion, if any
+    pub fn best(&self) -> Option<Action> {
+        self.best.lock().ok().and_then(|best| *best)
+    }
+}
+
+/// Spreads the remaining game clock over the remaining rounds
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TimeManager {
+    /// Time that is never handed out, to absorb network and runner overhead
+    pub reserve: Duration,
+    /// Smallest budget handed out, even when the clock is nearly gone
+    pub minimum: Duration,
+    /// How many decisions we expect to make in an average round
+    pub decisions_per_round: u32,
+}
+
+impl Default for TimeManager {
+    fn default() -> TimeManager {
+        TimeManager {
+            reserve: Duration::from_millis(500),
+            minimum: Duration::from_millis(1),
+            decisions_per_round: 2,
+        }
+    }
+}
+
+impl TimeManager {
+    /// Number of rounds left to play, counting the current one
+    pub fn remaining_rounds(gs: &GameState) -> u32 {
+        (NUM_ROUNDS + 1).saturating_sub(gs.round_num).max(1)
+    }
+
+    /// Time the current round may use, given what is left on the game clock
+    pub fn round_share(&self, gs: &GameState) -> Duration {
+        let clock = Duration::from_secs_f32(gs.game_clock.max(0.0));
+        clock.checked_sub(self.reserve).unwrap_or_default() / TimeManager::remaining_rounds(gs)
+    }
+
+    /// Starts the budget for a decision that has to be made right now
+    pub fn budget(&self, gs: &GameState) -> DecisionBudget {
+        let share = self.round_share(gs) / self.decisions_per_round.max(1);
+        DecisionBudget::new(share.max(self.minimum))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game_state(round_num: u32, game_clock: f32) -> GameState {
+        GameState { bankroll: 0, game_clock, round_num }
+    }
+
+    #[test]
+    fn clock_is_spread_over_the_rounds_left() {
+        let manager = TimeManager::default();
+        assert_eq!(TimeManager::remaining_rounds(&game_state(1, 60.0)), NUM_ROUNDS);
+        assert_eq!(TimeManager::remaining_rounds(&game_state(NUM_ROUNDS, 60.0)), 1);
+        assert_eq!(TimeManager::remaining_rounds(&game_state(NUM_ROUNDS + 5, 60.0)), 1);
+        assert_eq!(manager.round_share(&game_state(1, 60.5)), Duration::from_millis(60));
+        assert_eq!(manager.budget(&game_state(1, 60.5)).budget(), Duration::from_millis(30));
+        assert_eq!(manager.budget(&game_state(NUM_ROUNDS, 2.5)).budget(), Duration::from_secs(1));
+    }
+
+    #[test]
+    fn reserve_is_never_handed_out() {
+        let manager = TimeManager::default();
+        assert_eq!(manager.round_share(&game_state(NUM_ROUNDS, 0.25)), Duration::ZERO);
+        assert_eq!(manager.budget(&game_state(NUM_ROUNDS, 0.25)).budget(), manager.minimum);
+        assert_eq!(manager.budget(&game_state(NUM_ROUNDS, -1.0)).budget(), manager.minimum);
+    }
+
+    #[test]
+    fn budgets_run_out() {
+        let budget = DecisionBudget::new(Duration::ZERO);
+        assert!(budget.expired());
+        assert_eq!(budget.remaining(), Duration::ZERO);
+        let budget = DecisionBudget::new(Duration::from_secs(60));
+        assert!(!budget.expired());
+        assert!(budget.remaining() <= Duration::from_secs(60));
+        assert_eq!(budget.deadline() - budget.budget(), budget.started);
+    }
+
+    #[test]
+    fn deadlines_keep_the_latest_suggestion() {
+        let deadline = Deadline::new(DecisionBudget::new(Duration::from_secs(60)));
+        assert_eq!(deadline.best(), None);
+        deadline.suggest(Action::Call);
+        deadline.clone().suggest(Action::Raise(10));
+        assert_eq!(deadline.best(), Some(Action::Raise(10)));
+    }
+}
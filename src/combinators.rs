@@ -0,0 +1,682 @@
+//! Wrappers that build bots out of other bots
+//!
+//! Every wrapper passes all the notifications it gets on to the bots it wraps, so they keep
+//! up with the match even when they are not the one deciding.
+
+use crate::actions::Action;
+use crate::bot::PokerBot;
+use crate::bots::check_or_fold;
+use crate::cards::{Card, HoleCards};
+use crate::clock::{Deadline, DecisionBudget};
+use crate::config::RunnerConfig;
+use crate::rng::SeededRng;
+use crate::sanitizer::Correction;
+use crate::states::{GameState, RoundState, TerminalState};
+use crate::summary::MatchSummary;
+use std::collections::HashMap;
+use std::fmt;
+use std::time::{Duration, Instant};
+
+type BoxedBot<E> = Box<dyn PokerBot<Error=E> + Send + Sync>;
+
+/// Plays `primary`, and switches to `backup` for any call where the primary fails or runs out of time.
+///
+/// On every decision the backup goes first, and its action is suggested to the deadline, so it gets played
+/// even if the primary never comes back in time. The backup should be quick. The primary then gets a share of
+/// what is left of the deadline (80% by default), and if it is still deciding once that share is up,
+/// whatever it comes back with is thrown away in favour of the backup's action.
+/// `get_action` goes by the budget from the last `handle_decision_budget` the same way, if there was one.
+/// Errors from the primary are logged and counted, errors from the backup are returned.
+pub struct Fallback<A, B> {
+    primary: A,
+    backup: B,
+    share: f64,
+    failures: u32,
+    timeouts: u32,
+    budget: Option<DecisionBudget>,
+}
+
+impl<A, B> Fallback<A, B> where A: PokerBot, B: PokerBot {
+    pub fn new(primary: A, backup: B) -> Fallback<A, B> {
+        Fallback {
+            primary,
+            backup,
+            share: 0.8,
+            failures: 0,
+            timeouts: 0,
+            budget: None,
+        }
+    }
+
+    /// Changes the share of each deadline the primary gets. Clamped to [0, 1].
+    pub fn with_share(mut self, share: f64) -> Fallback<A, B> {
+        self.share = share.clamp(0.0, 1.0);
+        self
+    }
+
+    /// How many times the primary returned an error
+    pub fn failures(&self) -> u32 {
+        self.failures
+    }
+
+    /// How many decisions the primary ran out of time on
+    pub fn timeouts(&self) -> u32 {
+        self.timeouts
+    }
+
+    pub fn into_inner(self) -> (A, B) {
+        (self.primary, self.backup)
+    }
+
+    // Notes a failure of the primary, which is never fatal
+    fn primary_failed<T>(&mut self, call: &str, result: Result<T, A::Error>) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(e) => {
+                self.failures += 1;
+                log::warn!(target: "PBBot", "Primary bot failed in {}, falling back: {}", call, e);
+                None
+            },
+        }
+    }
+}
+
+impl<A, B> PokerBot for Fallback<A, B> where A: PokerBot, B: PokerBot {
+    type Error = B::Error;
+
+    fn handle_new_round(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<(), B::Error> {
+        let result = self.primary.handle_new_round(gs, rs, player_index);
+        self.primary_failed("handle_new_round", result);
+        self.backup.handle_new_round(gs, rs, player_index)
+    }
+
+    fn handle_round_over(&mut self, gs: &GameState, ts: &TerminalState, player_index: usize) -> Result<(), B::Error> {
+        let result = self.primary.handle_round_over(gs, ts, player_index);
+        self.primary_failed("handle_round_over", result);
+        self.backup.handle_round_over(gs, ts, player_index)
+    }
+
+    fn get_action(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<Action, B::Error> {
+        if let Some(budget) = self.budget.take() {
+            return self.get_action_by(gs, rs, player_index, &Deadline::new(budget));
+        }
+        let result = self.primary.get_action(gs, rs, player_index);
+        match self.primary_failed("get_action", result) {
+            Some(action) => Ok(action),
+            None => self.backup.get_action(gs, rs, player_index),
+        }
+    }
+
+    fn handle_decision_budget(&mut self, gs: &GameState, budget: &DecisionBudget) -> Result<(), B::Error> {
+        self.budget = Some(*budget);
+        let result = self.primary.handle_decision_budget(gs, &DecisionBudget::new(budget.remaining().mul_f64(self.share)));
+        self.primary_failed("handle_decision_budget", result);
+        self.backup.handle_decision_budget(gs, budget)
+    }
+
+    fn get_action_by(&mut self, gs: &GameState, rs: &RoundState, player_index: usize, deadline: &Deadline) -> Result<Action, B::Error> {
+        self.budget = None;
+        let backup = self.backup.get_action_by(gs, rs, player_index, deadline);
+        if let Ok(action) = backup {
+            deadline.suggest(action);
+        }
+        let primary_deadline = Deadline::new(DecisionBudget::new(deadline.remaining().mul_f64(self.share)));
+        let result = self.primary.get_action_by(gs, rs, player_index, &primary_deadline);
+        if let Some(action) = self.primary_failed("get_action_by", result) {
+            if !primary_deadline.expired() {
+                return Ok(action);
+            }
+            self.timeouts += 1;
+            log::warn!(target: "PBBot", "Primary bot overran its {:?} share of the deadline, falling back", primary_deadline.budget().budget());
+        }
+        backup
+    }
+
+    fn handle_correction(&mut self, gs: &GameState, rs: &RoundState, correction: &Correction) -> Result<(), B::Error> {
+        let result = self.primary.handle_correction(gs, rs, correction);
+        self.primary_failed("handle_correction", result);
+        self.backup.handle_correction(gs, rs, correction)
+    }
+
+    fn handle_game_start(&mut self, player_index: usize, config: &RunnerConfig) -> Result<(), B::Error> {
+        let result = self.primary.handle_game_start(player_index, config);
+        self.primary_failed("handle_game_start", result);
+        self.backup.handle_game_start(player_index, config)
+    }
+
+    fn handle_game_end(&mut self, gs: &GameState, summary: &MatchSummary) -> Result<(), B::Error> {
+        let result = self.primary.handle_game_end(gs, summary);
+        self.primary_failed("handle_game_end", result);
+        self.backup.handle_game_end(gs, summary)
+    }
+
+    fn handle_opponent_action(&mut self, gs: &GameState, rs: &RoundState, action: Action, player_index: usize) -> Result<(), B::Error> {
+        let result = self.primary.handle_opponent_action(gs, rs, action, player_index);
+        self.primary_failed("handle_opponent_action", result);
+        self.backup.handle_opponent_action(gs, rs, action, player_index)
+    }
+
+    fn handle_reveal(&mut self, gs: &GameState, hand: &HoleCards, player_index: usize) -> Result<(), B::Error> {
+        let result = self.primary.handle_reveal(gs, hand, player_index);
+        self.primary_failed("handle_reveal", result);
+        self.backup.handle_reveal(gs, hand, player_index)
+    }
+}
+
+/// Picks one of several bots at the start of every round, with the given weights,
+/// and lets it play the whole round. All the bots hear about everything that happens.
+pub struct Mixed<E> {
+    bots: Vec<(f64, BoxedBot<E>)>,
+    rng: SeededRng,
+    current: usize,
+}
+
+impl<E> Mixed<E> {
+    /// Mixes the bots with the given weights, which don't need to add up to 1
+    pub fn new(bots: Vec<(f64, BoxedBot<E>)>, seed: u64) -> Mixed<E> {
+        assert!(!bots.is_empty(), "Expected at least one bot to mix");
+        assert!(bots.iter().all(|(weight, _)| *weight >= 0.0) && bots.iter().any(|(weight, _)| *weight > 0.0),
+            "Expected non-negative weights, with at least one positive");
+        Mixed {
+            bots,
+            rng: SeededRng::new(seed),
+            current: 0,
+        }
+    }
+
+    /// Index of the bot playing the current round
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    fn pick(&mut self) {
+        let total: f64 = self.bots.iter().map(|(weight, _)| weight).sum();
+        let mut roll = self.rng.next_f64() * total;
+        self.current = self.bots.iter().position(|(weight, _)| {
+            roll -= weight;
+            roll < 0.0
+        }).unwrap_or_else(|| self.bots.iter().rposition(|(weight, _)| *weight > 0.0).unwrap_or(0));
+    }
+
+    fn each<F>(&mut self, mut call: F) -> Result<(), E> where F: FnMut(&mut BoxedBot<E>) -> Result<(), E> {
+        for (_, bot) in self.bots.iter_mut() {
+            call(bot)?;
+        }
+        Ok(())
+    }
+}
+
+impl<E: std::error::Error> PokerBot for Mixed<E> {
+    type Error = E;
+
+    fn handle_new_round(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<(), E> {
+        self.pick();
+        self.each(|bot| bot.handle_new_round(gs, rs, player_index))
+    }
+
+    fn handle_round_over(&mut self, gs: &GameState, ts: &TerminalState, player_index: usize) -> Result<(), E> {
+        self.each(|bot| bot.handle_round_over(gs, ts, player_index))
+    }
+
+    fn get_action(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<Action, E> {
+        self.bots[self.current].1.get_action(gs, rs, player_index)
+    }
+
+    fn handle_decision_budget(&mut self, gs: &GameState, budget: &DecisionBudget) -> Result<(), E> {
+        self.bots[self.current].1.handle_decision_budget(gs, budget)
+    }
+
+    fn get_action_by(&mut self, gs: &GameState, rs: &RoundState, player_index: usize, deadline: &Deadline) -> Result<Action, E> {
+        self.bots[self.current].1.get_action_by(gs, rs, player_index, deadline)
+    }
+
+    fn handle_correction(&mut self, gs: &GameState, rs: &RoundState, correction: &Correction) -> Result<(), E> {
+        self.bots[self.current].1.handle_correction(gs, rs, correction)
+    }
+
+    fn handle_game_start(&mut self, player_index: usize, config: &RunnerConfig) -> Result<(), E> {
+        self.each(|bot| bot.handle_game_start(player_index, config))
+    }
+
+    fn handle_game_end(&mut self, gs: &GameState, summary: &MatchSummary) -> Result<(), E> {
+        self.each(|bot| bot.handle_game_end(gs, summary))
+    }
+
+    fn handle_opponent_action(&mut self, gs: &GameState, rs: &RoundState, action: Action, player_index: usize) -> Result<(), E> {
+        self.each(|bot| bot.handle_opponent_action(gs, rs, action, player_index))
+    }
+
+    fn handle_reveal(&mut self, gs: &GameState, hand: &HoleCards, player_index: usize) -> Result<(), E> {
+        self.each(|bot| bot.handle_reveal(gs, hand, player_index))
+    }
+}
+
+/// Everything that identifies a decision point within a round, as far as we can see it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Situation {
+    pub hand: Option<HoleCards>,
+    pub board: Vec<Card>,
+    pub street: u32,
+    pub button: u32,
+    pub pips: [u32; 2],
+    pub stacks: [u32; 2],
+}
+
+impl Situation {
+    pub fn new(rs: &RoundState, player_index: usize) -> Situation {
+        Situation {
+            hand: rs.hands[player_index],
+            board: rs.deck.0.clone(),
+            street: rs.street,
+            button: rs.button,
+            pips: rs.pips,
+            stacks: rs.stacks,
+        }
+    }
+}
+
+/// A call made to a `Logging` bot
+#[derive(Debug, Clone)]
+pub struct LoggedCall {
+    pub method: &'static str,
+    pub round_num: u32,
+    /// Where the bot was asked to decide, for the calls that ask for an action
+    pub situation: Option<Situation>,
+    /// The action returned, for the calls that return one, or the error
+    pub outcome: Result<Option<Action>, String>,
+    pub elapsed: Duration,
+}
+
+impl fmt::Display for LoggedCall {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "round {} {} took {:?}: ", self.round_num, self.method, self.elapsed)?;
+        match self.outcome {
+            Ok(Some(action)) => write!(fmt, "{:?}", action),
+            Ok(None) => write!(fmt, "ok"),
+            Err(ref e) => write!(fmt, "error {}", e),
+        }
+    }
+}
+
+/// Records every call made to the bot, along with what came back and how long it took.
+/// Calls are also logged at trace level under "PBBot".
+pub struct Logging<B> {
+    inner: B,
+    calls: Vec<LoggedCall>,
+    round_num: u32,
+}
+
+impl<B: PokerBot> Logging<B> {
+    pub fn new(inner: B) -> Logging<B> {
+        Logging {
+            inner,
+            calls: vec![],
+            round_num: 0,
+        }
+    }
+
+    pub fn calls(&self) -> &[LoggedCall] {
+        &self.calls
+    }
+
+    /// Takes the calls recorded so far, leaving the log empty
+    pub fn take_calls(&mut self) -> Vec<LoggedCall> {
+        std::mem::take(&mut self.calls)
+    }
+
+    /// Every decision the bot made without failing, in order
+    pub fn decisions(&self) -> impl Iterator<Item=(&Situation, Action)> {
+        self.calls.iter().filter_map(|call| match (&call.situation, &call.outcome) {
+            (Some(situation), Ok(Some(action))) => Some((situation, *action)),
+            _ => None,
+        })
+    }
+
+    /// Freezes the decisions made so far into a bot that plays them back
+    pub fn freeze(self) -> Frozen<B> {
+        let decisions = self.decisions().map(|(situation, action)| (situation.clone(), action)).collect();
+        Frozen::new(self.inner, decisions)
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn record<T, F>(&mut self, method: &'static str, situation: Option<Situation>, call: F) -> Result<T, B::Error>
+        where F: FnOnce(&mut B) -> Result<T, B::Error>, T: Outcome {
+        let started = Instant::now();
+        let result = call(&mut self.inner);
+        let logged = LoggedCall {
+            method,
+            round_num: self.round_num,
+            situation,
+            outcome: match result {
+                Ok(ref value) => Ok(value.action()),
+                Err(ref e) => Err(e.to_string()),
+            },
+            elapsed: started.elapsed(),
+        };
+        log::trace!(target: "PBBot", "{}", logged);
+        self.calls.push(logged);
+        result
+    }
+}
+
+/// What a bot call returned, as far as the log cares
+trait Outcome {
+    fn action(&self) -> Option<Action>;
+}
+
+impl Outcome for () {
+    fn action(&self) -> Option<Action> {
+        None
+    }
+}
+
+impl Outcome for Action {
+    fn action(&self) -> Option<Action> {
+        Some(*self)
+    }
+}
+
+impl<B: PokerBot> PokerBot for Logging<B> {
+    type Error = B::Error;
+
+    fn handle_new_round(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<(), B::Error> {
+        self.round_num = gs.round_num;
+        self.record("handle_new_round", None, |bot| bot.handle_new_round(gs, rs, player_index))
+    }
+
+    fn handle_round_over(&mut self, gs: &GameState, ts: &TerminalState, player_index: usize) -> Result<(), B::Error> {
+        self.record("handle_round_over", None, |bot| bot.handle_round_over(gs, ts, player_index))
+    }
+
+    fn get_action(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<Action, B::Error> {
+        self.record("get_action", Some(Situation::new(rs, player_index)), |bot| bot.get_action(gs, rs, player_index))
+    }
+
+    fn handle_decision_budget(&mut self, gs: &GameState, budget: &DecisionBudget) -> Result<(), B::Error> {
+        self.record("handle_decision_budget", None, |bot| bot.handle_decision_budget(gs, budget))
+    }
+
+    fn get_action_by(&mut self, gs: &GameState, rs: &RoundState, player_index: usize, deadline: &Deadline) -> Result<Action, B::Error> {
+        self.record("get_action_by", Some(Situation::new(rs, player_index)), |bot| bot.get_action_by(gs, rs, player_index, deadline))
+    }
+
+    fn handle_correction(&mut self, gs: &GameState, rs: &RoundState, correction: &Correction) -> Result<(), B::Error> {
+        self.record("handle_correction", None, |bot| bot.handle_correction(gs, rs, correction))
+    }
+
+    fn handle_game_start(&mut self, player_index: usize, config: &RunnerConfig) -> Result<(), B::Error> {
+        self.record("handle_game_start", None, |bot| bot.handle_game_start(player_index, config))
+    }
+
+    fn handle_game_end(&mut self, gs: &GameState, summary: &MatchSummary) -> Result<(), B::Error> {
+        self.record("handle_game_end", None, |bot| bot.handle_game_end(gs, summary))
+    }
+
+    fn handle_opponent_action(&mut self, gs: &GameState, rs: &RoundState, action: Action, player_index: usize) -> Result<(), B::Error> {
+        self.record("handle_opponent_action", None, |bot| bot.handle_opponent_action(gs, rs, action, player_index))
+    }
+
+    fn handle_reveal(&mut self, gs: &GameState, hand: &HoleCards, player_index: usize) -> Result<(), B::Error> {
+        self.record("handle_reveal", None, |bot| bot.handle_reveal(gs, hand, player_index))
+    }
+}
+
+/// Plays back recorded decisions, and never asks the wrapped bot for an action.
+///
+/// Situations that were never recorded are checked if that is free, and folded otherwise.
+/// The wrapped bot still hears about everything that happens, so it can keep its own books.
+pub struct Frozen<B> {
+    inner: B,
+    decisions: HashMap<Situation, Action>,
+    misses: u32,
+}
+
+impl<B: PokerBot> Frozen<B> {
+    /// Later decisions for the same situation replace earlier ones
+    pub fn new(inner: B, decisions: Vec<(Situation, Action)>) -> Frozen<B> {
+        Frozen {
+            inner,
+            decisions: decisions.into_iter().collect(),
+            misses: 0,
+        }
+    }
+
+    /// Number of decisions that had nothing recorded
+    pub fn misses(&self) -> u32 {
+        self.misses
+    }
+
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+
+    fn recall(&mut self, rs: &RoundState, player_index: usize) -> Action {
+        match self.decisions.get(&Situation::new(rs, player_index)) {
+            Some(action) => *action,
+            None => {
+                self.misses += 1;
+                check_or_fold(rs)
+            },
+        }
+    }
+}
+
+impl<B: PokerBot> PokerBot for Frozen<B> {
+    type Error = B::Error;
+
+    fn handle_new_round(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<(), B::Error> {
+        self.inner.handle_new_round(gs, rs, player_index)
+    }
+
+    fn handle_round_over(&mut self, gs: &GameState, ts: &TerminalState, player_index: usize) -> Result<(), B::Error> {
+        self.inner.handle_round_over(gs, ts, player_index)
+    }
+
+    fn get_action(&mut self, _gs: &GameState, rs: &RoundState, player_index: usize) -> Result<Action, B::Error> {
+        Ok(self.recall(rs, player_index))
+    }
+
+    fn get_action_by(&mut self, _gs: &GameState, rs: &RoundState, player_index: usize, _deadline: &Deadline) -> Result<Action, B::Error> {
+        Ok(self.recall(rs, player_index))
+    }
+
+    fn handle_correction(&mut self, gs: &GameState, rs: &RoundState, correction: &Correction) -> Result<(), B::Error> {
+        self.inner.handle_correction(gs, rs, correction)
+    }
+
+    fn handle_game_start(&mut self, player_index: usize, config: &RunnerConfig) -> Result<(), B::Error> {
+        self.inner.handle_game_start(player_index, config)
+    }
+
+    fn handle_game_end(&mut self, gs: &GameState, summary: &MatchSummary) -> Result<(), B::Error> {
+        self.inner.handle_game_end(gs, summary)
+    }
+
+    fn handle_opponent_action(&mut self, gs: &GameState, rs: &RoundState, action: Action, player_index: usize) -> Result<(), B::Error> {
+        self.inner.handle_opponent_action(gs, rs, action, player_index)
+    }
+
+    fn handle_reveal(&mut self, gs: &GameState, hand: &HoleCards, player_index: usize) -> Result<(), B::Error> {
+        self.inner.handle_reveal(gs, hand, player_index)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::CardDeck;
+    use crate::states::{BIG_BLIND, SMALL_BLIND, STARTING_STACK};
+    use std::sync::{Arc, atomic::{AtomicU32, Ordering}};
+    use std::thread;
+
+    /// Answers with a fixed action after a while, or fails, and counts the rounds it started and finished
+    struct Fixed {
+        action: Option<Action>,
+        delay: Duration,
+        rounds: Arc<AtomicU32>,
+    }
+
+    impl Fixed {
+        fn new(action: Action, delay_ms: u64) -> Fixed {
+            Fixed { action: Some(action), delay: Duration::from_millis(delay_ms), rounds: Arc::default() }
+        }
+
+        fn failing() -> Fixed {
+            Fixed { action: None, delay: Duration::default(), rounds: Arc::default() }
+        }
+    }
+
+    impl PokerBot for Fixed {
+        type Error = fmt::Error;
+
+        fn handle_new_round(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<(), fmt::Error> {
+            self.rounds.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn handle_round_over(&mut self, _gs: &GameState, _ts: &TerminalState, _player_index: usize) -> Result<(), fmt::Error> {
+            self.rounds.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+
+        fn get_action(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<Action, fmt::Error> {
+            thread::sleep(self.delay);
+            self.action.ok_or(fmt::Error)
+        }
+    }
+
+    fn states() -> (GameState, RoundState) {
+        let gs = GameState { bankroll: 0, game_clock: 30.0, round_num: 1 };
+        let rs = RoundState {
+            button: 0,
+            street: 0,
+            pips: [SMALL_BLIND, BIG_BLIND],
+            stacks: [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [None, None],
+            deck: CardDeck(vec![]),
+            previous: None,
+        };
+        (gs, rs)
+    }
+
+    fn deadline(ms: u64) -> Deadline {
+        Deadline::new(DecisionBudget::new(Duration::from_millis(ms)))
+    }
+
+    #[test]
+    fn primary_decides_in_time() {
+        let (gs, rs) = states();
+        let mut bot = Fallback::new(Fixed::new(Action::Raise(6), 0), Fixed::new(Action::Call, 0));
+        assert_eq!(bot.get_action_by(&gs, &rs, 0, &deadline(1000)).unwrap(), Action::Raise(6));
+        assert_eq!((bot.failures(), bot.timeouts()), (0, 0));
+    }
+
+    #[test]
+    fn backup_covers_a_failing_primary() {
+        let (gs, rs) = states();
+        let mut bot = Fallback::new(Fixed::failing(), Fixed::new(Action::Call, 0));
+        assert_eq!(bot.get_action_by(&gs, &rs, 0, &deadline(1000)).unwrap(), Action::Call);
+        assert_eq!(bot.get_action(&gs, &rs, 0).unwrap(), Action::Call);
+        assert_eq!(bot.failures(), 2);
+    }
+
+    #[test]
+    fn backup_is_suggested_before_the_primary_runs() {
+        let (gs, rs) = states();
+        let mut bot = Fallback::new(Fixed::new(Action::Raise(6), 60), Fixed::new(Action::Call, 0));
+        let deadline = deadline(20);
+        assert_eq!(bot.get_action_by(&gs, &rs, 0, &deadline).unwrap(), Action::Call);
+        // What the runner plays if the primary is still going when the deadline passes
+        assert_eq!(deadline.best(), Some(Action::Call));
+        assert_eq!(bot.timeouts(), 1);
+    }
+
+    #[test]
+    fn get_action_goes_by_the_last_budget() {
+        let (gs, rs) = states();
+        let mut bot = Fallback::new(Fixed::new(Action::Raise(6), 60), Fixed::new(Action::Call, 0));
+        bot.handle_decision_budget(&gs, &DecisionBudget::new(Duration::from_millis(20))).unwrap();
+        assert_eq!(bot.get_action(&gs, &rs, 0).unwrap(), Action::Call);
+        assert_eq!(bot.timeouts(), 1);
+        // The budget was for that decision only
+        assert_eq!(bot.get_action(&gs, &rs, 0).unwrap(), Action::Raise(6));
+        assert_eq!(bot.timeouts(), 1);
+    }
+
+    /// Plays rounds of a single decision each, and returns which bot was picked for each round
+    fn mix(bot: &mut Mixed<fmt::Error>, rounds: u32) -> Vec<usize> {
+        let (gs, rs) = states();
+        let ts = TerminalState { deltas: [0, 0], previous: rs.clone() };
+        (0..rounds).map(|_| {
+            bot.handle_new_round(&gs, &rs, 0).unwrap();
+            let picked = bot.current();
+            let expected = if picked == 0 { Action::Raise(6) } else { Action::Call };
+            assert_eq!(bot.get_action(&gs, &rs, 0).unwrap(), expected);
+            bot.handle_round_over(&gs, &ts, 0).unwrap();
+            picked
+        }).collect()
+    }
+
+    #[test]
+    fn mixed_picks_by_weight_and_keeps_everyone_informed() {
+        let raiser = Fixed::new(Action::Raise(6), 0);
+        let caller = Fixed::new(Action::Call, 0);
+        let counts = [raiser.rounds.clone(), caller.rounds.clone()];
+        let mut bot: Mixed<fmt::Error> = Mixed::new(vec![(3.0, Box::new(raiser)), (1.0, Box::new(caller))], 11);
+        let picks = mix(&mut bot, 1000);
+        let raised = picks.iter().filter(|&&picked| picked == 0).count();
+        assert!((700..=800).contains(&raised), "raiser played {} of 1000 rounds", raised);
+        // Both bots start and finish every round, whoever plays it
+        assert_eq!(counts[0].load(Ordering::SeqCst), 2000);
+        assert_eq!(counts[1].load(Ordering::SeqCst), 2000);
+
+        let mut again: Mixed<fmt::Error> = Mixed::new(vec![(3.0, Box::new(Fixed::new(Action::Raise(6), 0))), (1.0, Box::new(Fixed::new(Action::Call, 0)))], 11);
+        assert_eq!(mix(&mut again, 1000), picks);
+    }
+
+    #[test]
+    fn logging_records_calls_in_order() {
+        let (gs, rs) = states();
+        let ts = TerminalState { deltas: [0, 0], previous: rs.clone() };
+        let mut bot = Logging::new(Fixed::new(Action::Raise(6), 0));
+        bot.handle_game_start(0, &RunnerConfig::default()).unwrap();
+        bot.handle_new_round(&gs, &rs, 0).unwrap();
+        assert_eq!(bot.get_action(&gs, &rs, 0).unwrap(), Action::Raise(6));
+        bot.handle_round_over(&gs, &ts, 0).unwrap();
+
+        let calls = bot.calls();
+        let methods: Vec<&str> = calls.iter().map(|call| call.method).collect();
+        assert_eq!(methods, ["handle_game_start", "handle_new_round", "get_action", "handle_round_over"]);
+        assert_eq!(calls.iter().map(|call| call.round_num).collect::<Vec<_>>(), [0, 1, 1, 1]);
+        assert_eq!(calls[2].outcome, Ok(Some(Action::Raise(6))));
+        assert_eq!(calls[2].situation, Some(Situation::new(&rs, 0)));
+        assert!(calls.iter().filter(|call| call.method != "get_action").all(|call| call.outcome == Ok(None) && call.situation.is_none()));
+
+        let mut failing = Logging::new(Fixed::failing());
+        assert!(failing.get_action(&gs, &rs, 0).is_err());
+        assert!(failing.calls()[0].outcome.is_err());
+        assert_eq!(failing.decisions().count(), 0);
+    }
+
+    #[test]
+    fn frozen_plays_back_recorded_decisions() {
+        let (gs, rs) = states();
+        let mut logged = Logging::new(Fixed::new(Action::Raise(6), 0));
+        logged.get_action(&gs, &rs, 0).unwrap();
+        let mut bot = logged.freeze();
+        assert_eq!(bot.get_action(&gs, &rs, 0).unwrap(), Action::Raise(6));
+        assert_eq!(bot.get_action_by(&gs, &rs, 0, &deadline(1000)).unwrap(), Action::Raise(6));
+        assert_eq!(bot.misses(), 0);
+
+        // Nothing was recorded after a limp, so the bot checks where the inner bot would have raised
+        let limped = RoundState { button: 1, pips: [BIG_BLIND, BIG_BLIND], stacks: [STARTING_STACK - BIG_BLIND; 2], ..rs.clone() };
+        assert_eq!(bot.get_action(&gs, &limped, 1).unwrap(), Action::Check);
+        // and folds when checking isn't free
+        let raised = RoundState { button: 1, pips: [6, BIG_BLIND], stacks: [STARTING_STACK - 6, STARTING_STACK - BIG_BLIND], ..rs };
+        assert_eq!(bot.get_action(&gs, &raised, 1).unwrap(), Action::Fold);
+        assert_eq!(bot.misses(), 2);
+    }
+}
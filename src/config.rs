@@ -0,0 +1,389 @@
+//! Settings for the runner
+
+use super::cards::MAX_HOLE_CARDS;
+use super::clock::TimeManager;
+use super::sanitizer::{ActionSanitizer, PassiveFallback};
+use super::states::{GameState, RoundState, NUM_ROUNDS};
+use super::summary::EndReason;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use std::fmt;
+use std::error::Error;
+use approx::relative_eq;
+use log::LevelFilter;
+
+/// Predicate deciding whether the game is over
+pub type EndPredicate = Box<dyn Fn(&GameState, Option<&RoundState>) -> bool + Send + Sync>;
+
+/// Decides when the runner stops playing, besides the engine telling it to quit
+pub enum GameEnd {
+    /// Only stop when the engine sends `Q`
+    Quit,
+    /// Stop once the given number of rounds are over, or the game clock runs out
+    RoundCap(u32),
+    /// Stop when the predicate says so. It is checked between updates from the engine.
+    Custom(EndPredicate),
+}
+
+impl GameEnd {
+    pub fn is_over(&self, gs: &GameState, rs: Option<&RoundState>) -> bool {
+        self.reason(gs, rs).is_some()
+    }
+
+    /// Why the game is over, or `None` if it is not
+    pub fn reason(&self, gs: &GameState, rs: Option<&RoundState>) -> Option<EndReason> {
+        match self {
+            GameEnd::Quit => None,
+            GameEnd::RoundCap(_) if relative_eq!(gs.game_clock, 0.0, epsilon = 0.001) && gs.round_num > 1 => Some(EndReason::GameClock),
+            GameEnd::RoundCap(rounds) if gs.round_num > *rounds && rs.is_none() => Some(EndReason::RoundCap),
+            GameEnd::RoundCap(_) => None,
+            GameEnd::Custom(predicate) if predicate(gs, rs) => Some(EndReason::Custom),
+            GameEnd::Custom(_) => None,
+        }
+    }
+}
+
+impl fmt::Debug for GameEnd {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameEnd::Quit => write!(fmt, "Quit"),
+            GameEnd::RoundCap(rounds) => write!(fmt, "RoundCap({})", rounds),
+            GameEnd::Custom(_) => write!(fmt, "Custom(..)"),
+        }
+    }
+}
+
+/// The kinds of faults the runner can run into while playing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ErrorClass {
+    /// Reading from or writing to the engine failed
+    Io,
+    /// The engine sent something we could not make sense of
+    Protocol,
+    /// The bot returned an error, an illegal action, or panicked
+    Bot,
+    /// Shared runner state was poisoned by a panicking thread
+    Poisoned,
+}
+
+impl ErrorClass {
+    fn index(self) -> usize {
+        match self {
+            ErrorClass::Io => 0,
+            ErrorClass::Protocol => 1,
+            ErrorClass::Bot => 2,
+            ErrorClass::Poisoned => 3,
+        }
+    }
+}
+
+/// What the runner does when it runs into a fault of some class
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    /// Stop the match and return the error from `run_bot`
+    Abort,
+    /// Try the failed step again up to this many times, then abort.
+    /// Protocol and poisoned faults fail the same way every time, so for them this is the same as aborting.
+    Retry(u32),
+    /// Carry on as well as we can: the bot checks or folds, bad engine clauses are skipped,
+    /// poisoned state is used as is, and I/O faults end the match with what we have so far
+    Fallback,
+}
+
+/// Settings for a runner. Use `RunnerConfig::builder` to make one.
+pub struct RunnerConfig {
+    connect_timeout: Duration,
+    write_timeout: Duration,
+    time_limit: Option<Duration>,
+    game_end: GameEnd,
+    time_manager: TimeManager,
+    sanitizer: Arc<dyn ActionSanitizer>,
+    session: Option<PathBuf>,
+    log_level: LevelFilter,
+    error_policies: [ErrorPolicy; 4],
+    hole_cards: usize,
+}
+
+impl RunnerConfig {
+    pub fn builder() -> RunnerConfigBuilder {
+        RunnerConfigBuilder::default()
+    }
+
+    pub fn connect_timeout(&self) -> Duration {
+        self.connect_timeout
+    }
+
+    pub fn write_timeout(&self) -> Duration {
+        self.write_timeout
+    }
+
+    pub fn time_limit(&self) -> Option<Duration> {
+        self.time_limit
+    }
+
+    pub fn game_end(&self) -> &GameEnd {
+        &self.game_end
+    }
+
+    pub fn time_manager(&self) -> &TimeManager {
+        &self.time_manager
+    }
+
+    pub fn sanitizer(&self) -> &Arc<dyn ActionSanitizer> {
+        &self.sanitizer
+    }
+
+    pub fn session(&self) -> Option<&Path> {
+        self.session.as_deref()
+    }
+
+    pub fn log_level(&self) -> LevelFilter {
+        self.log_level
+    }
+
+    pub fn error_policy(&self, class: ErrorClass) -> ErrorPolicy {
+        self.error_policies[class.index()]
+    }
+
+    pub fn hole_cards(&self) -> usize {
+        self.hole_cards
+    }
+}
+
+impl Default for RunnerConfig {
+    fn default() -> RunnerConfig {
+        RunnerConfigBuilder::default().build().expect("Default runner config is valid")
+    }
+}
+
+impl fmt::Debug for RunnerConfig {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RunnerConfig")
+            .field("connect_timeout", &self.connect_timeout)
+            .field("write_timeout", &self.write_timeout)
+            .field("time_limit", &self.time_limit)
+            .field("game_end", &self.game_end)
+            .field("time_manager", &self.time_manager)
+            .field("session", &self.session)
+            .field("log_level", &self.log_level)
+            .field("error_policies", &self.error_policies)
+            .field("hole_cards", &self.hole_cards)
+            .finish()
+    }
+}
+
+/// Builds a `RunnerConfig`, checking the values once `build` is called
+pub struct RunnerConfigBuilder {
+    connect_timeout: Duration,
+    write_timeout: Duration,
+    time_limit: Option<Duration>,
+    game_end: GameEnd,
+    time_manager: TimeManager,
+    sanitizer: Arc<dyn ActionSanitizer>,
+    session: Option<PathBuf>,
+    log_level: LevelFilter,
+    error_policies: [ErrorPolicy; 4],
+    hole_cards: usize,
+}
+
+impl Default for RunnerConfigBuilder {
+    fn default() -> RunnerConfigBuilder {
+        RunnerConfigBuilder {
+            connect_timeout: Duration::from_secs(10),
+            write_timeout: Duration::from_secs(1),
+            time_limit: Some(Duration::from_secs(60)),
+            game_end: GameEnd::RoundCap(NUM_ROUNDS),
+            time_manager: TimeManager::default(),
+            sanitizer: Arc::new(PassiveFallback),
+            session: None,
+            log_level: LevelFilter::Trace,
+            // Indexed by ErrorClass: io, protocol, bot, poisoned
+            error_policies: [ErrorPolicy::Retry(10), ErrorPolicy::Abort, ErrorPolicy::Fallback, ErrorPolicy::Abort],
+            hole_cards: 2,
+        }
+    }
+}
+
+impl RunnerConfigBuilder {
+    /// How long to wait for the engine to accept the connection
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.connect_timeout = timeout;
+        self
+    }
+
+    /// How long a single write to the engine may block
+    pub fn write_timeout(mut self, timeout: Duration) -> Self {
+        self.write_timeout = timeout;
+        self
+    }
+
+    /// Wall clock time after which the runner stops, whatever the engine says. `None` for no limit.
+    pub fn time_limit(mut self, limit: Option<Duration>) -> Self {
+        self.time_limit = limit;
+        self
+    }
+
+    pub fn game_end(mut self, game_end: GameEnd) -> Self {
+        self.game_end = game_end;
+        self
+    }
+
+    pub fn time_manager(mut self, time_manager: TimeManager) -> Self {
+        self.time_manager = time_manager;
+        self
+    }
+
+    /// What to do with illegal actions returned by the bot
+    pub fn sanitizer<S: ActionSanitizer + 'static>(mut self, sanitizer: S) -> Self {
+        self.sanitizer = Arc::new(sanitizer);
+        self
+    }
+
+    /// Tee everything sent to and received from the engine into a session file
+    pub fn record_session<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.session = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Most verbose level the runner logs its own messages at
+    pub fn log_level(mut self, level: LevelFilter) -> Self {
+        self.log_level = level;
+        self
+    }
+
+    /// What to do about faults of the given class
+    pub fn error_policy(mut self, class: ErrorClass, policy: ErrorPolicy) -> Self {
+        self.error_policies[class.index()] = policy;
+        self
+    }
+
+    /// How many cards the engine deals each player, like 4 for Omaha. Hands with any other number are a protocol error.
+    pub fn hole_cards(mut self, count: usize) -> Self {
+        self.hole_cards = count;
+        self
+    }
+
+    pub fn build(self) -> Result<RunnerConfig, ConfigError> {
+        if self.connect_timeout == Duration::from_secs(0) {
+            return Err(ConfigError::ZeroDuration("connect_timeout"));
+        }
+        if self.write_timeout == Duration::from_secs(0) {
+            return Err(ConfigError::ZeroDuration("write_timeout"));
+        }
+        if self.time_limit == Some(Duration::from_secs(0)) {
+            return Err(ConfigError::ZeroDuration("time_limit"));
+        }
+        if self.time_manager.decisions_per_round == 0 {
+            return Err(ConfigError::NoDecisions);
+        }
+        if self.hole_cards == 0 || self.hole_cards > MAX_HOLE_CARDS {
+            return Err(ConfigError::HoleCards(self.hole_cards));
+        }
+        Ok(RunnerConfig {
+            connect_timeout: self.connect_timeout,
+            write_timeout: self.write_timeout,
+            time_limit: self.time_limit,
+            game_end: self.game_end,
+            time_manager: self.time_manager,
+            sanitizer: self.sanitizer,
+            session: self.session,
+            log_level: self.log_level,
+            error_policies: self.error_policies,
+            hole_cards: self.hole_cards,
+        })
+    }
+}
+
+/// Describes the ways a runner config can be invalid
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConfigError {
+    ZeroDuration(&'static str),
+    NoDecisions,
+    HoleCards(usize),
+}
+
+impl Error for ConfigError {}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::ZeroDuration(setting) => write!(fmt, "{} must be longer than zero", setting),
+            ConfigError::NoDecisions => write!(fmt, "The time manager must expect at least one decision per round"),
+            ConfigError::HoleCards(count) => write!(fmt, "Players can't be dealt {} hole cards (at most {})", count, MAX_HOLE_CARDS),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn game_state(round_num: u32, game_clock: f32) -> GameState {
+        GameState { bankroll: 0, game_clock, round_num }
+    }
+
+    #[test]
+    fn defaults_are_valid() {
+        let config = RunnerConfig::default();
+        assert_eq!(config.hole_cards(), 2);
+        assert_eq!(config.time_limit(), Some(Duration::from_secs(60)));
+        assert_eq!(config.error_policy(ErrorClass::Io), ErrorPolicy::Retry(10));
+        assert_eq!(config.error_policy(ErrorClass::Protocol), ErrorPolicy::Abort);
+        assert_eq!(config.error_policy(ErrorClass::Bot), ErrorPolicy::Fallback);
+        assert_eq!(config.error_policy(ErrorClass::Poisoned), ErrorPolicy::Abort);
+        assert!(config.session().is_none());
+    }
+
+    #[test]
+    fn zero_values_are_rejected() {
+        assert_eq!(
+            RunnerConfig::builder().connect_timeout(Duration::ZERO).build().unwrap_err(),
+            ConfigError::ZeroDuration("connect_timeout")
+        );
+        assert_eq!(
+            RunnerConfig::builder().write_timeout(Duration::ZERO).build().unwrap_err(),
+            ConfigError::ZeroDuration("write_timeout")
+        );
+        assert_eq!(
+            RunnerConfig::builder().time_limit(Some(Duration::ZERO)).build().unwrap_err(),
+            ConfigError::ZeroDuration("time_limit")
+        );
+        assert!(RunnerConfig::builder().time_limit(None).build().is_ok());
+        let time_manager = TimeManager { decisions_per_round: 0, ..TimeManager::default() };
+        assert_eq!(RunnerConfig::builder().time_manager(time_manager).build().unwrap_err(), ConfigError::NoDecisions);
+    }
+
+    #[test]
+    fn hole_cards_are_checked() {
+        assert_eq!(RunnerConfig::builder().hole_cards(0).build().unwrap_err(), ConfigError::HoleCards(0));
+        assert_eq!(
+            RunnerConfig::builder().hole_cards(MAX_HOLE_CARDS + 1).build().unwrap_err(),
+            ConfigError::HoleCards(MAX_HOLE_CARDS + 1)
+        );
+        assert_eq!(RunnerConfig::builder().hole_cards(4).build().unwrap().hole_cards(), 4);
+    }
+
+    #[test]
+    fn error_policies_are_per_class() {
+        let config = RunnerConfig::builder()
+            .error_policy(ErrorClass::Protocol, ErrorPolicy::Fallback)
+            .build()
+            .unwrap();
+        assert_eq!(config.error_policy(ErrorClass::Protocol), ErrorPolicy::Fallback);
+        assert_eq!(config.error_policy(ErrorClass::Io), ErrorPolicy::Retry(10));
+    }
+
+    #[test]
+    fn round_cap_ends_between_rounds() {
+        let end = GameEnd::RoundCap(10);
+        assert_eq!(end.reason(&game_state(10, 5.0), None), None);
+        assert_eq!(end.reason(&game_state(11, 5.0), None), Some(EndReason::RoundCap));
+        assert_eq!(end.reason(&game_state(5, 0.0), None), Some(EndReason::GameClock));
+        assert_eq!(end.reason(&game_state(1, 0.0), None), None);
+        assert!(!GameEnd::Quit.is_over(&game_state(5000, 0.0), None));
+        let custom = GameEnd::Custom(Box::new(|gs, _| gs.bankroll > 100));
+        assert!(custom.is_over(&GameState { bankroll: 101, game_clock: 5.0, round_num: 3 }, None));
+        assert!(!custom.is_over(&game_state(3, 5.0), None));
+    }
+}
@@ -0,0 +1,293 @@
+//! Monte Carlo hand equity, built on the `ShowdownEngine`, with a bot that goes by it
+
+use crate::actions::Action;
+use crate::bot::PokerBot;
+use crate::bots::{check_or_call, check_or_fold, continue_cost, raise_to};
+use crate::cards::{Card, CardDeck, CardValue};
+use crate::clock::Deadline;
+use crate::config::RunnerConfig;
+use crate::range::Range;
+use crate::rng::SeededRng;
+use crate::showdown::{Hand, ShowdownEngine};
+use crate::states::{GameState, RoundState, TerminalState, STARTING_STACK};
+use std::cmp::Ordering;
+use std::convert::Infallible;
+use std::fmt;
+
+/// Tally of sampled showdowns
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Equity {
+    pub wins: u32,
+    pub ties: u32,
+    pub losses: u32,
+}
+
+impl Equity {
+    pub fn samples(&self) -> u32 {
+        self.wins + self.ties + self.losses
+    }
+
+    /// Share of the pot we expect to win, counting ties as half. Without samples, this is a coin flip.
+    pub fn value(&self) -> f64 {
+        match self.samples() {
+            0 => 0.5,
+            samples => (self.wins as f64 + self.ties as f64 / 2.0) / samples as f64,
+        }
+    }
+
+    /// Adds a showdown, as seen from our side
+    pub fn add(&mut self, result: Ordering) {
+        match result {
+            Ordering::Greater => self.wins += 1,
+            Ordering::Equal => self.ties += 1,
+            Ordering::Less => self.losses += 1,
+        }
+    }
+}
+
+impl fmt::Display for Equity {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{:.1}% ({}W/{}T/{}L)", self.value() * 100.0, self.wins, self.ties, self.losses)
+    }
+}
+
+/// Compares what two sets of cards make at showdown, any five of each playing. Draws don't count for anything here,
+/// and hands that make the same thing are compared on their kickers, down to the fifth card.
+pub fn compare_showdown(engine: &ShowdownEngine, a: &[Card], b: &[Card]) -> Ordering {
+    MadeHand::any_five(engine, a).compare(engine, &MadeHand::any_five(engine, b))
+}
+
+/// Compares what two players' hole cards make with the board at showdown, under the engine's hole rule
+pub fn compare_holdings(engine: &ShowdownEngine, ours: &[Card], theirs: &[Card], board: &[Card]) -> Ordering {
+    MadeHand::of(engine, ours, board).compare(engine, &MadeHand::of(engine, theirs, board))
+}
+
+/// What a set of cards makes at showdown, worked out once so it can be compared against many others
+#[derive(Debug, Clone)]
+pub(crate) struct MadeHand {
+    hand: Option<Hand>,
+    /// The values of the cards that fill out the five beside the hand, highest first
+    kickers: Vec<CardValue>,
+}
+
+impl MadeHand {
+    /// What the hole cards make with the board, under the engine's hole rule.
+    /// With `TwoPlusThree`, the kickers come from the same two hole cards and three board cards as the hand.
+    pub(crate) fn of(engine: &ShowdownEngine, hole: &[Card], board: &[Card]) -> MadeHand {
+        engine.holdings(hole, board).iter()
+            .map(|cards| MadeHand::any_five(engine, cards))
+            .max_by(|a, b| a.compare(engine, b))
+            .expect("Every hole rule allows at least one holding")
+    }
+
+    /// What any five of the cards make
+    pub(crate) fn any_five(engine: &ShowdownEngine, cards: &[Card]) -> MadeHand {
+        let hand = engine.process_hand_with_to_come(cards, 0).showdown();
+        let used = hand.as_ref().map(Hand::cards).unwrap_or_default();
+        let mut kickers: Vec<CardValue> = cards.iter().filter(|card| !used.contains(card)).map(|card| card.value()).collect();
+        kickers.sort_by(|a, b| engine.value_order(b, a));
+        kickers.truncate(5 - used.len().min(5));
+        MadeHand { hand, kickers }
+    }
+
+    /// Compares two made hands the same way as `compare_showdown`
+    pub(crate) fn compare(&self, engine: &ShowdownEngine, other: &MadeHand) -> Ordering {
+        let hands = match (&self.hand, &other.hand) {
+            (Some(a), Some(b)) => engine.compare_hands(a, b),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        hands.then_with(|| {
+            self.kickers.iter().zip(&other.kickers)
+                .map(|(a, b)| engine.value_order(a, b))
+                .find(|order| *order != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        })
+    }
+}
+
+/// Deals the opponent a random hand (of as many cards as the engine deals) and runs out the board, then plays the showdown
+pub fn sample_showdown(engine: &ShowdownEngine, hand: &[Card], board: &[Card], rng: &mut SeededRng) -> Ordering {
+    let known: Vec<Card> = hand.iter().chain(board).copied().collect();
+    let CardDeck(mut deck) = engine.deck_without(&known);
+    let (holes, runout) = (engine.hole_cards(), 5 - board.len().min(5));
+    rng.partial_shuffle(&mut deck, holes + runout);
+    let board: Vec<Card> = board.iter().chain(&deck[holes..holes + runout]).copied().collect();
+    compare_holdings(engine, hand, &deck[..holes], &board)
+}
+
+/// Estimates the equity of a hand against a random one, from the given number of samples
+pub fn estimate(engine: &ShowdownEngine, hand: &[Card], board: &[Card], samples: u32, rng: &mut SeededRng) -> Equity {
+    let mut equity = Equity::default();
+    for _ in 0..samples {
+        equity.add(sample_showdown(engine, hand, board, rng));
+    }
+    equity
+}
+
+/// Estimates the equity of a hand against the opponent's range, from the given number of samples.
+/// Opponent hands are drawn in proportion to their weight, leaving out any that use our cards, the board
+/// or cards the variant doesn't play with. Against a range with nothing left in it, this is the same as `estimate`.
+/// Ranges only hold two card hands, so this is for games that deal two.
+pub fn estimate_vs_range(engine: &ShowdownEngine, hand: &[Card], board: &[Card], range: &Range, samples: u32, rng: &mut SeededRng) -> Equity {
+    let known: Vec<Card> = hand.iter().chain(board).copied().collect();
+    let dead: Vec<Card> = known.iter().copied().chain(engine.removed_cards()).collect();
+    let sampler = match range.sampler(&dead) {
+        Some(sampler) => sampler,
+        None => return estimate(engine, hand, board, samples, rng),
+    };
+
+    let runout = 5 - board.len().min(5);
+    let mut equity = Equity::default();
+    for _ in 0..samples {
+        let theirs = sampler.sample(rng);
+        let dealt: Vec<Card> = known.iter().chain(&theirs.0).copied().collect();
+        let CardDeck(mut deck) = engine.deck_without(&dealt);
+        rng.partial_shuffle(&mut deck, runout);
+        let board: Vec<Card> = board.iter().chain(&deck[..runout]).copied().collect();
+        equity.add(compare_holdings(engine, hand, &theirs.0, &board));
+    }
+    equity
+}
+
+/// Chips both players have put in so far
+fn pot(rs: &RoundState) -> u32 {
+    (STARTING_STACK - rs.stacks[0]) + (STARTING_STACK - rs.stacks[1])
+}
+
+/// Estimates its equity against a random hand, and bets when it is ahead.
+/// It raises pot sized above `raise_at` equity, calls when the pot odds are good enough, and folds otherwise.
+/// The sampling stops early if the decision deadline comes up.
+#[derive(Debug, Clone)]
+pub struct EquityBot {
+    engine: ShowdownEngine,
+    rng: SeededRng,
+    samples: u32,
+    raise_at: f64,
+}
+
+impl EquityBot {
+    pub fn new(engine: ShowdownEngine, seed: u64) -> EquityBot {
+        EquityBot {
+            engine,
+            rng: SeededRng::new(seed),
+            samples: 200,
+            raise_at: 0.7,
+        }
+    }
+
+    /// Changes the number of showdowns sampled per decision (200 by default)
+    pub fn with_samples(mut self, samples: u32) -> EquityBot {
+        self.samples = samples;
+        self
+    }
+
+    /// Changes the equity we need to raise (0.7 by default)
+    pub fn with_raise_at(mut self, raise_at: f64) -> EquityBot {
+        self.raise_at = raise_at;
+        self
+    }
+
+    /// What to do with the given equity
+    fn act_on(&self, rs: &RoundState, equity: &Equity) -> Action {
+        let equity = equity.value();
+        let cost = continue_cost(rs);
+        let pot = pot(rs);
+        if equity >= self.raise_at {
+            let active = rs.button as usize % 2;
+            if let Some(raise) = raise_to(rs, rs.pips[active] + cost + pot + cost) {
+                return raise;
+            }
+        }
+        if cost == 0 {
+            Action::Check
+        } else if equity >= cost as f64 / (pot + cost) as f64 {
+            Action::Call
+        } else {
+            Action::Fold
+        }
+    }
+}
+
+impl PokerBot for EquityBot {
+    type Error = Infallible;
+
+    fn handle_new_round(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    fn handle_round_over(&mut self, _gs: &GameState, _ts: &TerminalState, _player_index: usize) -> Result<(), Infallible> {
+        Ok(())
+    }
+
+    /// Deals the opponent as many hole cards as the runner expects, whatever the engine was made with
+    fn handle_game_start(&mut self, _player_index: usize, config: &RunnerConfig) -> Result<(), Infallible> {
+        self.engine = self.engine.with_hole_cards(config.hole_cards(), self.engine.hole_rule());
+        Ok(())
+    }
+
+    fn get_action(&mut self, _gs: &GameState, rs: &RoundState, player_index: usize) -> Result<Action, Infallible> {
+        let hand = match rs.hands[player_index] {
+            Some(hand) => hand,
+            None => return Ok(check_or_call(rs)),
+        };
+        let equity = estimate(&self.engine, hand.cards(), &rs.deck.0, self.samples, &mut self.rng);
+        Ok(self.act_on(rs, &equity))
+    }
+
+    fn get_action_by(&mut self, _gs: &GameState, rs: &RoundState, player_index: usize, deadline: &Deadline) -> Result<Action, Infallible> {
+        const BATCH: u32 = 16;
+        let hand = match rs.hands[player_index] {
+            Some(hand) => hand,
+            None => return Ok(check_or_call(rs)),
+        };
+        let mut equity = Equity::default();
+        deadline.suggest(check_or_fold(rs));
+        while equity.samples() < self.samples && !deadline.expired() {
+            for _ in 0..BATCH.min(self.samples - equity.samples()) {
+                equity.add(sample_showdown(&self.engine, hand.cards(), &rs.deck.0, &mut self.rng));
+            }
+            deadline.suggest(self.act_on(rs, &equity));
+        }
+        Ok(self.act_on(rs, &equity))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::showdown::HoleRule;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn kickers_break_ties() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let board = cards("Ad 9c 6h 4s 2c");
+        assert_eq!(compare_holdings(&engine, &cards("As Kh"), &cards("Ac Qh"), &board), Ordering::Greater);
+        // Both play the same five, so the lower cards don't matter
+        assert_eq!(compare_holdings(&engine, &cards("As 3h"), &cards("Ac 3d"), &board), Ordering::Equal);
+        // High cards go down to the fifth, and no further
+        assert_eq!(compare_showdown(&engine, &cards("Kh Jd 9c 6h 3s 2d"), &cards("Kd Jc 9s 5c 4d 2c")), Ordering::Greater);
+        assert_eq!(compare_showdown(&engine, &cards("Kh Jd 9c 6h 4s 3c"), &cards("Kd Jc 9s 6c 4d 2c")), Ordering::Equal);
+    }
+
+    #[test]
+    fn ace_king_suited_against_a_random_hand() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let equity = estimate(&engine, &cards("As Ks"), &[], 10_000, &mut SeededRng::new(42));
+        // About 67% all in, give or take a few standard errors
+        assert!((equity.value() - 0.67).abs() < 0.015, "{}", equity);
+    }
+
+    #[test]
+    fn equity_bot_deals_what_the_runner_expects() {
+        let mut bot = EquityBot::new(ShowdownEngine::new(CardValue::ALL).with_hole_cards(2, HoleRule::TwoPlusThree), 1);
+        let config = RunnerConfig::builder().hole_cards(4).build().unwrap();
+        bot.handle_game_start(0, &config).unwrap();
+        assert_eq!((bot.engine.hole_cards(), bot.engine.hole_rule()), (4, HoleRule::TwoPlusThree));
+    }
+}
@@ -0,0 +1,309 @@
+//! Reconstructing who did what in a round from its chain of `RoundState`s
+//!
+//! The engine never says who acted, and `RoundState` only links back to the state before it,
+//! so the actions have to be read off the differences between consecutive states.
+
+use crate::actions::Action;
+use crate::cards::{Card, HoleCards};
+use crate::states::{RoundState, TerminalState};
+use std::fmt;
+
+/// Betting rounds, in order
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Street {
+    Preflop,
+    Flop,
+    Turn,
+    River,
+}
+
+impl Street {
+    pub const ALL: [Street; 4] = [Street::Preflop, Street::Flop, Street::Turn, Street::River];
+
+    /// The street after this one, if there is one
+    pub fn next(self) -> Option<Street> {
+        Street::ALL.get(self as usize + 1).copied()
+    }
+
+    /// The street with the given number of board cards out
+    pub fn from_board(cards: usize) -> Option<Street> {
+        match cards {
+            0 => Some(Street::Preflop),
+            3 => Some(Street::Flop),
+            4 => Some(Street::Turn),
+            5 => Some(Street::River),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Street {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Street::Preflop => write!(fmt, "preflop"),
+            Street::Flop => write!(fmt, "flop"),
+            Street::Turn => write!(fmt, "turn"),
+            Street::River => write!(fmt, "river"),
+        }
+    }
+}
+
+/// An action someone took during the round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PlayedAction {
+    pub player: usize,
+    pub street: Street,
+    pub action: Action,
+}
+
+/// Everything that happened in a finished round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoundHistory {
+    /// Every action, in the order it was taken. Raises are the amount raised to, as in `Action::Raise`.
+    pub actions: Vec<PlayedAction>,
+    /// The last street anyone got to
+    pub street: Street,
+    /// Whether the hands were shown down (as opposed to someone folding)
+    pub showdown: bool,
+    pub hands: [Option<HoleCards>; 2],
+    pub board: Vec<Card>,
+    pub deltas: [i32; 2],
+}
+
+impl RoundHistory {
+    /// Reads the history of a round off its terminal state
+    pub fn from_terminal(ts: &TerminalState) -> RoundHistory {
+        let (decisions, street, closed_by_call) = walk(&ts.previous);
+        let mut actions: Vec<PlayedAction> = decisions.into_iter().map(|(_, played)| played).collect();
+
+        let last = &ts.previous;
+        let showdown = last.hands.iter().all(Option::is_some);
+        let all_in = last.stacks.contains(&0) && last.pips[0] == last.pips[1];
+        // The action that ended the round doesn't make it into the chain, unless it was a call
+        // that left a state behind. Once someone is all in, the board just runs out.
+        let recorded = closed_by_call || (showdown && all_in);
+        if !recorded {
+            let active = last.button as usize % 2;
+            let action = if !showdown {
+                Action::Fold
+            } else if last.pips[0] != last.pips[1] {
+                Action::Call
+            } else {
+                Action::Check
+            };
+            actions.push(PlayedAction { player: active, street, action });
+        }
+
+        RoundHistory {
+            actions,
+            street: Street::from_board(last.deck.0.len()).map_or(street, |board| board.max(street)),
+            showdown,
+            hands: last.hands,
+            board: last.deck.0.clone(),
+            deltas: ts.deltas,
+        }
+    }
+
+    /// Actions taken by the given player
+    pub fn by(&self, player: usize) -> impl Iterator<Item=&PlayedAction> {
+        self.actions.iter().filter(move |played| played.player == player)
+    }
+
+    /// Actions taken on the given street
+    pub fn on(&self, street: Street) -> impl Iterator<Item=&PlayedAction> {
+        self.actions.iter().filter(move |played| played.street == street)
+    }
+
+    /// The player who raised last before the flop, if anyone raised
+    pub fn preflop_aggressor(&self) -> Option<usize> {
+        self.on(Street::Preflop).filter(|played| played.action.is_raise()).last().map(|played| played.player)
+    }
+}
+
+/// Every action taken so far in a round that is still going, along with the state it was taken in
+pub fn decisions(rs: &RoundState) -> Vec<(&RoundState, PlayedAction)> {
+    walk(rs).0
+}
+
+/// Walks the chain of states up to `last`, and reads off the actions between them.
+/// Also returns the street we got to, and whether the last action was a call that closed the betting.
+fn walk(last: &RoundState) -> (Vec<(&RoundState, PlayedAction)>, Street, bool) {
+    let mut chain = vec![last];
+    while let Some(previous) = chain[chain.len() - 1].previous.as_deref() {
+        chain.push(previous);
+    }
+    chain.reverse();
+
+    let mut decisions = vec![];
+    let mut street = Street::Preflop;
+    // Set when a call closed the betting, which leaves an extra state behind before the next street
+    let mut closed_by_call = false;
+    for pair in chain.windows(2) {
+        let (before, after) = (pair[0], pair[1]);
+        let active = before.button as usize % 2;
+        let next_street = after.button == 1 && before.button != 0 && after.pips == [0, 0];
+        if next_street {
+            if !closed_by_call {
+                // Checking behind skips straight to the next street
+                decisions.push((before, PlayedAction { player: active, street, action: Action::Check }));
+            }
+            closed_by_call = false;
+            street = street.next().unwrap_or(Street::River);
+        } else if after.pips[active] > before.pips[active] {
+            if after.pips[active] == before.pips[1 - active] {
+                decisions.push((before, PlayedAction { player: active, street, action: Action::Call }));
+                // The small blind completing preflop is the only call that doesn't close the betting
+                closed_by_call = !(street == Street::Preflop && before.button == 0);
+            } else {
+                decisions.push((before, PlayedAction { player: active, street, action: Action::Raise(after.pips[active]) }));
+            }
+        } else {
+            decisions.push((before, PlayedAction { player: active, street, action: Action::Check }));
+        }
+    }
+    (decisions, street, closed_by_call)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::CardDeck;
+    use crate::states::{StateResult, BIG_BLIND, SMALL_BLIND, STARTING_STACK};
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    const BOARD: &str = "2c 3d Kh 5s 9c";
+
+    /// The start of a round where we are the small blind, and don't know the opponent's hand yet
+    fn start(ours: &str) -> RoundState {
+        RoundState {
+            button: 0,
+            street: 0,
+            pips: [SMALL_BLIND, BIG_BLIND],
+            stacks: [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [HoleCards::new(&cards(ours)), None],
+            deck: CardDeck(vec![]),
+            previous: None,
+        }
+    }
+
+    /// Plays an action that doesn't end the round, dealing the next street the way the runner does
+    fn act(rs: &RoundState, action: Action) -> RoundState {
+        match rs.proceed(action) {
+            StateResult::Round(mut next) => {
+                if next.button == 1 && next.pips == [0, 0] {
+                    let dealt = if rs.street == 0 { 3 } else { rs.street as usize + 1 };
+                    next.deck = CardDeck(cards(BOARD)[..dealt].to_vec());
+                    next.street = dealt as u32;
+                }
+                next
+            },
+            StateResult::Terminal(_) => panic!("{:?} ended the round", action),
+        }
+    }
+
+    fn play(rs: RoundState, actions: &[Action]) -> RoundState {
+        actions.iter().fold(rs, |rs, &action| act(&rs, action))
+    }
+
+    /// Ends the round in a showdown with the last action taken in `rs`. Once the opponent's hand is revealed,
+    /// the runner rebuilds the terminal state from the state that action was taken in, not the one after it.
+    fn show(rs: RoundState, theirs: &str) -> TerminalState {
+        let mut previous = rs;
+        previous.hands[1] = HoleCards::new(&cards(theirs));
+        TerminalState { deltas: [0, 0], previous }
+    }
+
+    fn fold(rs: &RoundState) -> TerminalState {
+        match rs.proceed(Action::Fold) {
+            StateResult::Terminal(ts) => ts,
+            StateResult::Round(_) => panic!("folding didn't end the round"),
+        }
+    }
+
+    fn played(player: usize, street: Street, action: Action) -> PlayedAction {
+        PlayedAction { player, street, action }
+    }
+
+    #[test]
+    fn preflop_fold() {
+        let ts = fold(&play(start("As Ah"), &[Action::Raise(6)]));
+        let history = RoundHistory::from_terminal(&ts);
+        assert_eq!(history.actions, [
+            played(0, Street::Preflop, Action::Raise(6)),
+            played(1, Street::Preflop, Action::Fold),
+        ]);
+        assert_eq!(history.street, Street::Preflop);
+        assert!(!history.showdown);
+        assert!(history.board.is_empty());
+        assert_eq!(history.deltas, ts.deltas);
+        assert_eq!(history.preflop_aggressor(), Some(0));
+    }
+
+    #[test]
+    fn check_down_to_showdown() {
+        let mut rs = play(start("As Ah"), &[Action::Call, Action::Check]);
+        for _ in 0..2 {
+            rs = play(rs, &[Action::Check, Action::Check]);
+        }
+        // The small blind checking behind on the river ends the round
+        let ts = show(act(&rs, Action::Check), "Qd Jc");
+        let history = RoundHistory::from_terminal(&ts);
+
+        let mut expected = vec![played(0, Street::Preflop, Action::Call), played(1, Street::Preflop, Action::Check)];
+        for street in [Street::Flop, Street::Turn, Street::River] {
+            expected.push(played(1, street, Action::Check));
+            expected.push(played(0, street, Action::Check));
+        }
+        assert_eq!(history.actions, expected);
+        assert_eq!(history.street, Street::River);
+        assert!(history.showdown);
+        assert_eq!(history.board, cards(BOARD));
+        assert_eq!(history.hands[1], HoleCards::new(&cards("Qd Jc")));
+        assert_eq!(history.preflop_aggressor(), None);
+    }
+
+    #[test]
+    fn bet_raise_call_on_the_flop() {
+        let flop = play(start("As Ah"), &[Action::Call, Action::Check]);
+        let turn = play(flop, &[Action::Raise(4), Action::Raise(12), Action::Call]);
+        assert_eq!(turn.street, 4);
+        let ts = fold(&act(&turn, Action::Raise(10)));
+        let history = RoundHistory::from_terminal(&ts);
+
+        assert_eq!(history.on(Street::Flop).copied().collect::<Vec<_>>(), [
+            played(1, Street::Flop, Action::Raise(4)),
+            played(0, Street::Flop, Action::Raise(12)),
+            played(1, Street::Flop, Action::Call),
+        ]);
+        // The call closed the flop, so nobody checked before the turn came
+        assert_eq!(history.on(Street::Turn).copied().collect::<Vec<_>>(), [
+            played(1, Street::Turn, Action::Raise(10)),
+            played(0, Street::Turn, Action::Fold),
+        ]);
+        assert_eq!(history.actions.len(), 7);
+        assert_eq!(history.street, Street::Turn);
+        assert!(!history.showdown);
+    }
+
+    #[test]
+    fn all_in_preflop_runs_out_the_board() {
+        let mut rs = play(start("As Ah"), &[Action::Raise(STARTING_STACK), Action::Call]);
+        assert_eq!(rs.stacks, [0, 0]);
+        // Nobody can act any more, so the engine deals the rest of the board straight away
+        rs.deck = CardDeck(cards(BOARD));
+        rs.street = 5;
+        let ts = show(rs, "Kd Kc");
+        let history = RoundHistory::from_terminal(&ts);
+
+        assert_eq!(history.actions, [
+            played(0, Street::Preflop, Action::Raise(STARTING_STACK)),
+            played(1, Street::Preflop, Action::Call),
+        ]);
+        assert_eq!(history.street, Street::River);
+        assert!(history.showdown);
+        assert_eq!(history.board, cards(BOARD));
+    }
+}
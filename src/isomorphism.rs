@@ -0,0 +1,450 @@
+//! Suit isomorphism: treating deals that only differ by a renaming of suits as the same deal
+//!
+//! Follows Waugh's hand indexer. Cards are dealt in rounds (like the hole cards and then the board), and every
+//! deal gets a dense index, shared by exactly the deals it is suit isomorphic to. With the board as one round,
+//! there are 169, 1,286,792, 13,960,050 and 123,156,254 of them on the four streets.
+//! Every index can be turned back into a canonical deal, so tables can be keyed by index and still inspected.
+//!
+//! Per suit, the ranks dealt in each round are ranked as combinations among the ranks that suit has left,
+//! which gives each suit an index. Suits are then sorted by how many cards they got in each round
+//! (their shape), and suits with the same shape are indexed together as a multiset, since swapping them changes nothing.
+
+use crate::cards::{Card, CardHand};
+use crate::history::Street;
+use std::collections::{BTreeSet, HashMap};
+
+/// How many cards a suit got in each round
+type Shape = Vec<u8>;
+
+/// One way the cards can be spread over the suits, with the suits sorted by shape (largest first)
+#[derive(Debug, Clone)]
+struct Configuration {
+    shapes: [Shape; 4],
+    /// Runs of suits with the same shape: where the run starts, how long it is, and how many indices a suit in it can have
+    groups: Vec<(usize, usize, u64)>,
+    /// First index that belongs to this configuration
+    offset: u64,
+}
+
+/// Dense indices for deals up to a given round, up to suit isomorphism
+#[derive(Debug, Clone)]
+pub struct HandIndexer {
+    rounds: Vec<usize>,
+    configurations: Vec<Configuration>,
+    lookup: HashMap<[Shape; 4], usize>,
+    size: u64,
+}
+
+impl HandIndexer {
+    /// Indexes deals with the given number of cards in each round. At most 13 cards of each suit
+    /// can be dealt, so there can't be more than 52 cards in all.
+    pub fn new(cards_per_round: &[usize]) -> HandIndexer {
+        assert!(cards_per_round.iter().sum::<usize>() <= 52, "Can't deal more than 52 cards");
+
+        // Every way of spreading each round's cards over the suits, up to the order of the suits
+        let mut keys = BTreeSet::new();
+        let mut shapes: [Shape; 4] = Default::default();
+        spread(cards_per_round, 0, 0, &mut shapes, &mut keys);
+
+        let mut configurations = vec![];
+        let mut lookup = HashMap::new();
+        let mut offset = 0;
+        // Reversed so the configuration with the most cards in one suit comes first
+        for shapes in keys.into_iter().rev() {
+            let mut groups = vec![];
+            let mut size = 1;
+            let mut start = 0;
+            while start < 4 {
+                let mut end = start + 1;
+                while end < 4 && shapes[end] == shapes[start] {
+                    end += 1;
+                }
+                let indices = suit_indices(&shapes[start]);
+                size *= choose(indices + (end - start) as u64 - 1, (end - start) as u64);
+                groups.push((start, end - start, indices));
+                start = end;
+            }
+            lookup.insert(shapes.clone(), configurations.len());
+            configurations.push(Configuration { shapes, groups, offset });
+            offset += size;
+        }
+
+        HandIndexer {
+            rounds: cards_per_round.to_vec(),
+            configurations,
+            lookup,
+            size: offset,
+        }
+    }
+
+    /// The indexer for the hole cards and the board on the given street, with the board as one round:
+    /// which card came on which street makes no difference. To keep that apart, use `new(&[2, 3, 1, 1])` and the like.
+    pub fn for_street(street: Street) -> HandIndexer {
+        match street {
+            Street::Preflop => HandIndexer::new(&[2]),
+            Street::Flop => HandIndexer::new(&[2, 3]),
+            Street::Turn => HandIndexer::new(&[2, 4]),
+            Street::River => HandIndexer::new(&[2, 5]),
+        }
+    }
+
+    /// Number of cards dealt in each round
+    pub fn rounds(&self) -> &[usize] {
+        &self.rounds
+    }
+
+    /// Number of distinct deals up to suit isomorphism. Indices go from 0 to one less than this.
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+
+    /// Index of a deal given as the cards of each round in turn. Within a round, the order of the cards doesn't matter.
+    /// Gives `None` if the number of cards doesn't match the rounds, or a card shows up twice.
+    pub fn index_cards(&self, cards: &[Card]) -> Option<u64> {
+        if cards.len() != self.rounds.iter().sum::<usize>() {
+            return None;
+        }
+
+        // Which ranks each suit got in each round
+        let mut ranks = vec![vec![0u16; self.rounds.len()]; 4];
+        let mut seen = 0u64;
+        let mut dealt = cards.iter();
+        for (round, &count) in self.rounds.iter().enumerate() {
+            for card in dealt.by_ref().take(count) {
+                let index = card.index();
+                if seen & (1 << index) != 0 {
+                    return None;
+                }
+                seen |= 1 << index;
+                ranks[index / 13][round] |= 1 << (index % 13);
+            }
+        }
+
+        let mut suits: Vec<(Shape, u64)> = ranks.iter().map(|ranks| {
+            let shape: Shape = ranks.iter().map(|ranks| ranks.count_ones() as u8).collect();
+            (shape, suit_index(ranks))
+        }).collect();
+        suits.sort_by(|a, b| b.cmp(a));
+
+        let key = [suits[0].0.clone(), suits[1].0.clone(), suits[2].0.clone(), suits[3].0.clone()];
+        let configuration = &self.configurations[*self.lookup.get(&key)?];
+        let mut index = 0;
+        let mut multiplier = 1;
+        for &(start, length, indices) in configuration.groups.iter() {
+            let group: Vec<u64> = suits[start..start + length].iter().map(|suit| suit.1).collect();
+            index += multiset_index(&group) * multiplier;
+            multiplier *= choose(indices + length as u64 - 1, length as u64);
+        }
+        Some(configuration.offset + index)
+    }
+
+    /// Index of hole cards and a board. The board has to have as many cards as the rounds after the first call for.
+    pub fn index(&self, hand: &CardHand, board: &[Card]) -> Option<u64> {
+        let cards: Vec<Card> = hand.0.iter().chain(board).copied().collect();
+        self.index_cards(&cards)
+    }
+
+    /// The canonical deal with the given index, as the cards of each round in turn
+    pub fn unindex(&self, index: u64) -> Option<Vec<Card>> {
+        if index >= self.size {
+            return None;
+        }
+        let position = self.configurations.partition_point(|configuration| configuration.offset <= index) - 1;
+        let configuration = &self.configurations[position];
+
+        // Split the index back into one index per suit
+        let mut remainder = index - configuration.offset;
+        let mut suit_indices = [0u64; 4];
+        for &(start, length, indices) in configuration.groups.iter() {
+            let size = choose(indices + length as u64 - 1, length as u64);
+            let group = multiset_unindex(remainder % size, length);
+            suit_indices[start..start + length].copy_from_slice(&group);
+            remainder /= size;
+        }
+
+        let mut rounds: Vec<Vec<Card>> = self.rounds.iter().map(|&count| Vec::with_capacity(count)).collect();
+        for (suit, (shape, &suit_index)) in configuration.shapes.iter().zip(suit_indices.iter()).enumerate() {
+            let mut remainder = suit_index;
+            let mut used = 0u16;
+            for (round, &count) in shape.iter().enumerate() {
+                let left = 13 - used.count_ones() as u64;
+                let size = choose(left, count as u64);
+                let ranks = expand(colex_unindex(remainder % size, count as usize), used);
+                remainder /= size;
+                used |= ranks;
+                for rank in 0..13 {
+                    if ranks & (1 << rank) != 0 {
+                        rounds[round].push(Card::from_index(suit * 13 + rank)?);
+                    }
+                }
+            }
+        }
+        Some(rounds.into_iter().flatten().collect())
+    }
+
+    /// The canonical version of the hole cards and board: the deal every suit isomorphic one maps to
+    pub fn canonical(&self, hand: &CardHand, board: &[Card]) -> Option<(CardHand, Vec<Card>)> {
+        let cards = self.unindex(self.index(hand, board)?)?;
+        Some((CardHand([cards[0], cards[1]]), cards[2..].to_vec()))
+    }
+}
+
+/// Indexers for all four streets, for looking up whatever street a `RoundState` is on
+#[derive(Debug, Clone)]
+pub struct Isomorphism {
+    indexers: Vec<HandIndexer>,
+}
+
+impl Isomorphism {
+    pub fn new() -> Isomorphism {
+        Isomorphism {
+            indexers: Street::ALL.iter().map(|&street| HandIndexer::for_street(street)).collect(),
+        }
+    }
+
+    pub fn indexer(&self, street: Street) -> &HandIndexer {
+        &self.indexers[street as usize]
+    }
+
+    /// The street the board is on, and the index of the deal on that street
+    pub fn index(&self, hand: &CardHand, board: &[Card]) -> Option<(Street, u64)> {
+        let street = Street::from_board(board.len())?;
+        Some((street, self.indexer(street).index(hand, board)?))
+    }
+
+    /// The canonical version of the hole cards and board, on whatever street the board is on
+    pub fn canonical(&self, hand: &CardHand, board: &[Card]) -> Option<(CardHand, Vec<Card>)> {
+        self.indexer(Street::from_board(board.len())?).canonical(hand, board)
+    }
+}
+
+impl Default for Isomorphism {
+    fn default() -> Isomorphism {
+        Isomorphism::new()
+    }
+}
+
+/// Tries every way of dealing each round's cards to the suits, and keeps each configuration once, with its suits sorted
+fn spread(rounds: &[usize], round: usize, suit: usize, shapes: &mut [Shape; 4], keys: &mut BTreeSet<[Shape; 4]>) {
+    if round == rounds.len() {
+        let mut key = shapes.clone();
+        key.sort_by(|a, b| b.cmp(a));
+        keys.insert(key);
+        return;
+    }
+    let dealt: usize = shapes.iter().map(|shape| shape.get(round).copied().unwrap_or(0) as usize).sum();
+    let left = rounds[round] - dealt;
+    if suit == 3 {
+        // The last suit takes whatever is left in the round, if it has room
+        let total: usize = shapes[3].iter().map(|&count| count as usize).sum();
+        if total + left <= 13 {
+            shapes[3].push(left as u8);
+            spread(rounds, round + 1, 0, shapes, keys);
+            shapes[3].pop();
+        }
+        return;
+    }
+    let total: usize = shapes[suit].iter().map(|&count| count as usize).sum();
+    for count in 0..=left.min(13 - total) {
+        shapes[suit].push(count as u8);
+        spread(rounds, round, suit + 1, shapes, keys);
+        shapes[suit].pop();
+    }
+}
+
+/// How many ways a suit can get the ranks its shape calls for
+fn suit_indices(shape: &[u8]) -> u64 {
+    let mut used = 0;
+    let mut indices = 1;
+    for &count in shape {
+        indices *= choose(13 - used, count as u64);
+        used += count as u64;
+    }
+    indices
+}
+
+/// Index of the ranks a suit got, round by round, each among the ranks it had left
+fn suit_index(ranks: &[u16]) -> u64 {
+    let mut index = 0;
+    let mut multiplier = 1;
+    let mut used = 0u16;
+    for &round in ranks {
+        let left = 13 - used.count_ones() as u64;
+        index += colex_index(compress(round, used)) * multiplier;
+        multiplier *= choose(left, round.count_ones() as u64);
+        used |= round;
+    }
+    index
+}
+
+/// Renumbers the ranks in `ranks` by their position among the ranks not in `used`
+fn compress(ranks: u16, used: u16) -> u16 {
+    let mut compressed = 0;
+    let mut position = 0;
+    for rank in 0..13 {
+        if used & (1 << rank) == 0 {
+            if ranks & (1 << rank) != 0 {
+                compressed |= 1 << position;
+            }
+            position += 1;
+        }
+    }
+    compressed
+}
+
+/// Undoes `compress`
+fn expand(compressed: u16, used: u16) -> u16 {
+    let mut ranks = 0;
+    let mut position = 0;
+    for rank in 0..13 {
+        if used & (1 << rank) == 0 {
+            if compressed & (1 << position) != 0 {
+                ranks |= 1 << rank;
+            }
+            position += 1;
+        }
+    }
+    ranks
+}
+
+/// Colexicographic index of a set of ranks among all sets of the same size
+fn colex_index(set: u16) -> u64 {
+    let mut index = 0;
+    let mut nth = 0;
+    for rank in 0..16 {
+        if set & (1 << rank) != 0 {
+            nth += 1;
+            index += choose(rank, nth);
+        }
+    }
+    index
+}
+
+/// The set of `size` ranks with the given colexicographic index
+fn colex_unindex(mut index: u64, size: usize) -> u16 {
+    let mut set = 0;
+    let mut rank = 16;
+    for nth in (1..=size as u64).rev() {
+        rank -= 1;
+        while choose(rank, nth) > index {
+            rank -= 1;
+        }
+        index -= choose(rank, nth);
+        set |= 1 << rank;
+    }
+    set
+}
+
+/// Index of a multiset of suit indices, sorted from largest to smallest, among all multisets of the same size.
+/// Spreading the values out by their position turns the multiset into a set, which is then indexed like `colex_index`.
+fn multiset_index(values: &[u64]) -> u64 {
+    let length = values.len() as u64;
+    values.iter().enumerate().map(|(k, &value)| {
+        let k = k as u64;
+        choose(value + length - 1 - k, length - k)
+    }).sum()
+}
+
+/// Undoes `multiset_index`, giving the values from largest to smallest
+fn multiset_unindex(mut index: u64, length: usize) -> Vec<u64> {
+    let mut values = Vec::with_capacity(length);
+    for k in 0..length as u64 {
+        let nth = length as u64 - k;
+        // Largest spread out value that still fits, found by a binary search since the values can get big
+        let (mut low, mut high) = (nth - 1, nth);
+        while choose(high, nth) <= index {
+            high *= 2;
+        }
+        while high - low > 1 {
+            let middle = (low + high) / 2;
+            if choose(middle, nth) <= index {
+                low = middle;
+            } else {
+                high = middle;
+            }
+        }
+        index -= choose(low, nth);
+        values.push(low - (nth - 1));
+    }
+    values
+}
+
+/// Binomial coefficient, zero when `k` is more than `n`
+fn choose(n: u64, k: u64) -> u64 {
+    if k > n {
+        return 0;
+    }
+    let k = k.min(n - k);
+    let mut result: u128 = 1;
+    for i in 0..k as u128 {
+        result = result * (n as u128 - i) / (i + 1);
+    }
+    result as u64
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::CardDeck;
+    use crate::rng::SeededRng;
+
+    #[test]
+    fn streets_have_the_known_sizes() {
+        let isomorphism = Isomorphism::new();
+        let sizes: Vec<u64> = Street::ALL.iter().map(|&street| isomorphism.indexer(street).size()).collect();
+        assert_eq!(sizes, vec![169, 1_286_792, 13_960_050, 123_156_254]);
+    }
+
+    #[test]
+    fn unindex_round_trips() {
+        let mut rng = SeededRng::new(41);
+        for &street in Street::ALL.iter() {
+            let indexer = HandIndexer::for_street(street);
+            let samples = (0..500).map(|_| rng.next_u64() % indexer.size()).chain(vec![0, indexer.size() - 1]);
+            for index in samples {
+                let cards = indexer.unindex(index).unwrap();
+                assert_eq!(indexer.index_cards(&cards), Some(index), "{:?} on the {:?}", cards, street);
+            }
+            assert_eq!(indexer.unindex(indexer.size()), None);
+        }
+    }
+
+    #[test]
+    fn renaming_suits_keeps_the_index() {
+        let mut rng = SeededRng::new(7);
+        let indexer = HandIndexer::for_street(Street::River);
+        for _ in 0..200 {
+            let CardDeck(mut deck) = CardDeck::full();
+            rng.partial_shuffle(&mut deck, 7);
+            let mut suits = [0, 1, 2, 3];
+            rng.shuffle(&mut suits);
+            let renamed: Vec<Card> = deck[..7].iter()
+                .map(|card| Card::from_index(suits[card.index() / 13] * 13 + card.index() % 13).unwrap())
+                .collect();
+            assert_eq!(indexer.index_cards(&deck[..7]), indexer.index_cards(&renamed));
+        }
+    }
+
+    #[test]
+    fn every_preflop_class_is_hit() {
+        let indexer = HandIndexer::for_street(Street::Preflop);
+        let CardDeck(deck) = CardDeck::full();
+        let mut seen = vec![0; 169];
+        for (i, &first) in deck.iter().enumerate() {
+            for &second in deck[i + 1..].iter() {
+                seen[indexer.index(&CardHand([first, second]), &[]).unwrap() as usize] += 1;
+            }
+        }
+        // Pairs and suited hands come in 6 and 4 combos, offsuit hands in 12
+        assert!(seen.iter().all(|&count| count == 4 || count == 6 || count == 12));
+        assert_eq!(seen.iter().sum::<u32>(), 1326);
+    }
+
+    #[test]
+    fn bad_deals_have_no_index() {
+        let indexer = HandIndexer::for_street(Street::Flop);
+        let CardDeck(deck) = CardDeck::full();
+        assert_eq!(indexer.index_cards(&deck[..4]), None);
+        assert_eq!(indexer.index_cards(&[deck[0], deck[1], deck[2], deck[3], deck[0]]), None);
+    }
+}
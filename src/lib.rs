@@ -1,10 +1,33 @@
 pub mod actions;
 pub mod bot;
+pub mod bots;
 pub mod cards;
+pub mod clock;
+pub mod combinators;
+pub mod config;
+#[cfg(feature = "showdown")]
+pub mod equity;
+pub mod history;
+pub mod isomorphism;
+#[cfg(feature = "showdown")]
+pub mod lowball;
+#[cfg(feature = "showdown")]
+pub mod nuts;
+#[cfg(feature = "showdown")]
+pub mod outs;
+pub mod preflop;
+pub mod range;
+pub mod rng;
 pub mod runner;
+pub mod sanitizer;
+pub mod session;
 pub mod states;
+pub mod stats;
+#[cfg(feature = "showdown")]
+pub mod strength;
+pub mod summary;
+pub mod texture;
 pub mod thread_pool;
 #[cfg(feature = "showdown")]
 pub mod showdown;
 
-// I should write tests, but naaaah
@@ -0,0 +1,279 @@
+//! Low hands, for deuce-to-seven and ace-to-five lowball, and high/low split pots
+//!
+//! Lowball games go by the usual card values whatever ordering the `ShowdownEngine` was given.
+//! In deuce-to-seven the Ace is always high and straights and flushes count against you, so the best
+//! hand is 7-5-4-3-2 offsuit. In ace-to-five the Ace is always low and straights and flushes don't count,
+//! so the best hand is 5-4-3-2-A. Either way, a hand plays its best five cards.
+
+use crate::cards::{Card, CardValue};
+use crate::equity;
+use crate::showdown::ShowdownEngine;
+use itertools::Itertools;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// Which low game to play
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Lowball {
+    DeuceToSeven,
+    AceToFive,
+}
+
+impl Lowball {
+    /// Where a value stands in the game, from 1 for an Ace playing low up to 14 for an Ace playing high
+    fn number(&self, value: CardValue) -> u8 {
+        match (self, value) {
+            (Lowball::AceToFive, CardValue::Ace) => 1,
+            (_, value) => value as u8 + 2,
+        }
+    }
+}
+
+/// What five cards make, from the best low to the worst. Ace-to-five never makes a straight or a flush.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub enum LowCategory {
+    HighCard,
+    Pair,
+    TwoPair,
+    ThreeOfAKind,
+    Straight,
+    Flush,
+    FullHouse,
+    FourOfAKind,
+    StraightFlush,
+}
+
+impl fmt::Display for LowCategory {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", match self {
+            LowCategory::HighCard => "High card",
+            LowCategory::Pair => "Pair",
+            LowCategory::TwoPair => "Two pair",
+            LowCategory::ThreeOfAKind => "Three of a kind",
+            LowCategory::Straight => "Straight",
+            LowCategory::Flush => "Flush",
+            LowCategory::FullHouse => "Full house",
+            LowCategory::FourOfAKind => "Four of a kind",
+            LowCategory::StraightFlush => "Straight flush",
+        })
+    }
+}
+
+/// How good a low hand is. The better low hand compares greater, so ranks sort the same way as high hands do.
+/// Only ranks from the same game are meant to be compared.
+#[derive(Debug, Clone)]
+pub struct LowRank {
+    game: Lowball,
+    category: LowCategory,
+    /// Values that decide ties, most important first: bigger groups, then higher values
+    values: [u8; 5],
+    /// The five cards, in the same order
+    cards: [Card; 5],
+}
+
+impl LowRank {
+    /// Ranks exactly five cards
+    pub fn of(game: Lowball, cards: [Card; 5]) -> LowRank {
+        let number = |card: &Card| game.number(card.value());
+        let count = |value: u8| cards.iter().filter(|card| number(card) == value).count();
+        let mut sorted = cards;
+        sorted.sort_by(|a, b| count(number(b)).cmp(&count(number(a))).then(number(b).cmp(&number(a))));
+        let mut values = [0; 5];
+        for (slot, card) in values.iter_mut().zip(sorted.iter()) {
+            *slot = number(card);
+        }
+
+        // Sorted by size already, biggest first
+        let groups: Vec<usize> = sorted.iter().map(number).dedup().map(count).collect();
+        let flush = cards.iter().all(|card| card.suit() == cards[0].suit());
+        let straight = groups.len() == 5 && values[0] - values[4] == 4;
+        let category = match (groups[0], groups.get(1).copied().unwrap_or(0)) {
+            (4, _) => LowCategory::FourOfAKind,
+            (3, 2) => LowCategory::FullHouse,
+            (3, _) => LowCategory::ThreeOfAKind,
+            (2, 2) => LowCategory::TwoPair,
+            (2, _) => LowCategory::Pair,
+            _ => match (game, straight, flush) {
+                (Lowball::AceToFive, ..) => LowCategory::HighCard,
+                (_, true, true) => LowCategory::StraightFlush,
+                (_, false, true) => LowCategory::Flush,
+                (_, true, false) => LowCategory::Straight,
+                (_, false, false) => LowCategory::HighCard,
+            },
+        };
+        LowRank { game, category, values, cards: sorted }
+    }
+
+    /// The best low that five of the cards make, or `None` if there are fewer than five
+    pub fn best(game: Lowball, cards: &[Card]) -> Option<LowRank> {
+        cards.iter().copied().combinations(5)
+            .map(|five| LowRank::of(game, [five[0], five[1], five[2], five[3], five[4]]))
+            .max()
+    }
+
+    pub fn game(&self) -> Lowball {
+        self.game
+    }
+
+    pub fn category(&self) -> LowCategory {
+        self.category
+    }
+
+    /// The five cards, the ones that decide ties first
+    pub fn cards(&self) -> &[Card; 5] {
+        &self.cards
+    }
+
+    /// Whether the hand makes an ace-to-five low with no pair and nothing above the given value, like eight-or-better
+    pub fn qualifies(&self, highest: CardValue) -> bool {
+        self.game == Lowball::AceToFive && self.category == LowCategory::HighCard && self.values[0] <= self.game.number(highest)
+    }
+
+    // What makes a low worse, so the greater one is the worse low
+    fn key(&self) -> (LowCategory, [u8; 5]) {
+        (self.category, self.values)
+    }
+}
+
+impl PartialEq for LowRank {
+    fn eq(&self, other: &LowRank) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for LowRank {}
+
+impl PartialOrd for LowRank {
+    fn partial_cmp(&self, other: &LowRank) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LowRank {
+    fn cmp(&self, other: &LowRank) -> Ordering {
+        other.key().cmp(&self.key())
+    }
+}
+
+impl fmt::Display for LowRank {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}: {}", self.category, self.cards.iter().map(|card| card.value()).format("-"))
+    }
+}
+
+/// Compares the best lows two sets of cards make, like `equity::compare_showdown` does for high hands
+pub fn compare_low(game: Lowball, a: &[Card], b: &[Card]) -> Ordering {
+    LowRank::best(game, a).cmp(&LowRank::best(game, b))
+}
+
+/// How a high/low split showdown went, from our side
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub struct HiLo {
+    pub high: Ordering,
+    /// `None` when neither hand has a qualifying low, and the high hand takes the whole pot
+    pub low: Option<Ordering>,
+}
+
+impl HiLo {
+    /// Our share of the pot, from 0 to 1, with ties splitting their half
+    pub fn share(&self) -> f64 {
+        let half = |result: Ordering| match result {
+            Ordering::Greater => 1.0,
+            Ordering::Equal => 0.5,
+            Ordering::Less => 0.0,
+        };
+        match self.low {
+            Some(low) => (half(self.high) + half(low)) / 2.0,
+            None => half(self.high),
+        }
+    }
+}
+
+/// Plays a high/low split showdown with an eight-or-better ace-to-five low, from both players' hole cards and the board.
+/// Both halves go by the engine's hole rule, so in Omaha the low also takes exactly two hole cards and three board cards.
+/// The high half goes by the engine, and the low half to the best qualifying low.
+pub fn hi_lo(engine: &ShowdownEngine, ours: &[Card], theirs: &[Card], board: &[Card]) -> HiLo {
+    let low = |hole: &[Card]| engine.holdings(hole, board).iter()
+        .filter_map(|cards| LowRank::best(Lowball::AceToFive, cards))
+        .filter(|rank| rank.qualifies(CardValue::Eight))
+        .max();
+    let low = match (low(ours), low(theirs)) {
+        (Some(ours), Some(theirs)) => Some(ours.cmp(&theirs)),
+        (Some(_), None) => Some(Ordering::Greater),
+        (None, Some(_)) => Some(Ordering::Less),
+        (None, None) => None,
+    };
+    HiLo {
+        high: equity::compare_holdings(engine, ours, theirs, board),
+        low,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::showdown::HoleRule;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn best(game: Lowball, s: &str) -> LowRank {
+        LowRank::best(game, &cards(s)).unwrap()
+    }
+
+    #[test]
+    fn deuce_to_seven_plays_aces_high_and_counts_straights() {
+        let game = Lowball::DeuceToSeven;
+        assert!(best(game, "7s 5h 4d 3c 2s") > best(game, "7s 6h 4d 3c 2s"));
+        assert!(best(game, "8s 6h 4d 3c 2s") > best(game, "As 5h 4d 3c 2s"));
+        assert_eq!(best(game, "6s 5h 4d 3c 2s").category(), LowCategory::Straight);
+        assert_eq!(best(game, "9s 7s 5s 4s 2s").category(), LowCategory::Flush);
+    }
+
+    #[test]
+    fn ace_to_five_plays_aces_low_and_ignores_straights() {
+        let game = Lowball::AceToFive;
+        assert!(best(game, "5s 4h 3d 2c As") > best(game, "6s 4h 3d 2c As"));
+        assert_eq!(best(game, "5s 4s 3s 2s As").category(), LowCategory::HighCard);
+        assert!(best(game, "As 2h 3d 4c 6s Ks Kh") > best(game, "As 2h 3d 4c 7s"));
+        assert!(LowRank::best(game, &cards("As 2h 3d 4c")).is_none());
+    }
+
+    #[test]
+    fn eight_or_better_qualifies() {
+        let game = Lowball::AceToFive;
+        assert!(best(game, "8s 7h 4d 3c As").qualifies(CardValue::Eight));
+        assert!(!best(game, "9s 7h 4d 3c As").qualifies(CardValue::Eight));
+        assert!(!best(game, "8s 8h 4d 3c As Ks").qualifies(CardValue::Eight));
+        assert!(!best(Lowball::DeuceToSeven, "7s 5h 4d 3c 2s").qualifies(CardValue::Eight));
+    }
+
+    #[test]
+    fn hi_lo_splits_the_pot() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let board = cards("Ks Kd 7c 4h 2s");
+        // Kings full takes the high, and the wheel cards the low
+        let split = hi_lo(&engine, &cards("Kh Qc"), &cards("Ac 3d"), &board);
+        assert_eq!(split, HiLo { high: Ordering::Greater, low: Some(Ordering::Less) });
+        assert_eq!(split.share(), 0.5);
+        // Without a qualifying low, the high hand scoops
+        let scoop = hi_lo(&engine, &cards("Kh Qc"), &cards("Qd 5d"), &cards("Ks Td 9c 4h 2s"));
+        assert_eq!(scoop, HiLo { high: Ordering::Greater, low: None });
+        assert_eq!(scoop.share(), 1.0);
+    }
+
+    #[test]
+    fn omaha_hi_lo_takes_two_hole_cards_for_both_halves() {
+        let engine = ShowdownEngine::new(CardValue::ALL).with_hole_cards(4, HoleRule::TwoPlusThree);
+        let board = cards("Ah 2h 3h 5d Qc");
+        // A single low card doesn't make a low or a straight with the board, so their jacks lose to our kings
+        let ours = cards("Kh Ks 9c 9d");
+        let theirs = cards("4c Jc Jd Js");
+        let split = hi_lo(&engine, &ours, &theirs, &board);
+        assert_eq!(split, HiLo { high: Ordering::Greater, low: None });
+        // With any five playing, they would have the wheel, and scoop
+        let any_five = hi_lo(&ShowdownEngine::new(CardValue::ALL), &ours, &theirs, &board);
+        assert_eq!(any_five, HiLo { high: Ordering::Less, low: Some(Ordering::Less) });
+    }
+}
@@ -0,0 +1,152 @@
+//! Every two card holding on a board, ranked by what it makes
+//!
+//! Holdings that make equally good hands share a tier, and the first tier is the nuts. Since our own cards
+//! block some holdings, counts of what beats a hand only go over the holdings that don't share a card with it.
+
+use crate::cards::{Card, CardHand};
+use crate::equity::MadeHand;
+use crate::range::{self, COMBOS};
+use crate::showdown::ShowdownEngine;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// How many holdings a hand beats, ties and loses to
+#[derive(Debug, Copy, Clone, Default, Hash, Eq, PartialEq)]
+pub struct Standing {
+    pub better: usize,
+    pub tied: usize,
+    pub worse: usize,
+}
+
+impl Standing {
+    pub fn total(&self) -> usize {
+        self.better + self.tied + self.worse
+    }
+
+    /// Share of the holdings the hand beats, counting ties as half
+    pub fn percentile(&self) -> f64 {
+        if self.total() > 0 {
+            (self.worse as f64 + self.tied as f64 / 2.0) / self.total() as f64
+        } else {
+            0.0
+        }
+    }
+}
+
+impl fmt::Display for Standing {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{} better, {} tied, {} worse ({:.1}%)", self.better, self.tied, self.worse, self.percentile() * 100.0)
+    }
+}
+
+/// Every holding in play that doesn't use a board card, grouped into tiers of equal strength, best first
+#[derive(Debug, Clone)]
+pub struct HandRanking {
+    tiers: Vec<Vec<CardHand>>,
+    /// The tier of each holding, by `range::combo_index`
+    tier: Vec<Option<usize>>,
+}
+
+impl HandRanking {
+    /// Ranks every holding on the board under the engine, so under whatever ordering and variant it was made with
+    pub fn new(engine: &ShowdownEngine, board: &[Card]) -> HandRanking {
+        let dead: Vec<Card> = board.iter().copied().chain(engine.removed_cards()).collect();
+        let mut made: Vec<(CardHand, MadeHand)> = (0..COMBOS)
+            .filter_map(range::combo_at)
+            .filter(|hand| !range::blocked(hand, &dead))
+            .map(|hand| (hand, MadeHand::of(engine, &hand.0, board)))
+            .collect();
+        made.sort_by(|(_, a), (_, b)| b.compare(engine, a));
+
+        let mut tiers: Vec<Vec<CardHand>> = vec![];
+        let mut tier = vec![None; COMBOS];
+        for (i, (hand, _)) in made.iter().enumerate() {
+            let same = i > 0 && made[i - 1].1.compare(engine, &made[i].1) == Ordering::Equal;
+            if !same {
+                tiers.push(vec![]);
+            }
+            tier[range::combo_index(hand)] = Some(tiers.len() - 1);
+            if let Some(last) = tiers.last_mut() {
+                last.push(*hand);
+            }
+        }
+        HandRanking { tiers, tier }
+    }
+
+    /// Every tier, best first
+    pub fn tiers(&self) -> &[Vec<CardHand>] {
+        &self.tiers
+    }
+
+    /// The holdings that make the best hand possible
+    pub fn nuts(&self) -> &[CardHand] {
+        self.tiers.first().map_or(&[], |tier| tier.as_slice())
+    }
+
+    /// Position of the holding's tier, from 0 for the nuts, or `None` if it uses a board card or one out of play
+    pub fn tier(&self, hand: &CardHand) -> Option<usize> {
+        self.tier[range::combo_index(hand)]
+    }
+
+    /// The tiers without the holdings that use any of the given cards, leaving out tiers that end up empty.
+    /// Taking out our own hole cards gives what the opponent could still have, and so what the nuts are to us.
+    pub fn live_tiers(&self, blockers: &[Card]) -> Vec<Vec<CardHand>> {
+        self.tiers.iter()
+            .map(|tier| tier.iter().copied().filter(|hand| !range::blocked(hand, blockers)).collect::<Vec<_>>())
+            .filter(|tier| !tier.is_empty())
+            .collect()
+    }
+
+    /// How many holdings the opponent could have (that is, that don't share a card with ours) beat, tie and lose to ours
+    pub fn standing(&self, hand: &CardHand) -> Option<Standing> {
+        let ours = self.tier(hand)?;
+        let mut standing = Standing::default();
+        for (i, tier) in self.tiers.iter().enumerate() {
+            let live = tier.iter().filter(|theirs| !range::blocked(theirs, &hand.0)).count();
+            match i.cmp(&ours) {
+                Ordering::Less => standing.better += live,
+                Ordering::Equal => standing.tied += live,
+                Ordering::Greater => standing.worse += live,
+            }
+        }
+        Some(standing)
+    }
+
+    /// Share of the holdings the opponent could have that ours beats, counting ties as half
+    pub fn percentile(&self, hand: &CardHand) -> Option<f64> {
+        self.standing(hand).map(|standing| standing.percentile())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::CardValue;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn hand(s: &str) -> CardHand {
+        let cards = cards(s);
+        CardHand([cards[0], cards[1]])
+    }
+
+    #[test]
+    fn kickers_split_tiers() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let ranking = HandRanking::new(&engine, &cards("Ad 9c 6h 4s 2c"));
+        assert!(ranking.tier(&hand("As Kh")) < ranking.tier(&hand("Ac Qh")));
+        assert_eq!(ranking.tier(&hand("As Kh")), ranking.tier(&hand("Ac Kd")));
+    }
+
+    #[test]
+    fn full_houses_split_by_their_trips() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let ranking = HandRanking::new(&engine, &cards("Qd Kd 2s Qc Kc"));
+        // Kings full of queens over queens full of kings
+        assert!(ranking.tier(&hand("Ks 3h")) < ranking.tier(&hand("Qs 3h")));
+        assert_eq!(ranking.nuts().len(), 1);
+        assert_eq!(ranking.tier(&hand("Ks Kh")), Some(0));
+    }
+}
@@ -0,0 +1,259 @@
+//! Outs: the cards that complete the draws `ShowdownEngine::potential_hands` reports
+//!
+//! An out is tainted when it also helps the opponent: with it on the board, a good share of their range
+//! improves to a hand that beats ours. Those are worth less than they look, and a cautious count leaves them out.
+
+use crate::cards::{Card, CardDeck, CardHand};
+use crate::equity::MadeHand;
+use crate::range::{self, Range};
+use crate::showdown::{PotentialHand, ShowdownEngine};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+
+/// The outs to one draw
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DrawOuts {
+    pub draw: PotentialHand,
+    /// Live cards that complete the draw
+    pub outs: Vec<Card>,
+    /// The outs that also help the opponent
+    pub tainted: Vec<Card>,
+}
+
+/// Outs to every draw in a hand
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outs {
+    pub draws: Vec<DrawOuts>,
+    /// Cards that complete at least one draw
+    pub outs: Vec<Card>,
+    /// The outs that also help the opponent
+    pub tainted: Vec<Card>,
+    /// Cards we haven't seen, that could still come
+    pub unseen: usize,
+    /// Board cards still to come
+    pub to_come: usize,
+}
+
+impl Outs {
+    /// The outs that aren't tainted
+    pub fn clean(&self) -> Vec<Card> {
+        self.outs.iter().copied().filter(|card| !self.tainted.contains(card)).collect()
+    }
+
+    /// Chance of hitting one of the given number of outs in the next `cards` cards
+    pub fn chance_with(&self, outs: usize, cards: usize) -> f64 {
+        let cards = cards.min(self.to_come);
+        if outs == 0 || cards == 0 || self.unseen == 0 {
+            return 0.0;
+        }
+        // One minus the chance of missing with every card
+        let mut miss = 1.0;
+        for i in 0..cards {
+            let left = self.unseen.saturating_sub(i);
+            if left == 0 {
+                break;
+            }
+            miss *= left.saturating_sub(outs) as f64 / left as f64;
+        }
+        1.0 - miss
+    }
+
+    /// Chance of hitting an out with the next card
+    pub fn by_turn(&self) -> f64 {
+        self.chance_with(self.outs.len(), 1)
+    }
+
+    /// Chance of hitting an out by the time the board is out
+    pub fn by_river(&self) -> f64 {
+        self.chance_with(self.outs.len(), self.to_come)
+    }
+}
+
+impl fmt::Display for Outs {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{} outs ({} clean), {:.1}% next card, {:.1}% by the river",
+            self.outs.len(), self.outs.len() - self.tainted.len(), self.by_turn() * 100.0, self.by_river() * 100.0)
+    }
+}
+
+/// Finds the outs to every draw in our hand on the board.
+/// Dead cards (seen somewhere else, like a folded hand) can't come, so they aren't outs.
+/// An out is tainted when, with it on the board, more than `taint_at` of the opponent's range (by weight)
+/// improves to beat our completed hand. `Range::full` stands in for an opponent we know nothing about.
+pub fn count_outs(engine: &ShowdownEngine, hand: &[Card], board: &[Card], dead: &[Card], range: &Range, taint_at: f64) -> Outs {
+    let cards: Vec<Card> = hand.iter().chain(board).copied().collect();
+    let known: Vec<Card> = cards.iter().chain(dead).copied().chain(engine.removed_cards()).collect();
+    let CardDeck(live) = engine.deck_without(&known);
+
+    let mut taints = TaintCheck::new(engine, hand, board, &known, range, taint_at);
+    let draws: Vec<DrawOuts> = engine.potential_hands_with_to_come(&cards, true, 5 - board.len().min(5)).into_iter()
+        .flat_map(|draw| match draw {
+            // The engine only reports the straight flush draw, but the straight and the flush are still there to hit
+            PotentialHand::StraightFlushDraw(ref cards, typ) | PotentialHand::RoyalFlushDraw(ref cards, typ) => {
+                let (straight, flush) = (PotentialHand::StraightDraw(cards.clone(), typ), PotentialHand::FlushDraw(cards.clone()));
+                vec![draw, straight, flush]
+            },
+            PotentialHand::StraightDraw(..) | PotentialHand::FlushDraw(..) => vec![draw],
+            PotentialHand::Hand(_) | PotentialHand::HighCard(_) => vec![],
+        })
+        .map(|draw| {
+            let outs: Vec<Card> = live.iter().copied().filter(|&card| completes(engine, &draw, card)).collect();
+            let tainted = outs.iter().copied().filter(|&card| taints.tainted(card)).collect();
+            DrawOuts { draw, outs, tainted }
+        })
+        .collect();
+
+    let mut outs: Vec<Card> = vec![];
+    let mut tainted: Vec<Card> = vec![];
+    for draw in draws.iter() {
+        outs.extend(draw.outs.iter().filter(|card| !outs.contains(card)).collect::<Vec<_>>());
+        tainted.extend(draw.tainted.iter().filter(|card| !tainted.contains(card)).collect::<Vec<_>>());
+    }
+    Outs {
+        draws,
+        outs,
+        tainted,
+        unseen: live.len(),
+        to_come: 5 - board.len().min(5),
+    }
+}
+
+/// Whether adding the card to the draw makes the hand the draw is going for
+pub fn completes(engine: &ShowdownEngine, draw: &PotentialHand, card: Card) -> bool {
+    let mut cards: Vec<Card> = draw.cards().into_iter().collect();
+    if cards.contains(&card) {
+        return false;
+    }
+    cards.push(card);
+    match draw {
+        PotentialHand::StraightDraw(..) => straight(engine, &cards),
+        PotentialHand::FlushDraw(_) => flush(&cards),
+        PotentialHand::StraightFlushDraw(..) | PotentialHand::RoyalFlushDraw(..) => flush(&cards) && straight(engine, &cards),
+        PotentialHand::Hand(_) | PotentialHand::HighCard(_) => false,
+    }
+}
+
+/// Whether five of the cards share a suit
+fn flush(cards: &[Card]) -> bool {
+    cards.iter().any(|card| cards.iter().filter(|other| other.suit() == card.suit()).count() >= 5)
+}
+
+/// Whether five of the cards have consecutive values in the engine's straight order
+fn straight(engine: &ShowdownEngine, cards: &[Card]) -> bool {
+    let present: Vec<bool> = engine.straight_order().iter()
+        .map(|&value| cards.iter().any(|card| card.value() == value))
+        .collect();
+    present.windows(5).any(|window| window.iter().all(|&present| present))
+}
+
+/// Checks outs for taint, remembering how the opponent's hands stand without them and the outs already checked
+struct TaintCheck<'a> {
+    engine: &'a ShowdownEngine,
+    hand: &'a [Card],
+    board: &'a [Card],
+    taint_at: f64,
+    /// Each opponent hand that doesn't clash with ours, with its weight and what it makes now
+    opponents: Vec<(CardHand, f64, MadeHand)>,
+    checked: HashMap<Card, bool>,
+}
+
+impl<'a> TaintCheck<'a> {
+    fn new(engine: &'a ShowdownEngine, hand: &'a [Card], board: &'a [Card], known: &[Card], range: &Range, taint_at: f64) -> TaintCheck<'a> {
+        let opponents = range.iter()
+            .filter(|(theirs, _)| !range::blocked(theirs, known))
+            .map(|(theirs, weight)| (theirs, weight, MadeHand::of(engine, &theirs.0, board)))
+            .collect();
+        TaintCheck { engine, hand, board, taint_at, opponents, checked: HashMap::new() }
+    }
+
+    fn tainted(&mut self, out: Card) -> bool {
+        if let Some(&tainted) = self.checked.get(&out) {
+            return tainted;
+        }
+        let board: Vec<Card> = self.board.iter().chain(Some(&out)).copied().collect();
+        let ours = MadeHand::of(self.engine, self.hand, &board);
+        let (mut improved, mut total) = (0.0, 0.0);
+        for (theirs, weight, before) in self.opponents.iter() {
+            if theirs.0.contains(&out) {
+                continue;
+            }
+            total += weight;
+            let after = MadeHand::of(self.engine, &theirs.0, &board);
+            if after.compare(self.engine, before) == Ordering::Greater && after.compare(self.engine, &ours) == Ordering::Greater {
+                improved += weight;
+            }
+        }
+        let tainted = total > 0.0 && improved / total > self.taint_at;
+        self.checked.insert(out, tainted);
+        tainted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::CardValue;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn outs(hand: &str, board: &str) -> Outs {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        count_outs(&engine, &cards(hand), &cards(board), &[], &Range::empty(), 1.0)
+    }
+
+    #[test]
+    fn flush_draw_outs_by_street() {
+        let flop = outs("Ah 9h", "Qh 7h 2c");
+        assert_eq!(flop.outs.len(), 9);
+        assert!((flop.by_river() - (1.0 - (38.0 * 37.0) / (47.0 * 46.0))).abs() < 1e-9);
+        let turn = outs("Ah 9h", "Qh 7h 2c 3d");
+        assert_eq!(turn.outs.len(), 9);
+        assert_eq!(turn.by_turn(), turn.by_river());
+        let river = outs("Ah 9h", "Qh 7h 2c 3d 8s");
+        assert!(river.draws.is_empty());
+        assert_eq!(river.by_river(), 0.0);
+    }
+
+    #[test]
+    fn dead_cards_are_not_outs() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let (hand, board) = (cards("9s 8d"), cards("7c 6h 2s"));
+        assert_eq!(count_outs(&engine, &hand, &board, &[], &Range::empty(), 1.0).outs.len(), 8);
+        let dead = cards("Th");
+        let outs = count_outs(&engine, &hand, &board, &dead, &Range::empty(), 1.0);
+        assert_eq!(outs.outs.len(), 7);
+        assert!(!outs.outs.contains(&dead[0]));
+        assert_eq!(outs.unseen, 46);
+    }
+
+    #[test]
+    fn straight_flush_draws_count_every_out_once() {
+        let outs = outs("Ah Kh", "Qh Jh 2c");
+        let royal = cards("Th")[0];
+        // Nine hearts for the flush, four tens for the straight, and the ten of hearts for both and the royal
+        assert_eq!(outs.outs.len(), 12);
+        assert_eq!(outs.outs.iter().filter(|&&card| card == royal).count(), 1);
+        let draw = |wanted: fn(&PotentialHand) -> bool| outs.draws.iter().find(|draw| wanted(&draw.draw)).unwrap();
+        assert_eq!(draw(|draw| matches!(draw, PotentialHand::RoyalFlushDraw(..))).outs, [royal]);
+        assert_eq!(draw(|draw| matches!(draw, PotentialHand::StraightDraw(..))).outs.len(), 4);
+        assert_eq!(draw(|draw| matches!(draw, PotentialHand::FlushDraw(..))).outs.len(), 9);
+    }
+
+    #[test]
+    fn outs_that_fill_the_opponents_flush_are_tainted() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let range: Range = "AhKh".parse().unwrap();
+        let outs = count_outs(&engine, &cards("9s 8d"), &cards("7h 6h 2c"), &[], &range, 0.5);
+        assert_eq!(outs.outs.len(), 8);
+        let mut tainted = outs.tainted.clone();
+        tainted.sort_by_key(|card| card.to_string());
+        assert_eq!(tainted, cards("5h Th"));
+        assert_eq!(outs.clean().len(), 6);
+        // Against a range that can't have the flush, every out is clean
+        let pairs: Range = "QQ+".parse().unwrap();
+        assert!(count_outs(&engine, &cards("9s 8d"), &cards("7h 6h 2c"), &[], &pairs, 0.5).tainted.is_empty());
+    }
+}
@@ -0,0 +1,489 @@
+//! The 169 kinds of starting hand, and the Chen score of a hand
+//!
+//! Before the flop, the suits only matter in whether the two cards share one, so every
+//! `CardHand` falls into one of 169 classes: 13 pairs, 78 suited hands and 78 offsuit hands.
+//! How strong each class is depends on the engine's value ordering, variant, wheel and hole rule, so the `EquityTable`
+//! remembers the ones it was worked out for, and `EquityTable::load_or_generate` rebuilds it when they change.
+//! The table needs the `showdown` feature.
+
+use crate::cards::{Card, CardHand, CardSuit, CardValue};
+#[cfg(feature = "showdown")]
+use crate::{cards::Variant, equity, rng::SeededRng, showdown::{HoleRule, ShowdownEngine, WheelRule}};
+use std::error::Error;
+use std::fmt;
+#[cfg(feature = "showdown")]
+use std::fs::File;
+use std::io;
+#[cfg(feature = "showdown")]
+use std::io::{BufRead, BufReader, BufWriter, Write};
+#[cfg(feature = "showdown")]
+use std::path::Path;
+use std::str::FromStr;
+
+/// Number of preflop hand classes
+pub const CLASSES: usize = 169;
+
+/// A kind of starting hand. The higher card (in the usual ordering) always comes first.
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum HandClass {
+    Pair(CardValue),
+    Suited(CardValue, CardValue),
+    Offsuit(CardValue, CardValue),
+}
+
+impl HandClass {
+    /// The class a hand belongs to
+    pub fn of(hand: &CardHand) -> HandClass {
+        let [a, b] = hand.0;
+        let (high, low) = if a.value() >= b.value() { (a.value(), b.value()) } else { (b.value(), a.value()) };
+        if high == low {
+            HandClass::Pair(high)
+        } else if a.suit() == b.suit() {
+            HandClass::Suited(high, low)
+        } else {
+            HandClass::Offsuit(high, low)
+        }
+    }
+
+    /// Every class, in `index` order
+    pub fn all() -> impl Iterator<Item=HandClass> {
+        (0..CLASSES).filter_map(HandClass::from_index)
+    }
+
+    /// Position in a 13 by 13 grid, from 0 to 168: pairs on the diagonal, suited hands on one side and offsuit hands on the other
+    pub fn index(&self) -> usize {
+        match *self {
+            HandClass::Pair(value) => value as usize * 13 + value as usize,
+            HandClass::Suited(high, low) => high as usize * 13 + low as usize,
+            HandClass::Offsuit(high, low) => low as usize * 13 + high as usize,
+        }
+    }
+
+    /// The class at the given position, if there is one
+    pub fn from_index(index: usize) -> Option<HandClass> {
+        if index >= CLASSES {
+            return None;
+        }
+        let (row, column) = (CardValue::ALL[index / 13], CardValue::ALL[index % 13]);
+        Some(match row.cmp(&column) {
+            std::cmp::Ordering::Equal => HandClass::Pair(row),
+            std::cmp::Ordering::Greater => HandClass::Suited(row, column),
+            std::cmp::Ordering::Less => HandClass::Offsuit(column, row),
+        })
+    }
+
+    /// Every hand in the class
+    pub fn hands(&self) -> Vec<CardHand> {
+        let (high, low) = self.values();
+        let mut hands = vec![];
+        for (i, &first) in CardSuit::ALL.iter().enumerate() {
+            for (j, &second) in CardSuit::ALL.iter().enumerate() {
+                let keep = match self {
+                    HandClass::Pair(_) => i < j,
+                    HandClass::Suited(..) => i == j,
+                    HandClass::Offsuit(..) => i != j,
+                };
+                if keep {
+                    hands.push(CardHand([Card::new(first, high), Card::new(second, low)]));
+                }
+            }
+        }
+        hands
+    }
+
+    /// Number of hands in the class: 6 for pairs, 4 for suited and 12 for offsuit hands
+    pub fn combos(&self) -> usize {
+        match self {
+            HandClass::Pair(_) => 6,
+            HandClass::Suited(..) => 4,
+            HandClass::Offsuit(..) => 12,
+        }
+    }
+
+    /// One hand from the class. Against a random hand, they all do the same.
+    pub fn representative(&self) -> CardHand {
+        self.hands()[0]
+    }
+
+    /// The two values, higher first
+    pub fn values(&self) -> (CardValue, CardValue) {
+        match *self {
+            HandClass::Pair(value) => (value, value),
+            HandClass::Suited(high, low) | HandClass::Offsuit(high, low) => (high, low),
+        }
+    }
+}
+
+impl fmt::Display for HandClass {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HandClass::Pair(value) => write!(fmt, "{}{}", value, value),
+            HandClass::Suited(high, low) => write!(fmt, "{}{}s", high, low),
+            HandClass::Offsuit(high, low) => write!(fmt, "{}{}o", high, low),
+        }
+    }
+}
+
+impl FromStr for HandClass {
+    type Err = PreflopError;
+
+    /// Parses classes like `AA`, `AKs` and `KAo`, with the values in either order
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let malformed = || PreflopError::Malformed(s.to_string());
+        let chars: Vec<char> = s.chars().collect();
+        if chars.len() < 2 || chars.len() > 3 {
+            return Err(malformed());
+        }
+        let value = |chr: char| chr.to_string().parse::<CardValue>().map_err(|_| malformed());
+        let (a, b) = (value(chars[0])?, value(chars[1])?);
+        let (high, low) = (a.max(b), a.min(b));
+        match (chars.get(2), high == low) {
+            (None, true) => Ok(HandClass::Pair(high)),
+            (Some('s'), false) => Ok(HandClass::Suited(high, low)),
+            (Some('o'), false) => Ok(HandClass::Offsuit(high, low)),
+            _ => Err(malformed()),
+        }
+    }
+}
+
+/// All in equity of every class against a random hand, under one value ordering, variant, wheel and hole rule
+#[cfg(feature = "showdown")]
+#[derive(Debug, Clone, PartialEq)]
+pub struct EquityTable {
+    ordering: [CardValue; 13],
+    variant: Variant,
+    wheel: WheelRule,
+    hole_cards: usize,
+    hole_rule: HoleRule,
+    samples: u32,
+    equity: Vec<f64>,
+}
+
+#[cfg(feature = "showdown")]
+impl EquityTable {
+    /// Works out the table by sampling showdowns, with the given number of samples per class.
+    /// The same seed gives the same table. This takes a few seconds for a thousand samples.
+    /// Classes the variant can't deal (like 55 in short deck) get an equity of 0.
+    pub fn generate(engine: &ShowdownEngine, samples: u32, seed: u64) -> EquityTable {
+        let values = engine.variant().values(engine.ordering());
+        let equity = HandClass::all().map(|class| {
+            let (high, low) = class.values();
+            if !values.contains(&high) || !values.contains(&low) {
+                return 0.0;
+            }
+            // Seeding each class on its own keeps its number the same however the table is built
+            let mut rng = SeededRng::new(seed ^ class.index() as u64);
+            equity::estimate(engine, &class.representative().0, &[], samples, &mut rng).value()
+        }).collect();
+        EquityTable {
+            ordering: *engine.ordering(),
+            variant: engine.variant(),
+            wheel: engine.wheel(),
+            hole_cards: engine.hole_cards(),
+            hole_rule: engine.hole_rule(),
+            samples,
+            equity,
+        }
+    }
+
+    /// Loads the table saved at `path` if it was made for an engine with the same settings (see `made_for`)
+    /// with at least as many samples, and otherwise (or if the file is missing or unreadable) generates a new one and saves it there
+    pub fn load_or_generate<P: AsRef<Path>>(path: P, engine: &ShowdownEngine, samples: u32, seed: u64) -> Result<EquityTable, PreflopError> {
+        match EquityTable::load(&path) {
+            Ok(table) if table.made_for(engine) && table.samples >= samples => return Ok(table),
+            Ok(_) | Err(PreflopError::Malformed(_)) => {},
+            Err(PreflopError::Io(ref e)) if e.kind() == io::ErrorKind::NotFound => {},
+            Err(e) => return Err(e),
+        }
+        let table = EquityTable::generate(engine, samples, seed);
+        table.save(path)?;
+        Ok(table)
+    }
+
+    /// The ordering the table was worked out for, lowest first
+    pub fn ordering(&self) -> &[CardValue; 13] {
+        &self.ordering
+    }
+
+    /// The variant the table was worked out for
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// The wheel rule the table was worked out for
+    pub fn wheel(&self) -> WheelRule {
+        self.wheel
+    }
+
+    /// The hole cards dealt and the hole rule the table was worked out for
+    pub fn hole_cards(&self) -> (usize, HoleRule) {
+        (self.hole_cards, self.hole_rule)
+    }
+
+    /// Whether the table was worked out under the same ordering, variant, wheel and hole rule as the engine uses
+    pub fn made_for(&self, engine: &ShowdownEngine) -> bool {
+        self.ordering == *engine.ordering() && self.variant == engine.variant() && self.wheel == engine.wheel()
+            && self.hole_cards == engine.hole_cards() && self.hole_rule == engine.hole_rule()
+    }
+
+    /// Samples per class that went into the table
+    pub fn samples(&self) -> u32 {
+        self.samples
+    }
+
+    pub fn equity(&self, class: HandClass) -> f64 {
+        self.equity[class.index()]
+    }
+
+    /// Equity of a hand, going by its class
+    pub fn of(&self, hand: &CardHand) -> f64 {
+        self.equity(HandClass::of(hand))
+    }
+
+    /// Every class with its equity, best first
+    pub fn ranked(&self) -> Vec<(HandClass, f64)> {
+        let mut ranked: Vec<(HandClass, f64)> = HandClass::all().map(|class| (class, self.equity(class))).collect();
+        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        ranked
+    }
+
+    /// Saves the table as text, to be read back with `load`
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "{}", self)?;
+        writer.flush()
+    }
+
+    /// Loads a table saved with `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<EquityTable, PreflopError> {
+        EquityTable::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a table from any buffered reader. Blank lines are skipped, and every setting and class has to be there.
+    pub fn read<R: BufRead>(reader: R) -> Result<EquityTable, PreflopError> {
+        let mut ordering = None;
+        let mut variant = None;
+        let mut wheel = None;
+        let mut hole_cards = None;
+        let mut hole_rule = None;
+        let mut samples = None;
+        let mut equity = vec![None; CLASSES];
+        for line in reader.lines() {
+            let line = line?;
+            let malformed = || PreflopError::Malformed(line.to_string());
+            let mut parts = line.split_whitespace();
+            let (key, value) = match (parts.next(), parts.next(), parts.next()) {
+                (None, ..) => continue,
+                (Some(key), Some(value), None) => (key, value),
+                _ => return Err(malformed()),
+            };
+            match key {
+                "ordering" => ordering = Some(parse_ordering(value).ok_or_else(malformed)?),
+                "variant" => variant = Some(value.parse::<Variant>().map_err(|_| malformed())?),
+                "wheel" => wheel = Some(*WheelRule::ALL.iter().find(|wheel| wheel.to_string() == value).ok_or_else(malformed)?),
+                "hole_cards" => hole_cards = Some(value.parse::<usize>().map_err(|_| malformed())?),
+                "hole_rule" => hole_rule = Some(*HoleRule::ALL.iter().find(|rule| rule.to_string() == value).ok_or_else(malformed)?),
+                "samples" => samples = Some(value.parse::<u32>().map_err(|_| malformed())?),
+                class => {
+                    let class = class.parse::<HandClass>()?;
+                    equity[class.index()] = Some(value.parse::<f64>().map_err(|_| malformed())?);
+                },
+            }
+        }
+        let missing = |what: &str| PreflopError::Malformed(format!("missing {}", what));
+        Ok(EquityTable {
+            ordering: ordering.ok_or_else(|| missing("ordering"))?,
+            variant: variant.ok_or_else(|| missing("variant"))?,
+            wheel: wheel.ok_or_else(|| missing("wheel"))?,
+            hole_cards: hole_cards.ok_or_else(|| missing("hole_cards"))?,
+            hole_rule: hole_rule.ok_or_else(|| missing("hole_rule"))?,
+            samples: samples.ok_or_else(|| missing("samples"))?,
+            equity: equity.iter().enumerate()
+                .map(|(index, equity)| equity.ok_or_else(|| missing(&HandClass::from_index(index).map_or(String::new(), |class| class.to_string()))))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+#[cfg(feature = "showdown")]
+impl fmt::Display for EquityTable {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "ordering ")?;
+        for value in self.ordering.iter() {
+            write!(fmt, "{}", value)?;
+        }
+        writeln!(fmt)?;
+        writeln!(fmt, "variant {}", self.variant)?;
+        writeln!(fmt, "wheel {}", self.wheel)?;
+        writeln!(fmt, "hole_cards {}", self.hole_cards)?;
+        writeln!(fmt, "hole_rule {}", self.hole_rule)?;
+        writeln!(fmt, "samples {}", self.samples)?;
+        for class in HandClass::all() {
+            writeln!(fmt, "{} {:.5}", class, self.equity(class))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(feature = "showdown")]
+impl FromStr for EquityTable {
+    type Err = PreflopError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        EquityTable::read(s.as_bytes())
+    }
+}
+
+/// Reads an ordering written out as 13 values, like `23456789TJQKA`
+#[cfg(feature = "showdown")]
+fn parse_ordering(s: &str) -> Option<[CardValue; 13]> {
+    let mut ordering = CardValue::ALL;
+    let mut chars = s.chars();
+    for slot in ordering.iter_mut() {
+        *slot = chars.next()?.to_string().parse().ok()?;
+    }
+    let distinct = CardValue::ALL.iter().all(|value| ordering.contains(value));
+    if chars.next().is_none() && distinct {
+        Some(ordering)
+    } else {
+        None
+    }
+}
+
+/// Scores a starting hand with the Chen formula under the given value ordering (lowest first).
+/// Aces score 20, and the worst hands go slightly negative.
+pub fn chen_score(ordering: &[CardValue; 13], hand: &CardHand) -> f32 {
+    let rank = |value: CardValue| ordering.iter().position(|&x| x == value).unwrap_or(0);
+    let [first, second] = hand.0;
+    let (high, low) = if rank(first.value()) >= rank(second.value()) {
+        (rank(first.value()), rank(second.value()))
+    } else {
+        (rank(second.value()), rank(first.value()))
+    };
+    let mut score = match high {
+        12 => 10.0,
+        11 => 8.0,
+        10 => 7.0,
+        9 => 6.0,
+        r => (r + 2) as f32 / 2.0,
+    };
+    if high == low {
+        return (score * 2.0).max(5.0).ceil();
+    }
+    if first.suit() == second.suit() {
+        score += 2.0;
+    }
+    let gap = high - low - 1;
+    score -= match gap {
+        0 => 0.0,
+        1 => 1.0,
+        2 => 2.0,
+        3 => 4.0,
+        _ => 5.0,
+    };
+    // Connected and gapped low cards can still make straights
+    if gap <= 1 && high < 10 {
+        score += 1.0;
+    }
+    score.ceil()
+}
+
+/// Describes errors that can occur when loading an equity table or parsing a hand class
+#[derive(Debug)]
+pub enum PreflopError {
+    Io(io::Error),
+    Malformed(String),
+}
+
+impl From<io::Error> for PreflopError {
+    fn from(e: io::Error) -> PreflopError {
+        PreflopError::Io(e)
+    }
+}
+
+impl Error for PreflopError {}
+
+impl fmt::Display for PreflopError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PreflopError::Io(e) => write!(fmt, "Equity table I/O error: {}", e),
+            PreflopError::Malformed(s) => write!(fmt, "Malformed preflop data: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hand(s: &str) -> CardHand {
+        CardHand([s[..2].parse().unwrap(), s[2..].parse().unwrap()])
+    }
+
+    #[test]
+    fn classes_cover_every_hand() {
+        let classes: Vec<HandClass> = HandClass::all().collect();
+        assert_eq!(classes.len(), CLASSES);
+        assert_eq!(classes.iter().map(HandClass::combos).sum::<usize>(), 1326);
+        for (index, class) in classes.iter().enumerate() {
+            assert_eq!(class.index(), index);
+            assert_eq!(class.hands().len(), class.combos());
+            assert!(class.hands().iter().all(|hand| HandClass::of(hand) == *class));
+            assert_eq!(class.to_string().parse::<HandClass>().unwrap(), *class);
+        }
+        assert_eq!(HandClass::from_index(CLASSES), None);
+    }
+
+    #[test]
+    fn hands_fall_into_their_class() {
+        assert_eq!(HandClass::of(&hand("KhAh")), HandClass::Suited(CardValue::Ace, CardValue::King));
+        assert_eq!(HandClass::of(&hand("AhKd")), HandClass::Offsuit(CardValue::Ace, CardValue::King));
+        assert_eq!(HandClass::of(&hand("7c7s")), HandClass::Pair(CardValue::Seven));
+        assert_eq!("KAo".parse::<HandClass>().unwrap(), HandClass::Offsuit(CardValue::Ace, CardValue::King));
+        for bad in ["", "A", "AKx", "AAs", "AK", "AKso"] {
+            assert!(matches!(bad.parse::<HandClass>(), Err(PreflopError::Malformed(_))), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn chen_scores() {
+        let ordering = CardValue::ALL;
+        assert_eq!(chen_score(&ordering, &hand("AhAd")), 20.0);
+        assert_eq!(chen_score(&ordering, &hand("KhKd")), 16.0);
+        assert_eq!(chen_score(&ordering, &hand("2h2d")), 5.0);
+        assert_eq!(chen_score(&ordering, &hand("AhKh")), 12.0);
+        assert_eq!(chen_score(&ordering, &hand("9h8h")), 8.0);
+        assert_eq!(chen_score(&ordering, &hand("7h2d")), -1.0);
+        // With the ordering reversed, deuces are the best cards
+        let mut reversed = CardValue::ALL;
+        reversed.reverse();
+        assert_eq!(chen_score(&reversed, &hand("2h2d")), 20.0);
+    }
+
+    #[cfg(feature = "showdown")]
+    #[test]
+    fn tables_read_back_what_they_write() {
+        let engine = ShowdownEngine::new(CardValue::ALL).with_wheel(WheelRule::NoWrap);
+        let table = EquityTable::generate(&engine, 1, 3);
+        let read = EquityTable::read(table.to_string().as_bytes()).unwrap();
+        assert_eq!(read.wheel(), WheelRule::NoWrap);
+        assert_eq!(read.hole_cards(), (2, HoleRule::AnyFive));
+        assert!(read.made_for(&engine));
+        assert!(!read.made_for(&ShowdownEngine::new(CardValue::ALL)));
+        assert!(!read.made_for(&engine.with_hole_cards(4, HoleRule::TwoPlusThree)));
+        let without_wheel: String = table.to_string().lines().filter(|line| !line.starts_with("wheel")).map(|line| format!("{}\n", line)).collect();
+        assert!(matches!(EquityTable::read(without_wheel.as_bytes()), Err(PreflopError::Malformed(_))));
+    }
+
+    #[cfg(feature = "showdown")]
+    #[test]
+    fn changing_the_wheel_regenerates_the_table() {
+        let path = std::env::temp_dir().join(format!("pbrust_equity_table_{}", std::process::id()));
+        let holdem = ShowdownEngine::new(CardValue::ALL);
+        EquityTable::load_or_generate(&path, &holdem, 1, 3).unwrap();
+        let table = EquityTable::load_or_generate(&path, &holdem.with_wheel(WheelRule::NoWrap), 1, 3).unwrap();
+        assert_eq!(table.wheel(), WheelRule::NoWrap);
+        assert_eq!(EquityTable::load(&path).unwrap().wheel(), WheelRule::NoWrap);
+        let _ = std::fs::remove_file(&path);
+    }
+}
@@ -0,0 +1,787 @@
+//! Keeping track of what the opponent could be holding as a round goes on
+//!
+//! A `Range` puts a weight on each of the 1326 two card hands. A `RangeTracker` starts every round
+//! with all of them, drops the ones that use cards we can see, and reweights the rest after every
+//! opponent action, using an `ActionModel` that says how likely each hand was to take that action.
+//!
+//! Ranges can also be written in the usual notation, like `TT+, A2s-A5s, AKo, KQ:0.5, AsKh`.
+//! Since card values can be permuted, `+` and `-` go by a value ordering (see `ShowdownEngine::ordering`).
+
+use crate::actions::Action;
+use crate::cards::{Card, CardConversionError, CardHand, CardSuit, CardValue, HoleCards};
+use crate::history;
+use crate::preflop::chen_score;
+use crate::rng::SeededRng;
+use crate::states::RoundState;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// Number of distinct two card hands
+pub const COMBOS: usize = 1326;
+
+/// Position of a hand in a `Range`, which doesn't depend on the order of its cards
+pub fn combo_index(hand: &CardHand) -> usize {
+    let (a, b) = (hand.0[0].index(), hand.0[1].index());
+    let (high, low) = if a > b { (a, b) } else { (b, a) };
+    high * (high - 1) / 2 + low
+}
+
+/// The hand at the given position in a `Range`, with the higher card first
+pub fn combo_at(index: usize) -> Option<CardHand> {
+    if index >= COMBOS {
+        return None;
+    }
+    // Invert high * (high - 1) / 2 + low, starting from an estimate and correcting for rounding
+    let mut high = ((1.0 + (1.0 + 8.0 * index as f64).sqrt()) / 2.0) as usize;
+    while high * (high - 1) / 2 > index {
+        high -= 1;
+    }
+    while (high + 1) * high / 2 <= index {
+        high += 1;
+    }
+    let low = index - high * (high - 1) / 2;
+    Some(CardHand([Card::from_index(high)?, Card::from_index(low)?]))
+}
+
+/// A weight for every two card hand. Hands with no weight are out of the range.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Range {
+    weights: Vec<f64>,
+}
+
+impl Range {
+    /// Every hand, all equally likely
+    pub fn full() -> Range {
+        Range {
+            weights: vec![1.0; COMBOS],
+        }
+    }
+
+    /// No hands at all
+    pub fn empty() -> Range {
+        Range {
+            weights: vec![0.0; COMBOS],
+        }
+    }
+
+    pub fn weight(&self, hand: &CardHand) -> f64 {
+        self.weights[combo_index(hand)]
+    }
+
+    /// Sets the weight of a hand. Negative weights count as zero.
+    pub fn set_weight(&mut self, hand: &CardHand, weight: f64) {
+        self.weights[combo_index(hand)] = weight.max(0.0);
+    }
+
+    /// Takes out every hand that uses one of the given cards
+    pub fn remove_blocked(&mut self, cards: &[Card]) {
+        for (index, weight) in self.weights.iter_mut().enumerate() {
+            if *weight > 0.0 && combo_at(index).is_some_and(|hand| blocked(&hand, cards)) {
+                *weight = 0.0;
+            }
+        }
+    }
+
+    /// Sum of all the weights. For a range where every hand has weight 0 or 1, this is the number of combos.
+    pub fn total(&self) -> f64 {
+        self.weights.iter().sum()
+    }
+
+    /// Number of hands still in the range
+    pub fn live(&self) -> usize {
+        self.weights.iter().filter(|&&weight| weight > 0.0).count()
+    }
+
+    /// Scales the weights so they add up to one. Does nothing to an empty range.
+    pub fn normalize(&mut self) {
+        let total = self.total();
+        if total > 0.0 {
+            self.weights.iter_mut().for_each(|weight| *weight /= total);
+        }
+    }
+
+    /// The hands still in the range, with their weights
+    pub fn iter(&self) -> impl Iterator<Item=(CardHand, f64)> + '_ {
+        self.weights.iter()
+            .enumerate()
+            .filter(|(_, &weight)| weight > 0.0)
+            .filter_map(|(index, &weight)| combo_at(index).map(|hand| (hand, weight)))
+    }
+
+    /// Something to draw hands from the range with, leaving out hands that use any of the dead cards.
+    /// Gives `None` if there is nothing left to draw.
+    pub fn sampler(&self, dead: &[Card]) -> Option<RangeSampler> {
+        // Running totals of the weights, to pick hands from with a binary search
+        let mut total = 0.0;
+        let cumulative: Vec<(CardHand, f64)> = self.iter()
+            .filter(|(hand, _)| !blocked(hand, dead))
+            .map(|(hand, weight)| {
+                total += weight;
+                (hand, total)
+            })
+            .collect();
+        if cumulative.is_empty() {
+            None
+        } else {
+            Some(RangeSampler { cumulative, total })
+        }
+    }
+
+    /// Multiplies the weight of each hand by what `factor` gives for it
+    pub fn reweight<F>(&mut self, mut factor: F) where F: FnMut(&CardHand) -> f64 {
+        for (index, weight) in self.weights.iter_mut().enumerate() {
+            if *weight > 0.0 {
+                if let Some(hand) = combo_at(index) {
+                    *weight *= factor(&hand).max(0.0);
+                }
+            }
+        }
+    }
+}
+
+impl Default for Range {
+    fn default() -> Range {
+        Range::full()
+    }
+}
+
+impl Range {
+    /// Parses range notation, where `+` and `-` go by the given value ordering (lowest first).
+    ///
+    /// Items are separated by commas, and can be a pair (`TT`), a pair and everything above it (`TT+`),
+    /// a run of pairs (`22-55`), suited or offsuit hands or both (`AKs`, `AKo`, `AK`), the same with the
+    /// kicker going up to just under the top card (`A2s+`) or over a run (`A2s-A5s`), or a single combo (`AsKh`).
+    /// Any item can end in a weight, like `QQ:0.5`. Later items overwrite the weights of earlier ones.
+    pub fn parse(notation: &str, ordering: &[CardValue; 13]) -> Result<Range, RangeError> {
+        let mut range = Range::empty();
+        for item in notation.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+            let (hands, weight) = match item.find(':') {
+                Some(colon) => {
+                    let weight = item[colon + 1..].trim();
+                    let weight = weight.parse::<f64>().ok()
+                        .filter(|weight| (0.0..=1.0).contains(weight))
+                        .ok_or_else(|| RangeError::InvalidWeight(weight.to_string()))?;
+                    (item[..colon].trim(), weight)
+                },
+                None => (item, 1.0),
+            };
+            for hand in parse_item(hands, ordering)? {
+                range.set_weight(&hand, weight);
+            }
+        }
+        Ok(range)
+    }
+
+    /// Writes the range in range notation, using the given value ordering (lowest first).
+    /// Whole classes of hands are grouped together where they can be, and anything else is listed combo by combo.
+    pub fn notation(&self, ordering: &[CardValue; 13]) -> String {
+        let mut items = vec![];
+
+        // Pairs, from the top down, in runs of the same weight
+        let pairs: Vec<Option<f64>> = (0..13).map(|rank| self.uniform(&class_hands(ordering, rank, rank, None))).collect();
+        let mut rank = 13;
+        while rank > 0 {
+            rank -= 1;
+            match pairs[rank] {
+                Some(weight) if weight > 0.0 => {
+                    let mut bottom = rank;
+                    while bottom > 0 && pairs[bottom - 1] == Some(weight) {
+                        bottom -= 1;
+                    }
+                    let (top, low) = (ordering[rank], ordering[bottom]);
+                    items.push(with_weight(if rank == 12 && bottom < rank {
+                        format!("{}{}+", low, low)
+                    } else if bottom < rank {
+                        format!("{}{}-{}{}", top, top, low, low)
+                    } else {
+                        format!("{}{}", top, top)
+                    }, weight));
+                    rank = bottom;
+                },
+                Some(_) => {},
+                None => items.extend(self.listed(&class_hands(ordering, rank, rank, None))),
+            }
+        }
+
+        // Everything else, by top card and then kicker, from the top down
+        for high in (1..13).rev() {
+            // What each kicker looks like as a class: both kinds together, or suited and offsuit on their own
+            let kinds: Vec<[Option<f64>; 3]> = (0..high).map(|low| {
+                let suited = self.uniform(&class_hands(ordering, high, low, Some(true)));
+                let offsuit = self.uniform(&class_hands(ordering, high, low, Some(false)));
+                match (suited, offsuit) {
+                    (Some(suited), Some(offsuit)) if suited == offsuit => [Some(suited), Some(0.0), Some(0.0)],
+                    (suited, offsuit) => [Some(0.0), suited, offsuit],
+                }
+            }).collect();
+            for (kind, suffix) in ["", "s", "o"].iter().enumerate() {
+                let mut low = high;
+                while low > 0 {
+                    low -= 1;
+                    match kinds[low][kind] {
+                        Some(weight) if weight > 0.0 => {
+                            let mut bottom = low;
+                            while bottom > 0 && kinds[bottom - 1][kind] == Some(weight) {
+                                bottom -= 1;
+                            }
+                            let (top, kicker, last) = (ordering[high], ordering[low], ordering[bottom]);
+                            items.push(with_weight(if low == high - 1 && bottom < low {
+                                format!("{}{}{}+", top, last, suffix)
+                            } else if bottom < low {
+                                format!("{}{}{}-{}{}{}", top, kicker, suffix, top, last, suffix)
+                            } else {
+                                format!("{}{}{}", top, kicker, suffix)
+                            }, weight));
+                            low = bottom;
+                        },
+                        Some(_) => {},
+                        None => {
+                            let suited = kind == 1;
+                            items.extend(self.listed(&class_hands(ordering, high, low, Some(suited))));
+                        },
+                    }
+                }
+            }
+        }
+        items.join(", ")
+    }
+
+    /// Weight of every hand in either range, taking the larger weight where both have a hand
+    pub fn union(&self, other: &Range) -> Range {
+        self.combine(other, f64::max)
+    }
+
+    /// Weight of every hand in both ranges, taking the smaller weight
+    pub fn intersection(&self, other: &Range) -> Range {
+        self.combine(other, f64::min)
+    }
+
+    /// Takes the weights of the other range away from this one
+    pub fn difference(&self, other: &Range) -> Range {
+        self.combine(other, |a, b| (a - b).max(0.0))
+    }
+
+    fn combine<F>(&self, other: &Range, combine: F) -> Range where F: Fn(f64, f64) -> f64 {
+        Range {
+            weights: self.weights.iter().zip(&other.weights).map(|(&a, &b)| combine(a, b)).collect(),
+        }
+    }
+
+    /// The weight all the given hands share, if they do
+    fn uniform(&self, hands: &[CardHand]) -> Option<f64> {
+        let weight = self.weight(&hands[0]);
+        if hands.iter().all(|hand| self.weight(hand) == weight) {
+            Some(weight)
+        } else {
+            None
+        }
+    }
+
+    /// The given hands that are in the range, one item each
+    fn listed(&self, hands: &[CardHand]) -> Vec<String> {
+        hands.iter()
+            .filter(|hand| self.weight(hand) > 0.0)
+            .map(|hand| with_weight(format!("{}{}", hand.0[0], hand.0[1]), self.weight(hand)))
+            .collect()
+    }
+}
+
+impl FromStr for Range {
+    type Err = RangeError;
+
+    /// Parses range notation with the usual value ordering
+    fn from_str(s: &str) -> Result<Range, RangeError> {
+        Range::parse(s, &CardValue::ALL)
+    }
+}
+
+impl fmt::Display for Range {
+    /// Writes range notation with the usual value ordering
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", self.notation(&CardValue::ALL))
+    }
+}
+
+/// Adds a weight to a notation item, unless it is 1.
+/// The weight is written out in full, so that parsing it gives back the same range.
+fn with_weight(item: String, weight: f64) -> String {
+    if weight == 1.0 {
+        item
+    } else {
+        format!("{}:{}", item, weight)
+    }
+}
+
+/// Every combo of the given ranks, suited, offsuit, or both.
+/// The higher card comes first, and for pairs the suitedness is ignored.
+fn class_hands(ordering: &[CardValue; 13], high: usize, low: usize, suited: Option<bool>) -> Vec<CardHand> {
+    let mut hands = vec![];
+    for (i, &first) in CardSuit::ALL.iter().enumerate() {
+        for (j, &second) in CardSuit::ALL.iter().enumerate() {
+            let keep = if high == low {
+                i < j
+            } else {
+                suited.map_or(true, |suited| (first == second) == suited)
+            };
+            if keep {
+                hands.push(CardHand([Card::new(first, ordering[high]), Card::new(second, ordering[low])]));
+            }
+        }
+    }
+    hands
+}
+
+/// A class of hands in notation: two ranks (high first) and whether they are suited, if that was given
+struct Class {
+    high: usize,
+    low: usize,
+    suited: Option<bool>,
+}
+
+impl Class {
+    fn parse(notation: &str, ordering: &[CardValue; 13]) -> Result<Class, RangeError> {
+        let malformed = || RangeError::Malformed(notation.to_string());
+        let chars: Vec<char> = notation.chars().collect();
+        if chars.len() < 2 || chars.len() > 3 {
+            return Err(malformed());
+        }
+        let rank = |chr: char| -> Result<usize, RangeError> {
+            let value = chr.to_string().parse::<CardValue>()?;
+            ordering.iter().position(|&x| x == value).ok_or_else(malformed)
+        };
+        let (a, b) = (rank(chars[0])?, rank(chars[1])?);
+        let suited = match chars.get(2) {
+            None => None,
+            Some('s') if a != b => Some(true),
+            Some('o') if a != b => Some(false),
+            Some(_) => return Err(malformed()),
+        };
+        Ok(Class {
+            high: a.max(b),
+            low: a.min(b),
+            suited,
+        })
+    }
+
+    fn hands(&self, ordering: &[CardValue; 13]) -> Vec<CardHand> {
+        class_hands(ordering, self.high, self.low, self.suited)
+    }
+}
+
+/// The hands one item of range notation (without its weight) stands for
+fn parse_item(item: &str, ordering: &[CardValue; 13]) -> Result<Vec<CardHand>, RangeError> {
+    let malformed = || RangeError::Malformed(item.to_string());
+    if let Some(dash) = item.find('-') {
+        let from = Class::parse(item[..dash].trim(), ordering)?;
+        let to = Class::parse(item[dash + 1..].trim(), ordering)?;
+        let classes: Vec<Class> = if from.high == from.low && to.high == to.low {
+            (from.low.min(to.low)..=from.low.max(to.low)).map(|rank| Class { high: rank, low: rank, suited: None }).collect()
+        } else if from.high == to.high && from.suited == to.suited && from.high != from.low && to.high != to.low {
+            (from.low.min(to.low)..=from.low.max(to.low)).map(|low| Class { high: from.high, low, suited: from.suited }).collect()
+        } else {
+            return Err(malformed());
+        };
+        Ok(classes.iter().flat_map(|class| class.hands(ordering)).collect())
+    } else if let Some(class) = item.strip_suffix('+') {
+        let class = Class::parse(class.trim(), ordering)?;
+        let classes: Vec<Class> = if class.high == class.low {
+            (class.low..13).map(|rank| Class { high: rank, low: rank, suited: None }).collect()
+        } else {
+            (class.low..class.high).map(|low| Class { high: class.high, low, suited: class.suited }).collect()
+        };
+        Ok(classes.iter().flat_map(|class| class.hands(ordering)).collect())
+    } else if item.len() == 4 && item.is_ascii() {
+        let first = item[..2].parse::<Card>()?;
+        let second = item[2..].parse::<Card>()?;
+        if first == second {
+            return Err(malformed());
+        }
+        Ok(vec![CardHand([first, second])])
+    } else {
+        Ok(Class::parse(item, ordering)?.hands(ordering))
+    }
+}
+
+/// Describes what can go wrong parsing range notation
+#[derive(Debug)]
+pub enum RangeError {
+    InvalidCard(CardConversionError),
+    InvalidWeight(String),
+    Malformed(String),
+}
+
+impl Error for RangeError {}
+
+impl fmt::Display for RangeError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RangeError::InvalidCard(e) => write!(fmt, "Invalid card in range: {}", e),
+            RangeError::InvalidWeight(s) => write!(fmt, "Weight should be between 0 and 1: {}", s),
+            RangeError::Malformed(s) => write!(fmt, "Not a range: {}", s),
+        }
+    }
+}
+
+impl From<CardConversionError> for RangeError {
+    fn from(e: CardConversionError) -> RangeError {
+        RangeError::InvalidCard(e)
+    }
+}
+
+/// Draws hands from a range in proportion to their weights, made with `Range::sampler`
+#[derive(Debug, Clone)]
+pub struct RangeSampler {
+    cumulative: Vec<(CardHand, f64)>,
+    total: f64,
+}
+
+impl RangeSampler {
+    pub fn sample(&self, rng: &mut SeededRng) -> CardHand {
+        let pick = rng.next_f64() * self.total;
+        let index = self.cumulative.partition_point(|&(_, cumulative)| cumulative <= pick);
+        self.cumulative[index.min(self.cumulative.len() - 1)].0
+    }
+}
+
+/// Whether a hand uses one of the given cards
+pub fn blocked(hand: &CardHand, cards: &[Card]) -> bool {
+    hand.0.iter().any(|card| cards.contains(card))
+}
+
+/// How likely a hand was to take an action. The numbers only matter relative to each other,
+/// so they don't need to add up to one over the possible actions.
+pub trait ActionModel {
+    /// Likelihood that `opponent` holding `hand` took `action` in `rs_before`
+    fn likelihood(&self, hand: &CardHand, rs_before: &RoundState, action: Action, opponent: usize) -> f64;
+}
+
+impl<F> ActionModel for F where F: Fn(&CardHand, &RoundState, Action, usize) -> f64 {
+    fn likelihood(&self, hand: &CardHand, rs_before: &RoundState, action: Action, opponent: usize) -> f64 {
+        self(hand, rs_before, action, opponent)
+    }
+}
+
+/// Assumes actions say nothing about the hand, so only card removal narrows the range
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Uniform;
+
+impl ActionModel for Uniform {
+    fn likelihood(&self, _hand: &CardHand, _rs_before: &RoundState, _action: Action, _opponent: usize) -> f64 {
+        1.0
+    }
+}
+
+/// Assumes the opponent plays preflop by hand strength, scored with the Chen formula:
+/// strong hands raise more, weak hands fold more, and everything calls some of the time.
+/// Actions after the flop are taken as uniform.
+#[derive(Debug, Clone)]
+pub struct ChenModel {
+    ordering: [CardValue; 13],
+    floor: f64,
+}
+
+impl ChenModel {
+    /// Scores hands with the given value ordering (lowest first)
+    pub fn new(ordering: [CardValue; 13]) -> ChenModel {
+        ChenModel {
+            ordering,
+            floor: 0.05,
+        }
+    }
+
+    /// Changes the smallest likelihood any hand gets for any action (0.05 by default),
+    /// so a surprising action never rules a hand out completely
+    pub fn with_floor(mut self, floor: f64) -> ChenModel {
+        self.floor = floor;
+        self
+    }
+}
+
+impl Default for ChenModel {
+    fn default() -> ChenModel {
+        ChenModel::new(CardValue::ALL)
+    }
+}
+
+impl ActionModel for ChenModel {
+    fn likelihood(&self, hand: &CardHand, rs_before: &RoundState, action: Action, _opponent: usize) -> f64 {
+        if rs_before.street > 0 {
+            return 1.0;
+        }
+        // Chen scores go from about -1 to 20
+        let strength = ((chen_score(&self.ordering, hand) as f64 + 1.0) / 21.0).clamp(0.0, 1.0);
+        let raise = strength * strength;
+        let fold = (1.0 - strength) * (1.0 - strength);
+        let likelihood = match action {
+            Action::Raise(_) => raise,
+            Action::Fold => fold,
+            Action::Call | Action::Check => 1.0 - raise - fold,
+        };
+        likelihood.max(self.floor)
+    }
+}
+
+/// Follows the opponent's range through a round.
+/// Call `new_round` in `handle_new_round` and `observe` in `handle_opponent_action`,
+/// or `rebuild` from scratch whenever it is our turn.
+pub struct RangeTracker {
+    model: Box<dyn ActionModel + Send + Sync>,
+    range: Range,
+}
+
+impl RangeTracker {
+    pub fn new<M>(model: M) -> RangeTracker where M: ActionModel + Send + Sync + 'static {
+        RangeTracker {
+            model: Box::new(model),
+            range: Range::full(),
+        }
+    }
+
+    /// Starts over with every hand that doesn't use our cards or the board
+    pub fn new_round(&mut self, rs: &RoundState, player_index: usize) {
+        self.range = Range::full();
+        if let Some(hand) = rs.hands[player_index] {
+            self.range.remove_blocked(hand.cards());
+        }
+        self.update(rs);
+    }
+
+    /// Takes out hands that use board cards which have come out since the last update
+    pub fn update(&mut self, rs: &RoundState) {
+        self.range.remove_blocked(&rs.deck.0);
+    }
+
+    /// Reweights the range after the opponent took `action` in `rs_before`.
+    /// If the model rules out every hand left, the action is ignored rather than emptying the range.
+    pub fn observe(&mut self, rs_before: &RoundState, action: Action, opponent: usize) {
+        self.update(rs_before);
+        let mut range = self.range.clone();
+        let model = &self.model;
+        range.reweight(|hand| model.likelihood(hand, rs_before, action, opponent));
+        if range.total() > 0.0 {
+            range.normalize();
+            self.range = range;
+        }
+    }
+
+    /// Narrows the range down to the hand the opponent showed, as `handle_reveal` gets it.
+    /// A range only holds two card hands, so a bigger holding leaves it as it was.
+    pub fn reveal(&mut self, hand: &HoleCards) {
+        if let Some(hand) = hand.pair() {
+            self.range = Range::empty();
+            self.range.set_weight(&hand, 1.0);
+        }
+    }
+
+    /// Works the range out again from the start of the round, replaying every opponent action in `rs`
+    pub fn rebuild(&mut self, rs: &RoundState, player_index: usize) {
+        self.range = Range::full();
+        if let Some(hand) = rs.hands[player_index] {
+            self.range.remove_blocked(hand.cards());
+        }
+        for (before, played) in history::decisions(rs) {
+            if played.player != player_index {
+                self.observe(before, played.action, played.player);
+            }
+        }
+        self.update(rs);
+    }
+
+    /// The opponent's range as it stands
+    pub fn range(&self) -> &Range {
+        &self.range
+    }
+
+    /// The share of the range made up by the given hands, e.g. to see how often they have a premium hand
+    pub fn share<F>(&self, mut filter: F) -> f64 where F: FnMut(&CardHand) -> bool {
+        let total = self.range.total();
+        if total <= 0.0 {
+            return 0.0;
+        }
+        self.range.iter().filter(|(hand, _)| filter(hand)).map(|(_, weight)| weight).sum::<f64>() / total
+    }
+}
+
+impl fmt::Debug for RangeTracker {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("RangeTracker").field("range", &self.range).finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::CardDeck;
+    use crate::states::{StateResult, BIG_BLIND, SMALL_BLIND, STARTING_STACK};
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn hand(s: &str) -> CardHand {
+        CardHand([s[..2].parse().unwrap(), s[2..].parse().unwrap()])
+    }
+
+    /// The start of a round where we are the big blind holding `ours`, so the opponent acts first
+    fn start(ours: &str) -> RoundState {
+        RoundState {
+            button: 0,
+            street: 0,
+            pips: [SMALL_BLIND, BIG_BLIND],
+            stacks: [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [None, HoleCards::new(&cards(ours))],
+            deck: CardDeck(vec![]),
+            previous: None,
+        }
+    }
+
+    /// Plays an action that doesn't end the round, dealing the flop the way the runner does when it comes
+    fn act(rs: &RoundState, action: Action) -> RoundState {
+        match rs.proceed(action) {
+            StateResult::Round(mut next) => {
+                if next.button == 1 && next.pips == [0, 0] {
+                    next.deck = CardDeck(cards("Ah 9d 4c"));
+                    next.street = 3;
+                }
+                next
+            },
+            StateResult::Terminal(_) => panic!("{:?} ended the round", action),
+        }
+    }
+
+    fn live(notation: &str) -> usize {
+        notation.parse::<Range>().unwrap().live()
+    }
+
+    #[test]
+    fn combo_indices_round_trip() {
+        for index in 0..COMBOS {
+            assert_eq!(combo_index(&combo_at(index).unwrap()), index);
+        }
+        assert!(combo_at(COMBOS).is_none());
+        assert_eq!(combo_index(&hand("AsKh")), combo_index(&hand("KhAs")));
+    }
+
+    #[test]
+    fn items_cover_the_right_combos() {
+        assert_eq!(live("AA"), 6);
+        assert_eq!(live("TT+"), 30);
+        assert_eq!(live("22-55"), 24);
+        assert_eq!(live("AKs"), 4);
+        assert_eq!(live("AKo"), 12);
+        assert_eq!(live("AK"), 16);
+        assert_eq!(live("A2s+"), 48);
+        assert_eq!(live("A2s-A5s"), 16);
+        assert_eq!(live("AsKh"), 1);
+        assert_eq!(live("TT+, AKs, AsKh"), 35);
+    }
+
+    #[test]
+    fn later_items_overwrite_weights() {
+        let range: Range = "QQ+, KK:0.25".parse().unwrap();
+        assert_eq!(range.weight(&hand("AsAh")), 1.0);
+        assert_eq!(range.weight(&hand("KsKh")), 0.25);
+        assert_eq!(range.weight(&hand("JsJh")), 0.0);
+    }
+
+    #[test]
+    fn plus_follows_the_ordering() {
+        // Twos play high and Aces low
+        let mut ordering = CardValue::ALL;
+        ordering.reverse();
+        let range = Range::parse("33+", &ordering).unwrap();
+        assert_eq!(range.live(), 12);
+        assert_eq!(range.weight(&hand("2s2h")), 1.0);
+        assert_eq!(range.weight(&hand("AsAh")), 0.0);
+    }
+
+    #[test]
+    fn bad_notation_is_rejected() {
+        assert!(matches!("QQ:2".parse::<Range>(), Err(RangeError::InvalidWeight(_))));
+        assert!(matches!("QQ:x".parse::<Range>(), Err(RangeError::InvalidWeight(_))));
+        assert!("QQQ".parse::<Range>().is_err());
+        assert!("AsXh".parse::<Range>().is_err());
+    }
+
+    #[test]
+    fn notation_groups_classes() {
+        let notation = "TT+, A5s-A2s, AKo:0.5";
+        assert_eq!(notation.parse::<Range>().unwrap().to_string(), notation);
+    }
+
+    #[test]
+    fn notation_round_trips_exactly() {
+        let mut range: Range = "TT+, A2s-A5s, KQo:0.3, 72o".parse().unwrap();
+        range.set_weight(&hand("7h2c"), 1.0 / 3.0);
+        range.normalize();
+        let printed = range.to_string();
+        let parsed: Range = printed.parse().unwrap();
+        assert_eq!(parsed, range);
+        assert_eq!(parsed.to_string(), printed);
+    }
+
+    #[test]
+    fn tracker_reveal_takes_hole_cards() {
+        let mut tracker = RangeTracker::new(Uniform);
+        tracker.reveal(&HoleCards::from(hand("AsKh")));
+        assert_eq!(tracker.range().live(), 1);
+        assert_eq!(tracker.range().weight(&hand("KhAs")), 1.0);
+
+        let mut tracker = RangeTracker::new(Uniform);
+        let omaha = ["As", "Kh", "Qd", "Jc"].iter().map(|card| card.parse().unwrap()).collect::<Vec<Card>>();
+        tracker.reveal(&HoleCards::new(&omaha).unwrap());
+        assert_eq!(tracker.range().live(), COMBOS);
+    }
+
+    #[test]
+    fn tracker_leans_towards_strong_hands_after_a_raise() {
+        let rs = start("7h 2c");
+        let premium = |hand: &CardHand| "TT+, AK".parse::<Range>().unwrap().weight(hand) > 0.0;
+        let mut tracker = RangeTracker::new(ChenModel::default());
+        tracker.new_round(&rs, 1);
+        assert_eq!(tracker.range().live(), 1225);
+        let before = tracker.share(premium);
+
+        tracker.observe(&rs, Action::Raise(6), 0);
+        // Nothing is ruled out, but the premiums now make up more of the range
+        assert_eq!(tracker.range().live(), 1225);
+        assert!(tracker.share(premium) > 2.0 * before);
+        assert!(tracker.range().weight(&hand("AsAd")) > 4.0 * tracker.range().weight(&hand("8s3d")));
+        assert!((tracker.range().total() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn tracker_ignores_actions_that_rule_out_everything() {
+        let rs = start("7h 2c");
+        let mut tracker = RangeTracker::new(|_: &CardHand, _: &RoundState, _: Action, _: usize| 0.0);
+        tracker.new_round(&rs, 1);
+        let before = tracker.range().clone();
+        tracker.observe(&rs, Action::Raise(6), 0);
+        assert_eq!(tracker.range(), &before);
+    }
+
+    #[test]
+    fn tracker_rebuilds_what_it_observed() {
+        let start = start("7h 2c");
+        let raised = act(&start, Action::Raise(6));
+        let reraised = act(&raised, Action::Raise(18));
+        let flop = act(&reraised, Action::Call);
+        let checked = act(&flop, Action::Check);
+        let now = act(&checked, Action::Raise(10));
+
+        let mut incremental = RangeTracker::new(ChenModel::default());
+        incremental.new_round(&start, 1);
+        incremental.observe(&start, Action::Raise(6), 0);
+        incremental.observe(&reraised, Action::Call, 0);
+        incremental.update(&flop);
+        incremental.observe(&checked, Action::Raise(10), 0);
+
+        let mut rebuilt = RangeTracker::new(ChenModel::default());
+        rebuilt.rebuild(&now, 1);
+        assert_eq!(rebuilt.range(), incremental.range());
+        // Our cards and the flop are both out
+        assert_eq!(rebuilt.range().live(), 1081);
+    }
+}
@@ -0,0 +1,87 @@
+//! A small seedable random number generator
+//!
+//! Bots that sample need randomness that can be replayed, so the same seed always
+//! gives the same match. This is xorshift64*, which is fast and more than good enough
+//! for shuffling cards, but is not meant for anything that needs to be unpredictable.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Seedable xorshift64* generator
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub fn new(seed: u64) -> SeededRng {
+        // Run the seed through splitmix64, so that similar seeds don't give similar streams,
+        // and a seed of 0 (which xorshift would never leave) still works
+        let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^= z >> 31;
+        SeededRng { state: if z == 0 { 0x9E37_79B9_7F4A_7C15 } else { z } }
+    }
+
+    /// Seeds from the system clock, for when the match doesn't need to be reproducible
+    pub fn from_time() -> SeededRng {
+        let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_nanos() as u64).unwrap_or(0);
+        SeededRng::new(nanos)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state ^= self.state >> 12;
+        self.state ^= self.state << 25;
+        self.state ^= self.state >> 27;
+        self.state.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// A number in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// A number in `[0, bound)`. Panics if `bound` is 0.
+    pub fn below(&mut self, bound: usize) -> usize {
+        assert!(bound > 0, "Expected a non-zero bound");
+        // Lemire's multiply and shift. The bias is negligible for bounds this small
+        (((self.next_u64() >> 32) * bound as u64) >> 32) as usize
+    }
+
+    /// A number in `[low, high]`
+    pub fn between(&mut self, low: u32, high: u32) -> u32 {
+        if high <= low {
+            low
+        } else {
+            low + self.below((high - low) as usize + 1) as u32
+        }
+    }
+
+    /// True with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    /// Shuffles the slice in place (Fisher-Yates)
+    pub fn shuffle<T>(&mut self, items: &mut [T]) {
+        for i in (1..items.len()).rev() {
+            let j = self.below(i + 1);
+            items.swap(i, j);
+        }
+    }
+
+    /// Shuffles just the first `count` items into place, which is all a deal needs
+    pub fn partial_shuffle<T>(&mut self, items: &mut [T], count: usize) {
+        let len = items.len();
+        for i in 0..count.min(len) {
+            let j = i + self.below(len - i);
+            items.swap(i, j);
+        }
+    }
+}
+
+impl Default for SeededRng {
+    fn default() -> SeededRng {
+        SeededRng::from_time()
+    }
+}
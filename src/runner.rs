@@ -1,562 +1,1201 @@
 use std::net::{TcpStream, Shutdown, ToSocketAddrs};
 use super::bot::PokerBot;
-use std::io::{prelude::*, BufReader, ErrorKind};
+use std::io::{self, prelude::*, BufReader, ErrorKind};
 use crate::into_cards;
 use super::actions::{Action, ActionType};
+use super::sanitizer::{IllegalAction, Correction};
+use super::config::{RunnerConfig, ConfigError, ErrorClass, ErrorPolicy};
 use super::states::{SMALL_BLIND, BIG_BLIND, STARTING_STACK, GameState, RoundState, TerminalState, StateResult};
-use super::cards::{Card, CardHand, CardDeck};
+use super::cards::{Card, CardDeck, HoleCards};
+use super::session::{SessionRecorder, Session, SessionEvent, ReplayOutcome, Divergence};
+use super::clock::Deadline;
+use super::summary::{MatchSummary, EndReason};
 use std::time::{Duration, Instant};
-use super::thread_pool::ThreadPool;
 use std::sync::{
-    atomic::{AtomicUsize, AtomicBool, Ordering},
-    Arc, Mutex, RwLock,
-    TryLockError,
-    RwLockReadGuard, RwLockWriteGuard,
-    MutexGuard,
-    mpsc::channel,
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+    mpsc::{channel, Receiver, Sender, RecvTimeoutError},
 };
-use std::thread;
-use approx::relative_eq;
+use std::thread::{self, JoinHandle};
+use std::fmt;
 use std::error::Error;
-use log::{trace, error};
 
-const CONNECT_TIMEOUT: u64 = 10; // seconds
-const WRITE_TIMEOUT: u64 = 1; // microseconds
-const PLAYER_INDEX_LOAD_ORDERING: Ordering = Ordering::SeqCst;
-const PLAYER_INDEX_STOR_ORDERING: Ordering = Ordering::SeqCst;
-const MAX_THREAD_COUNT: usize = 16;
-const SLEEP_DURATION: u64 = 1; // milliseconds
-const COMP_TIME: u64 = 60; // microseconds
+type BoxedBot<E> = Box<dyn PokerBot<Error=E> + Send + Sync>;
+type SharedRecorder = Arc<Mutex<SessionRecorder>>;
 
+// Logs under the runner's target, but only up to the level it was configured with
+macro_rules! runner_log {
+    ($max:expr, $level:ident, $($arg:tt)+) => {
+        if log::Level::$level <= $max {
+            log::log!(target: "PBRunner", log::Level::$level, $($arg)+);
+        }
+    };
+}
+
+/// Plays a match against the engine.
+///
+/// Three threads are involved. A reader thread blocks on the engine and hands every line to the
+/// main thread, which applies the updates in order and sends exactly one response per line.
+/// The bot lives on its own thread, and is told about everything through a single queue,
+/// so it sees updates in the order they happened, and can be cut off when it overruns its deadline.
+/// Every wait is a blocking one, so the runner costs nothing while it waits.
 pub struct Runner {
-    socket: Arc<Mutex<Socket>>,
+    socket: Socket,
+    closing: Arc<AtomicBool>,
     runner_start: Instant,
-    thread_count: usize,
+    config: Arc<RunnerConfig>,
 }
 
+/// Our end of the connection, which we write responses to
 #[derive(Debug)]
 struct Socket {
-    stream: BufReader<TcpStream>,
-    read_queue: Vec<ServerAction>,
-    write_action: Vec<Action>,
-    round_sent: AtomicBool,
+    stream: TcpStream,
+    recorder: Option<SharedRecorder>,
+    config: Arc<RunnerConfig>,
+    // Set once the engine quit or we hung up, after which I/O faults are expected
+    closing: Arc<AtomicBool>,
+    // An I/O fault we fell back from, which ends the match with what we have
+    fault: Option<String>,
+}
+
+/// Describes the faults that can stop the runner
+#[derive(Debug)]
+pub enum RunnerError {
+    Io(io::Error),
+    Config(ConfigError),
+    Protocol(String),
+    Bot(String),
+    Illegal(IllegalAction),
+    Poisoned(&'static str),
+}
+
+impl RunnerError {
+    fn bot<E: fmt::Display>(e: E) -> RunnerError {
+        RunnerError::Bot(e.to_string())
+    }
+
+    /// The class of fault, which decides the error policy that applies.
+    /// Config errors happen before anything is played, so they have none.
+    pub fn class(&self) -> Option<ErrorClass> {
+        match self {
+            RunnerError::Io(_) => Some(ErrorClass::Io),
+            RunnerError::Config(_) => None,
+            RunnerError::Protocol(_) => Some(ErrorClass::Protocol),
+            RunnerError::Bot(_) | RunnerError::Illegal(_) => Some(ErrorClass::Bot),
+            RunnerError::Poisoned(_) => Some(ErrorClass::Poisoned),
+        }
+    }
+}
+
+impl From<io::Error> for RunnerError {
+    fn from(e: io::Error) -> RunnerError {
+        RunnerError::Io(e)
+    }
+}
+
+impl From<ConfigError> for RunnerError {
+    fn from(e: ConfigError) -> RunnerError {
+        RunnerError::Config(e)
+    }
+}
+
+impl Error for RunnerError {}
+
+impl fmt::Display for RunnerError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RunnerError::Io(e) => write!(fmt, "Engine I/O error: {}", e),
+            RunnerError::Config(e) => write!(fmt, "Invalid runner config: {}", e),
+            RunnerError::Protocol(e) => write!(fmt, "Protocol error: {}", e),
+            RunnerError::Bot(e) => write!(fmt, "Bot error: {}", e),
+            RunnerError::Illegal(e) => write!(fmt, "{}", e),
+            RunnerError::Poisoned(device_id) => write!(fmt, "Resource {} poisoned", device_id),
+        }
+    }
+}
+
+/// Deals with a fault that trying again would not fix: carries on if the policy
+/// says to fall back, and hands the error back otherwise
+fn tolerate(config: &RunnerConfig, error: RunnerError) -> Result<(), RunnerError> {
+    runner_log!(config.log_level(), Error, "{}", error);
+    match error.class().map(|class| config.error_policy(class)) {
+        Some(ErrorPolicy::Fallback) => Ok(()),
+        _ => Err(error),
+    }
+}
+
+/// Runs a step that can fail, retrying it or falling back as the error policy says
+fn attempt<T, S, F>(config: &RunnerConfig, mut step: S, fallback: F) -> Result<T, RunnerError>
+    where S: FnMut() -> Result<T, RunnerError>, F: FnOnce(&RunnerError) -> T {
+    let mut retries = 0;
+    loop {
+        let error = match step() {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        runner_log!(config.log_level(), Error, "{}", error);
+        match error.class().map(|class| config.error_policy(class)) {
+            Some(ErrorPolicy::Retry(max)) if retries < max => retries += 1,
+            Some(ErrorPolicy::Fallback) => return Ok(fallback(&error)),
+            _ => return Err(error),
+        }
+    }
+}
+
+/// Writes to the session recording, if there is one. Failing to record is not worth stopping the match over.
+fn record<F>(recorder: &Option<SharedRecorder>, config: &RunnerConfig, write: F) -> Result<(), RunnerError> where F: FnOnce(&mut SessionRecorder) -> io::Result<()> {
+    if let Some(recorder) = recorder {
+        let mut recorder = match recorder.lock() {
+            Ok(guard) => guard,
+            Err(poisoned) => {
+                tolerate(config, RunnerError::Poisoned("session"))?;
+                poisoned.into_inner()
+            },
+        };
+        if let Err(e) = write(&mut recorder) {
+            runner_log!(config.log_level(), Error, "Failed to record session {}", e);
+        }
+    }
+    Ok(())
 }
 
 #[derive(Debug, Clone)]
 enum ServerAction {
     SetGameClock(f32), // T
     SetPlayerIndex(usize), // P
-    SetPlayerHand(CardHand), // H
+    SetPlayerHand(HoleCards), // H
     PlayFold, // F
     PlayCall, // C
     PlayCheck, // K
     PlayRaise(u32), // R
     UpdateDeck(CardDeck), // B
-    RevealOpponentHand(CardHand), // O
+    RevealOpponentHand(HoleCards), // O
     Delta(i32), // D
     Quit // Q
 }
 
-// Actions that we should preserve the ordering for, so we
-// push them into a queue, and have only one thread that controls them
+// Updates that change the match state, which have to be applied in the order they were sent,
+// since the server doesn't tell us who did what
 #[derive(Debug)]
 enum PreservedOrdering {
     Action(Action),
     Delta(i32),
-    StartRound(CardHand),
-    Reveal(CardHand),
+    StartRound(HoleCards),
+    Reveal(HoleCards),
     UpdateDeck(CardDeck),
     SetPlayerIndex(usize),
 }
 
-impl Socket {
-    fn new(stream: BufReader<TcpStream>) -> Socket {
-        Socket {
-            stream,
-            read_queue: vec![],
-            write_action: vec![], // We always start off with checking to ack the server
-            round_sent: AtomicBool::new(false),
+impl ServerAction {
+    /// Parses a single clause sent by the engine, where hands have the given number of cards
+    fn parse(clause: &str, hole_cards: usize) -> Result<ServerAction, RunnerError> {
+        let malformed = |what: &str| RunnerError::Protocol(format!("{} in clause {:?}", what, clause));
+        let mut chars = clause.chars();
+        let act = chars.next().ok_or_else(|| malformed("Empty clause"))?;
+        let arg = chars.as_str();
+        let hand = |arg: &str| {
+            let cards: Vec<Card> = into_cards!(arg).map_err(|e| malformed(&e.to_string()))?;
+            match HoleCards::new(&cards) {
+                Some(hand) if hand.len() == hole_cards => Ok(hand),
+                _ => Err(malformed(&format!("Expected {} cards for a hand", hole_cards))),
+            }
+        };
+        Ok(match act {
+            'T' => ServerAction::SetGameClock(arg.parse::<f32>().map_err(|_| malformed("Expected float for game clock"))?),
+            'P' => ServerAction::SetPlayerIndex(arg.parse::<usize>().map_err(|_| malformed("Expected positive integer for player index"))?),
+            'H' => ServerAction::SetPlayerHand(hand(arg)?),
+            'F' => ServerAction::PlayFold,
+            'C' => ServerAction::PlayCall,
+            'K' => ServerAction::PlayCheck,
+            'R' => ServerAction::PlayRaise(arg.parse::<u32>().map_err(|_| malformed("Expected positive integer for raise amount"))?),
+            'B' => ServerAction::UpdateDeck(CardDeck(into_cards!(arg).map_err(|e| malformed(&e.to_string()))?)),
+            'O' => ServerAction::RevealOpponentHand(hand(arg)?),
+            'D' => ServerAction::Delta(arg.parse::<i32>().map_err(|_| malformed("Expected integer for delta"))?),
+            'Q' => ServerAction::Quit,
+            c => return Err(malformed(&format!("Unknown server command {}", c))),
+        })
+    }
+
+    /// Parses one line sent by the engine into its clauses.
+    /// Clauses that do not parse are skipped if the protocol error policy falls back.
+    fn parse_line(line: &str, config: &RunnerConfig) -> Result<Vec<ServerAction>, RunnerError> {
+        let mut clauses = vec![];
+        for clause in line.split_whitespace() {
+            match ServerAction::parse(clause, config.hole_cards()) {
+                Ok(clause) => clauses.push(clause),
+                Err(e) => tolerate(config, e)?,
+            }
         }
+        Ok(clauses)
     }
 
-    /// Returns an incoming messages from the engine.
-    fn receive(&mut self) -> Vec<ServerAction> {
-        self.read_queue.drain(..).collect()
+    /// Converts the clause into an update that has to be applied in order.
+    /// The game clock and quitting are handled as soon as they arrive, so they have none.
+    fn ordered(self) -> Option<PreservedOrdering> {
+        match self {
+            // Set player index (also referred to as "active")
+            ServerAction::SetPlayerIndex(index) => Some(PreservedOrdering::SetPlayerIndex(index)),
+            // Set our hand
+            ServerAction::SetPlayerHand(hand) => Some(PreservedOrdering::StartRound(hand)),
+            ServerAction::PlayFold => Some(PreservedOrdering::Action(Action::Fold)),
+            ServerAction::PlayCall => Some(PreservedOrdering::Action(Action::Call)),
+            ServerAction::PlayCheck => Some(PreservedOrdering::Action(Action::Check)),
+            ServerAction::PlayRaise(by) => Some(PreservedOrdering::Action(Action::Raise(by))),
+            // The deck was updated
+            ServerAction::UpdateDeck(deck) => Some(PreservedOrdering::UpdateDeck(deck)),
+            // Reveal the opponent's hand
+            ServerAction::RevealOpponentHand(hand) => Some(PreservedOrdering::Reveal(hand)),
+            // Delta has been calculated
+            ServerAction::Delta(delta) => Some(PreservedOrdering::Delta(delta)),
+            ServerAction::SetGameClock(_) | ServerAction::Quit => None,
+        }
     }
+}
 
-    // A logical separation between actual action, and just keep-alive messages
-    fn ping(&mut self) {
-        self.send(Action::Check);
+/// Encodes an action the way the engine expects it
+fn action_code(action: Action) -> String {
+    match action {
+        Action::Fold => "F".into(),
+        Action::Call => "C".into(),
+        Action::Check => "K".into(),
+        Action::Raise(amt) => format!("R{}", amt)
     }
+}
 
-    /// Send an action message to the engine
-    fn send(&mut self, action: Action) {
-        let ref mut socket = self.stream;
-
-        let code = match action {
-            Action::Fold => "F".into(),
-            Action::Call => "C".into(),
-            Action::Check => "K".into(),
-            Action::Raise(amt) => format!("R{}", amt)
+/// The action we fall back on when the bot cannot give us one in time
+fn safe_action(round_state: &RoundState) -> Action {
+    if (round_state.legal_actions() & ActionType::CHECK) == ActionType::CHECK {
+        Action::Check
+    } else {
+        Action::Fold
+    }
+}
+
+/// Hands the bot its budget, asks it for an action, and makes that action legal.
+/// Also returns the correction the sanitizer made, if any.
+fn decide<B, E>(bot: &mut B, config: &RunnerConfig, game_state: &GameState, round_state: &RoundState, player_index: usize, deadline: &Deadline) -> Result<(Action, Option<Correction>), RunnerError> where B: PokerBot<Error=E> + ?Sized, E: Error {
+    bot.handle_decision_budget(game_state, deadline.budget()).map_err(RunnerError::bot)?;
+    let bot_action = bot.get_action_by(game_state, round_state, player_index, deadline).map_err(RunnerError::bot)?;
+    let (action, correction) = config.sanitizer().sanitize(bot_action, round_state).map_err(RunnerError::Illegal)?;
+    if let Some(ref correction) = correction {
+        runner_log!(config.log_level(), Error, "Corrected bot action: {}", correction);
+        bot.handle_correction(game_state, round_state, correction).map_err(RunnerError::bot)?;
+    }
+    Ok((action, correction))
+}
+
+/// Something that happened in the match that the bot has to be told about
+#[derive(Debug)]
+enum Notification {
+    GameStart(usize),
+    NewRound(GameState, RoundState, usize),
+    OpponentAction(GameState, RoundState, Action, usize),
+    Reveal(GameState, HoleCards, usize),
+    RoundOver(GameState, TerminalState, usize),
+    GameEnd(GameState, Box<MatchSummary>),
+    /// A correction to an action the runner played for the bot after it overran its deadline
+    Correction(GameState, RoundState, Correction),
+}
+
+impl Notification {
+    fn deliver<B, E>(&self, bot: &mut B, config: &RunnerConfig) -> Result<(), RunnerError> where B: PokerBot<Error=E> + ?Sized, E: Error {
+        attempt(config, || match self {
+            Notification::GameStart(player_index) => bot.handle_game_start(*player_index, config),
+            Notification::NewRound(game_state, round_state, player_index) => bot.handle_new_round(game_state, round_state, *player_index),
+            Notification::OpponentAction(game_state, round_state, action, player_index) => bot.handle_opponent_action(game_state, round_state, *action, *player_index),
+            Notification::Reveal(game_state, hand, player_index) => bot.handle_reveal(game_state, hand, *player_index),
+            Notification::RoundOver(game_state, terminal_state, player_index) => bot.handle_round_over(game_state, terminal_state, *player_index),
+            Notification::GameEnd(game_state, summary) => bot.handle_game_end(game_state, summary),
+            Notification::Correction(game_state, round_state, correction) => bot.handle_correction(game_state, round_state, correction),
+        }.map_err(RunnerError::bot), |_| ())
+    }
+}
+
+/// Work for the bot thread, which does it in the order it was queued
+enum BotJob {
+    Notify(Notification),
+    Decide(u64, GameState, RoundState, usize, Deadline),
+}
+
+enum BotReply {
+    /// The decision for the job with the given id
+    Decision(u64, Result<(Action, Option<Correction>), RunnerError>),
+    /// A notification failed, and the error policy says not to carry on
+    Failed(RunnerError),
+}
+
+/// Runs the bot's side of the match, until the runner stops sending it work
+fn serve_bot<E: Error>(mut bot: BoxedBot<E>, config: Arc<RunnerConfig>, jobs: Receiver<BotJob>, replies: Sender<BotReply>) {
+    for job in jobs {
+        let reply = match job {
+            BotJob::Notify(notification) => match notification.deliver(&mut *bot, &config) {
+                Ok(()) => continue,
+                Err(e) => BotReply::Failed(e),
+            },
+            BotJob::Decide(id, game_state, round_state, player_index, deadline) =>
+                BotReply::Decision(id, decide(&mut *bot, &config, &game_state, &round_state, player_index, &deadline)),
         };
+        if replies.send(reply).is_err() {
+            return;
+        }
+    }
+}
 
-        let mut retries = 10;
-        while self.round_sent.load(Ordering::SeqCst) {
-            match writeln!(socket.get_mut(), "{}", code) {
-                Ok(_) => break,
-                Err(_) => if retries > 0 {
-                    retries -= 1;
-                } else {
-                    panic!("[Socket] Server unresponsive. Panicing...")
-                }
-            }
-            socket.get_mut().flush().unwrap();
+/// The runner's end of the bot thread
+struct BotHandle {
+    jobs: Sender<BotJob>,
+    replies: Receiver<BotReply>,
+    next_id: u64,
+    // A failure the bot thread reported, which ends the match once we are done with the current line
+    failed: Option<RunnerError>,
+}
+
+impl BotHandle {
+    fn spawn<E: Error + 'static>(bot: BoxedBot<E>, config: Arc<RunnerConfig>) -> BotHandle {
+        let (jobs, job_receiver) = channel();
+        let (reply_sender, replies) = channel();
+        thread::spawn(move || serve_bot(bot, config, job_receiver, reply_sender));
+        BotHandle {
+            jobs,
+            replies,
+            next_id: 0,
+            failed: None,
         }
+    }
 
-        self.round_sent.store(false, Ordering::SeqCst);
+    fn stopped() -> RunnerError {
+        RunnerError::Bot("bot thread has stopped".into())
+    }
 
-        Socket::check_for_socket_errors(socket.get_ref());
+    fn notify(&self, notification: Notification, config: &RunnerConfig) -> Result<(), RunnerError> {
+        match self.jobs.send(BotJob::Notify(notification)) {
+            Ok(()) => Ok(()),
+            Err(_) => tolerate(config, BotHandle::stopped()),
+        }
     }
 
-    fn check_for_socket_errors(socket: &TcpStream) {
-        // Check stream for errors. If there is one, disconnect.
-        match socket.take_error() {
-            Ok(Some(error)) => panic!("[Socket] Disconnecting because of stream error {}", error),
-            Ok(None) => {}, // No stream error detected
-            Err(e) => match e.kind() {
-                ErrorKind::TimedOut | ErrorKind::WouldBlock => {}, // We don't care about these errors,
-                kind => panic!("[Socket] Unexpected error when checking for socket errors ({:?}) {}", kind, e)
+    /// Asks the bot for an action, and plays its latest suggestion (or a safe action)
+    /// instead if it overruns its deadline
+    fn decide(&mut self, game_state: GameState, round_state: &RoundState, player_index: usize, deadline: Deadline, config: &RunnerConfig) -> Result<(Action, Option<Correction>), RunnerError> {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.jobs.send(BotJob::Decide(id, game_state, round_state.clone(), player_index, deadline.clone()))
+            .map_err(|_| BotHandle::stopped())?;
+        loop {
+            match self.replies.recv_timeout(deadline.remaining()) {
+                Ok(BotReply::Decision(reply_id, decision)) if reply_id == id => return decision,
+                // The answer to a decision we already gave up on
+                Ok(BotReply::Decision(..)) => {},
+                Ok(BotReply::Failed(e)) => self.failed = Some(e),
+                Err(RecvTimeoutError::Timeout) => {
+                    let (action, correction) = match deadline.best() {
+                        Some(best) => config.sanitizer().sanitize(best, round_state).map_err(RunnerError::Illegal)?,
+                        None => (safe_action(round_state), None),
+                    };
+                    runner_log!(config.log_level(), Error, "Bot overran its {:?} budget, playing {:?} instead", deadline.budget().budget(), action);
+                    if let Some(correction) = correction {
+                        runner_log!(config.log_level(), Error, "Corrected bot action: {}", correction);
+                        // The bot thread is still busy with the decision, so it hears about this once it is done
+                        self.notify(Notification::Correction(game_state, round_state.clone(), correction), config)?;
+                    }
+                    return Ok((action, correction));
+                },
+                Err(RecvTimeoutError::Disconnected) => return Err(RunnerError::Bot("bot panicked".into())),
             }
         }
     }
 
-    // Do all read processing here
-    fn sync(&mut self) {
-        let mut server_process = vec![];
-        let ref mut socket = self.stream;
+    /// Picks up failures the bot thread reported since we last asked
+    fn check(&mut self) -> Result<(), RunnerError> {
+        while let Ok(reply) = self.replies.try_recv() {
+            if let BotReply::Failed(e) = reply {
+                self.failed.get_or_insert(e);
+            }
+        }
+        self.failed.take().map_or(Ok(()), Err)
+    }
 
-        let mut s = String::new();
+    /// Stops sending the bot work, and gives it a moment to get through what it has queued
+    fn finish(self, grace: Duration) -> Result<(), RunnerError> {
+        let BotHandle { jobs, replies, failed, .. } = self;
+        drop(jobs);
+        let started = Instant::now();
+        let mut failed = failed;
+        loop {
+            match replies.recv_timeout(grace.checked_sub(started.elapsed()).unwrap_or_default()) {
+                Ok(BotReply::Failed(e)) => { failed.get_or_insert(e); },
+                Ok(BotReply::Decision(..)) => {},
+                // Either the bot is done, or it is stuck, and we are not waiting for it
+                Err(_) => break,
+            }
+        }
+        failed.map_or(Ok(()), Err)
+    }
+}
+
+/// Everything the runner knows about the match being played
+#[derive(Debug)]
+struct MatchState {
+    game_state: GameState,
+    round_state: Option<RoundState>,
+    terminal_state: Option<TerminalState>,
+    player_index: usize,
+    // Whether the bot has been told the game started
+    started: bool,
+    // Bookkeeping for the match summary
+    clock_start: Option<f32>,
+    deltas: Vec<i32>,
+    showdowns: u32,
+    folds: u32,
+    sanitized: u32,
+}
 
-        match socket.read_line(&mut s) {
-            Ok(_) => {},
-            Err(e) => panic!("[Socket] Unexpected read error ({:?}) {}", e.kind(), e),
+impl MatchState {
+    fn new() -> MatchState {
+        MatchState {
+            game_state: GameState {
+                bankroll: 0,
+                game_clock: 0.0,
+                round_num: 1
+            },
+            round_state: None,
+            terminal_state: None,
+            player_index: 0,
+            started: false,
+            clock_start: None,
+            deltas: vec![],
+            showdowns: 0,
+            folds: 0,
+            sanitized: 0,
         }
+    }
 
-        for action in s.trim().split(" ").map(|x| x.trim().to_string()) {
-            if !action.is_empty() {
-                server_process.push(action);
-            }
+    fn set_game_clock(&mut self, clock: f32) {
+        self.clock_start.get_or_insert(clock);
+        self.game_state.game_clock = clock;
+    }
+
+    fn summary(&self, runtime: Duration, end_reason: EndReason) -> MatchSummary {
+        MatchSummary {
+            bankroll: self.game_state.bankroll,
+            deltas: self.deltas.clone(),
+            showdowns: self.showdowns,
+            folds: self.folds,
+            clock_start: self.clock_start.unwrap_or(0.0),
+            clock_remaining: self.game_state.game_clock,
+            runtime,
+            sanitized: self.sanitized,
+            end_reason,
         }
+    }
 
-        Socket::check_for_socket_errors(socket.get_ref());
+    /// Tells the bot the game started, the first time we know which seat we are in
+    fn start(&mut self, notifications: &mut Vec<Notification>) {
+        if !self.started {
+            self.started = true;
+            notifications.push(Notification::GameStart(self.player_index));
+        }
+    }
 
-        // Process server strings into ServerAction objects
-        for action in server_process.into_iter() {
-            let act = action.chars().nth(0).unwrap();
-            let arg = action.chars().skip(1).collect::<String>();
-            let server_action = match act {
-                'T' => ServerAction::SetGameClock(arg.parse::<f32>().expect("Expected float for game clock")),
-                'P' => ServerAction::SetPlayerIndex(arg.parse::<usize>().expect("Expected positive integer for player index")),
-                'H' => {
-                    let cards: Vec<_> = into_cards!(arg).unwrap();
-                    assert!(cards.len() == 2, "Server sent too many cards for player hand");
-                    ServerAction::SetPlayerHand(CardHand([cards[0], cards[1]]))
-                },
-                'F' => ServerAction::PlayFold,
-                'C' => ServerAction::PlayCall,
-                'K' => ServerAction::PlayCheck,
-                'R' => ServerAction::PlayRaise(arg.parse::<u32>().expect("Expected positive integer for raise amount")),
-                'B' => ServerAction::UpdateDeck(CardDeck(into_cards!(arg).unwrap())),
-                'O' => {
-                    let cards: Vec<_> = into_cards!(arg).unwrap();
-                    assert!(cards.len() == 2, "Server sent too many cards for player hand");
-                    ServerAction::RevealOpponentHand(CardHand([cards[0], cards[1]]))
-                },
-                'D' => ServerAction::Delta(arg.parse::<i32>().expect("Expected integer for delta")),
-                'Q' => ServerAction::Quit,
-                c => panic!("[Socket] Unknown server command {} with arg {}", c, arg)
-            };
-            self.read_queue.push(server_action);
+    /// Applies an update from the engine, and returns what the bot needs to be told about it, in order
+    fn apply(&mut self, update: PreservedOrdering, config: &RunnerConfig) -> Result<Vec<Notification>, RunnerError> {
+        let mut notifications = vec![];
+        match update {
+            PreservedOrdering::Action(act) => {
+                if let Some(ref rs) = self.round_state {
+                    if rs.button as usize % 2 != self.player_index {
+                        notifications.push(Notification::OpponentAction(self.game_state, rs.clone(), act, self.player_index));
+                    }
+                    match rs.proceed(act) {
+                        StateResult::Round(r) => self.round_state = Some(r),
+                        StateResult::Terminal(t) => {
+                            if act == Action::Fold {
+                                self.folds += 1;
+                            } else {
+                                self.showdowns += 1;
+                            }
+                            self.terminal_state = Some(t);
+                        }
+                    }
+                } else {
+                    tolerate(config, RunnerError::Protocol(format!("Round state must exist for action {:?}", act)))?;
+                }
+            },
+            PreservedOrdering::Delta(delta) => {
+                if let Some(ref tstate) = self.terminal_state {
+                    let mut deltas = [-delta, -delta];
+                    deltas[self.player_index] = delta;
+                    let term = TerminalState{
+                        deltas,
+                        previous: tstate.previous.clone()
+                    };
+                    self.game_state = GameState {
+                        bankroll: self.game_state.bankroll + delta as i64,
+                        game_clock: self.game_state.game_clock,
+                        round_num: self.game_state.round_num
+                    };
+                    self.deltas.push(delta);
+                    notifications.push(Notification::RoundOver(self.game_state, term.clone(), self.player_index));
+                    self.terminal_state = Some(term);
+                    self.game_state = GameState {
+                        bankroll: self.game_state.bankroll,
+                        game_clock: self.game_state.game_clock,
+                        round_num: self.game_state.round_num + 1
+                    };
+                    self.round_state = None;
+                } else {
+                    tolerate(config, RunnerError::Protocol(format!("Round must be over for delta {}", delta)))?;
+                }
+            },
+            PreservedOrdering::StartRound(hand) => {
+                self.start(&mut notifications);
+                let mut hands = [None, None];
+                hands[self.player_index] = Some(hand);
+                let pips = [SMALL_BLIND, BIG_BLIND];
+                let stacks = [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND];
+                let round = RoundState {
+                    button: 0,
+                    street: 0,
+                    pips,
+                    stacks,
+                    hands,
+                    deck: CardDeck(vec![]),
+                    previous: None
+                };
+                self.round_state = Some(round.clone());
+                notifications.push(Notification::NewRound(self.game_state, round, self.player_index));
+            },
+            PreservedOrdering::Reveal(hand) => {
+                if let Some(ref prs) = self.round_state {
+                    notifications.push(Notification::Reveal(self.game_state, hand, self.player_index));
+                    let mut revised_hands = prs.hands;
+                    revised_hands[1 - self.player_index] = Some(hand);
+                    // rebuild history
+                    let new_round_state = RoundState {
+                        button: prs.button,
+                        street: prs.street,
+                        pips: prs.pips,
+                        stacks: prs.stacks,
+                        hands: revised_hands,
+                        deck: prs.deck.clone(),
+                        previous: prs.previous.clone()
+                    };
+                    self.terminal_state = Some(TerminalState{
+                        deltas: [0, 0],
+                        previous: new_round_state
+                    });
+                } else {
+                    tolerate(config, RunnerError::Protocol("Round state must exist for reveal".into()))?;
+                }
+            },
+            PreservedOrdering::UpdateDeck(deck) => {
+                if let Some(ref rs) = self.round_state {
+                    self.round_state = Some(RoundState {
+                        button: rs.button,
+                        street: deck.0.len() as u32,
+                        pips: rs.pips,
+                        stacks: rs.stacks,
+                        hands: rs.hands,
+                        deck,
+                        previous: rs.previous.clone()
+                    })
+                } else {
+                    tolerate(config, RunnerError::Protocol("Round state must exist for deck update".into()))?;
+                }
+            },
+            PreservedOrdering::SetPlayerIndex(index) => {
+                self.player_index = index;
+                self.start(&mut notifications);
+            },
         }
+        Ok(notifications)
     }
-}
 
-// Shutdown the socket even if we panic, and right when we panic
-impl Drop for Socket {
-    fn drop(&mut self) {
-        // Might not even need to call this explicitly...
-        match self.stream.get_mut().shutdown(Shutdown::Both) {
-            Ok(()) => {},
-            // We don't really care about errors here, as our goal is simply to end the socket
-            Err(_) => {}
+    /// Makes sure that the round state agrees it is our turn to act
+    fn check_turn(&self, round_state: &RoundState, config: &RunnerConfig) -> Result<(), RunnerError> {
+        if self.player_index == round_state.button as usize % 2 {
+            Ok(())
+        } else {
+            tolerate(config, RunnerError::Protocol("Asked to act when it is not our turn".into()))
         }
     }
+
+    /// Works out what to send back to the engine, using `decide` to get the bot's action.
+    /// Outside of a round, we just ping.
+    fn respond<D>(&mut self, config: &RunnerConfig, mut decide: D) -> Result<Action, RunnerError>
+        where D: FnMut(GameState, &RoundState, usize, Deadline) -> Result<(Action, Option<Correction>), RunnerError> {
+        let round_state = match self.round_state {
+            Some(ref round_state) => round_state,
+            None => return Ok(Action::Check),
+        };
+        self.check_turn(round_state, config)?;
+        let (game_state, player_index) = (self.game_state, self.player_index);
+        let (action, correction) = attempt(config, || {
+            let deadline = Deadline::new(config.time_manager().budget(&game_state));
+            decide(game_state, round_state, player_index, deadline)
+        }, |_| (safe_action(round_state), None))?;
+        if correction.is_some() {
+            self.sanitized += 1;
+        }
+        Ok(action)
+    }
 }
 
-impl Runner {
-    /// Runs a PokerBot using the Runner
-    pub fn run_bot<TS, E: Error + 'static>(bot: Box<dyn PokerBot<Error=E> + Send + Sync>, addr: TS, thread_count: usize) -> std::io::Result<()> where TS: ToSocketAddrs {
-        if let Some(addr) = addr.to_socket_addrs()?.nth(0) {
-            let stream = TcpStream::connect_timeout(&addr, Duration::from_secs(CONNECT_TIMEOUT))?;
-            stream.set_nodelay(true).expect("set_nodelay call failed");
-            stream.set_write_timeout(Some(Duration::from_micros(WRITE_TIMEOUT))).expect("write_timeout call failed");
-            let mut runner = Runner {
-                socket: Arc::new(Mutex::new(Socket::new(BufReader::new(stream)))),
-                runner_start: Instant::now(),
-                thread_count,
-            };
-            Ok(runner.run(bot))
-        } else {
-            panic!("No addresses were sent to run on");
+impl Socket {
+    fn new(stream: TcpStream, recorder: Option<SharedRecorder>, config: Arc<RunnerConfig>, closing: Arc<AtomicBool>) -> Socket {
+        Socket {
+            stream,
+            recorder,
+            config,
+            closing,
+            fault: None,
         }
     }
 
-    // We never want to block access to state when we have write access to the bot, as
-    // that is asking for a lockup to happen, so we have some functions that continually query
-    // whether the device (piece of state) is actually ready for bot access
-    // This function polls for unique access
-    fn poll_until_write<'a, T>(device: &'a Arc<RwLock<T>>, device_id: &'static str) -> RwLockWriteGuard<'a, T> {
-        loop {
-            match device.try_write() {
-                Ok(guard) => return guard,
-                Err(try_error) => match try_error {
-                    TryLockError::WouldBlock => {}, // Just try again
-                    TryLockError::Poisoned(_) => panic!("Resource {} poisoned.", device_id),
-                }
-            }
+    /// Send an action message to the engine
+    fn send(&mut self, action: Action) -> Result<(), RunnerError> {
+        if self.closing.load(Ordering::SeqCst) {
+            return Ok(());
+        }
+
+        let Socket { ref mut stream, ref config, ref mut fault, .. } = *self;
+
+        let code = action_code(action);
+
+        let sent = attempt(config, || {
+            writeln!(stream, "{}", code)?;
+            stream.flush()?;
+            Socket::check_for_socket_errors(stream)?;
+            Ok(true)
+        }, |e| {
+            *fault = Some(e.to_string());
+            false
+        })?;
+
+        if sent {
+            record(&self.recorder, config, |recorder| recorder.sent(&code))?;
         }
+        Ok(())
     }
 
-    // This function polls for read access
-    fn poll_until_read<'a, T>(device: &'a Arc<RwLock<T>>, device_id: &'static str) -> RwLockReadGuard<'a, T> {
-        loop {
-            match device.try_read() {
-                Ok(guard) => return guard,
-                Err(try_error) => match try_error {
-                    TryLockError::WouldBlock => {}, // Just try again
-                    TryLockError::Poisoned(_) => panic!("Resource {} poisoned.", device_id),
-                }
+    fn check_for_socket_errors(socket: &TcpStream) -> Result<(), RunnerError> {
+        // Check stream for errors. If there is one, disconnect.
+        match socket.take_error() {
+            Ok(Some(error)) => Err(RunnerError::Io(error)),
+            Ok(None) => Ok(()), // No stream error detected
+            Err(e) => match e.kind() {
+                ErrorKind::TimedOut | ErrorKind::WouldBlock => Ok(()), // We don't care about these errors,
+                _ => Err(RunnerError::Io(e)),
             }
         }
     }
+}
 
-    // Put bot and socket lock error-handling code in one place
-    // Is basically the same code as poll_until_* but for Mutexed stuff
-    fn lock_device<'a, T>(device: &'a Arc<Mutex<T>>, device_id: &'static str) -> MutexGuard<'a, T> {
-        loop {
-            match device.try_lock() {
-                Ok(guard) => return guard,
-                Err(try_error) => match try_error {
-                    TryLockError::WouldBlock => {}, // Just try again
-                    TryLockError::Poisoned(_) => panic!("Device {} poisoned.", device_id)
-                }
+// Shutdown the socket even if we panic, and right when we panic
+impl Drop for Socket {
+    fn drop(&mut self) {
+        // We don't really care about errors here, as our goal is simply to end the socket
+        let _ = self.stream.shutdown(Shutdown::Both);
+    }
+}
+
+/// What the reader thread hands to the runner
+enum Incoming {
+    Line(Vec<ServerAction>),
+    /// An I/O fault we fell back from, which ends the match with what we have
+    Fault(String),
+    Failed(RunnerError),
+}
+
+/// Reads lines from the engine until it quits, we hang up, or something goes wrong
+fn read_engine(mut stream: BufReader<TcpStream>, recorder: Option<SharedRecorder>, config: Arc<RunnerConfig>, closing: Arc<AtomicBool>, lines: Sender<Incoming>) {
+    let mut line = String::new();
+    loop {
+        let mut fault = None;
+        let read = attempt(&config, || {
+            line.clear();
+            let read = stream.read_line(&mut line);
+            if closing.load(Ordering::SeqCst) {
+                // We hung up ourselves, so whatever happened to the read is expected
+                return Ok(0);
             }
+            match read? {
+                0 => Err(RunnerError::Io(io::Error::new(ErrorKind::UnexpectedEof, "engine closed the connection"))),
+                read => Socket::check_for_socket_errors(stream.get_ref()).map(|_| read),
+            }
+        }, |e| {
+            fault = Some(e.to_string());
+            0
+        });
+
+        let incoming = match read {
+            Ok(0) => match fault {
+                Some(fault) => Incoming::Fault(fault),
+                None => return,
+            },
+            Ok(_) => match record(&recorder, &config, |recorder| recorder.received(&line))
+                .and_then(|_| ServerAction::parse_line(&line, &config)) {
+                Ok(clauses) => Incoming::Line(clauses),
+                Err(e) => Incoming::Failed(e),
+            },
+            Err(e) => Incoming::Failed(e),
+        };
+
+        let last = match incoming {
+            Incoming::Line(ref clauses) => clauses.iter().any(|clause| matches!(clause, ServerAction::Quit)),
+            _ => true,
+        };
+        if lines.send(incoming).is_err() || last {
+            return;
         }
     }
+}
 
-    /// Processes actions from the engine and never returns when called
-    fn run<E: Error + 'static>(&mut self, bot: Box<dyn PokerBot<Error=E> + Send + Sync>) {
-        let game_state = Arc::new(RwLock::new(GameState {
-            bankroll: 0,
-            game_clock: 0.0,
-            round_num: 1
-        }));
-        let round_state: Arc<RwLock<Option<RoundState>>> = Arc::new(RwLock::new(None));
-        let terminal_state: Arc<RwLock<Option<TerminalState>>> = Arc::new(RwLock::new(None));
-        let bot = Arc::new(Mutex::new(bot));
-        let player_index = Arc::new(AtomicUsize::new(0usize));
-        let mut pool = if self.thread_count <= MAX_THREAD_COUNT {
-            ThreadPool::new(self.thread_count).unwrap()
-        } else {
-            panic!("Attempted to make {} threads, which is too many.", self.thread_count);
+impl Runner {
+    /// Runs a PokerBot using the Runner, with the default config.
+    /// The thread count is ignored: it is only here so existing callers keep compiling, since the runner
+    /// always plays on its own reader, main and bot threads.
+    pub fn run_bot<TS, E: Error + 'static>(bot: BoxedBot<E>, addr: TS, _thread_count: usize) -> Result<MatchSummary, RunnerError> where TS: ToSocketAddrs {
+        Runner::run_bot_with(bot, addr, RunnerConfig::default())
+    }
+
+    /// Runs a PokerBot using the Runner, and sums up the match once it is over.
+    /// Faults that the config's error policies do not let us carry on from are returned.
+    pub fn run_bot_with<TS, E: Error + 'static>(bot: BoxedBot<E>, addr: TS, config: RunnerConfig) -> Result<MatchSummary, RunnerError> where TS: ToSocketAddrs {
+        let recorder = match config.session() {
+            Some(path) => Some(Arc::new(Mutex::new(SessionRecorder::create(path)?))),
+            None => None,
+        };
+        let addr = addr.to_socket_addrs()?.next()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "No addresses were sent to run on"))?;
+        let stream = TcpStream::connect_timeout(&addr, config.connect_timeout())?;
+        stream.set_nodelay(true)?;
+        stream.set_write_timeout(Some(config.write_timeout()))?;
+        let reader = BufReader::new(stream.try_clone()?);
+        let config = Arc::new(config);
+        let closing = Arc::new(AtomicBool::new(false));
+        let mut runner = Runner {
+            socket: Socket::new(stream, recorder.clone(), config.clone(), closing.clone()),
+            closing: closing.clone(),
+            runner_start: Instant::now(),
+            config: config.clone(),
         };
 
-        let (action_sender, action_receiver) = channel();
-        let action_receiver = Arc::new(Mutex::new(action_receiver));
-        let mut state_change = false;
+        let (line_sender, lines) = channel();
+        let reader = thread::spawn(move || read_engine(reader, recorder, config, closing, line_sender));
+        runner.run(bot, lines, reader)
+    }
 
-        loop {
-            {
-                let socket = self.socket.clone();
-                pool.execute(88, move || {
-                    Runner::lock_device(&socket, "socket").sync();
-                });
+    /// Feeds a recorded session back into a bot, checking that it still responds the way it did.
+    /// The replay goes through the same state handling as a live match, but runs on the calling thread,
+    /// so the bot is never cut off by its deadline.
+    pub fn replay_bot<B, E>(bot: &mut B, session: &Session, config: &RunnerConfig) -> Result<ReplayOutcome, RunnerError> where B: PokerBot<Error=E> + ?Sized, E: Error {
+        let mut state = MatchState::new();
+        let mut compared = 0;
+        let mut entries = session.0.iter().enumerate();
+
+        while let Some((_, entry)) = entries.next() {
+            // Anything we sent without being asked is not something the bot decided
+            let line = match entry.event {
+                SessionEvent::Received(ref line) => line,
+                SessionEvent::Sent(_) => continue,
+            };
+
+            let mut quit = false;
+            for clause in ServerAction::parse_line(line, config)? {
+                match clause {
+                    ServerAction::SetGameClock(clock) => state.set_game_clock(clock),
+                    ServerAction::Quit => quit = true,
+                    clause => if let Some(update) = clause.ordered() {
+                        for notification in state.apply(update, config)? {
+                            notification.deliver(bot, config)?;
+                        }
+                    },
+                }
+            }
+            if quit {
+                let summary = state.summary(Duration::default(), EndReason::Quit);
+                Notification::GameEnd(state.game_state, Box::new(summary)).deliver(bot, config)?;
+                break;
             }
 
-            // Read from the server
-            {
-                let mut socket = Runner::lock_device(&self.socket, "socket");
-                // Read the server messages and then react to them by changing our state
-                let clauses = socket.receive();
-                for clause in clauses.into_iter() {
-                    // Spawn the change state jobs.
-                    state_change = true;
-                    let game_state = game_state.clone();
-                    // The main runner code is entirely run in thread pools! We reserve the main thread for
-                    // receiving updates from the server, but the rest is asynchrous!
-                    let action_sender = action_sender.clone();
-                    match clause.clone() {
-                        // Set game clock
-                        ServerAction::SetGameClock(clock) => {
-                            let mut game_state = Runner::poll_until_write(&game_state, "game");
-                            *game_state = GameState {
-                                bankroll: game_state.bankroll,
-                                game_clock: clock,
-                                round_num: game_state.round_num
-                            };
-                        },
-                        // Set player index (also referred to as "active")
-                        ServerAction::SetPlayerIndex(index) => action_sender.send(PreservedOrdering::SetPlayerIndex(index)).unwrap(),
-                        // Set our hand
-                        ServerAction::SetPlayerHand(hand) => action_sender.send(PreservedOrdering::StartRound(hand)).unwrap(),
-                        // Since the server doesn't tell us who did what, we have to preserve that information
-                        // By preserving the order of actions, so we push them to a queue and run them all in order
-
-                        // A fold action
-                        ServerAction::PlayFold => action_sender.send(PreservedOrdering::Action(Action::Fold)).unwrap(),
-                        // A call action
-                        ServerAction::PlayCall => action_sender.send(PreservedOrdering::Action(Action::Call)).unwrap(),
-                        // A check action
-                        ServerAction::PlayCheck => action_sender.send(PreservedOrdering::Action(Action::Check)).unwrap(),
-                        // A raise action
-                        ServerAction::PlayRaise(by) => action_sender.send(PreservedOrdering::Action(Action::Raise(by))).unwrap(),
-                        // The deck was updated
-                        ServerAction::UpdateDeck(deck) => action_sender.send(PreservedOrdering::UpdateDeck(deck)).unwrap(),
-                        // Reveal the opponent's hand
-                        ServerAction::RevealOpponentHand(hand) => action_sender.send(PreservedOrdering::Reveal(hand)).unwrap(),
-                        // Delta has been calculated
-                        ServerAction::Delta(delta) => action_sender.send(PreservedOrdering::Delta(delta)).unwrap(),
-                        // End the game
-                        ServerAction::Quit => {pool.shutdown(); return},
+            let response = state.respond(config, |game_state, round_state, player_index, deadline| {
+                decide(bot, config, &game_state, round_state, player_index, &deadline)
+            });
+            let actual = match response {
+                Ok(action) => action_code(action),
+                Err(e) => format!("<{}>", e),
+            };
+
+            match entries.next() {
+                Some((index, response)) => {
+                    let expected = match response.event {
+                        SessionEvent::Sent(ref expected) => expected.clone(),
+                        SessionEvent::Received(_) => "<no response>".into(),
+                    };
+                    if expected != actual {
+                        return Ok(ReplayOutcome::Diverged(Divergence {
+                            entry: index,
+                            elapsed: response.elapsed,
+                            received: line.clone(),
+                            expected,
+                            actual,
+                        }));
                     }
-                }
+                    compared += 1;
+                },
+                // The recording stopped before we got to respond
+                None => break,
             }
+        }
 
+        Ok(ReplayOutcome::Matched(compared))
+    }
 
+    /// Hangs up on the engine, so the reader thread stops waiting on it
+    fn close(&self) {
+        self.closing.store(true, Ordering::SeqCst);
+        let _ = self.socket.stream.shutdown(Shutdown::Both);
+    }
 
-            if state_change {
-                // Run actions in the action_queue
-                {
-                    let action_receiver = action_receiver.clone();
-                    let (game_state, round_state, terminal_state, bot, player_index) =
-                        (game_state.clone(), round_state.clone(), terminal_state.clone(), bot.clone(), player_index.clone());
-                    pool.execute(69, move || {
-                        let mut round_state = Runner::poll_until_write(&round_state, "round");
-                        let mut game_state = Runner::poll_until_write(&game_state, "game");
-                        let mut terminal_state = Runner::poll_until_write(&terminal_state, "terminal");
-                        let mut bot = Runner::lock_device(&bot, "bot");
-                        let action_queue = Runner::lock_device(&action_receiver, "actions");
-                        // Receive as many actions as possible, but don't block on it.
-                        while let Ok(action) = action_queue.try_recv() {
-                            match action {
-                                PreservedOrdering::Action(act) => {
-                                    if let Some(ref rs) = *round_state {
-                                        match rs.proceed(act) {
-                                            StateResult::Round(r) => *round_state = Some(r),
-                                            StateResult::Terminal(t) => {
-                                                *terminal_state = Some(t);
-                                            }
-                                        }
-                                    } else {
-                                        panic!("Round state must exist for action {:?}", action);
-                                    }
-                                },
-                                PreservedOrdering::Delta(delta) => {
-                                    assert!(terminal_state.is_some());
-                                    let player_index_ = player_index.load(PLAYER_INDEX_LOAD_ORDERING);
-                                    if let Some(ref tstate) = *terminal_state {
-                                        let mut deltas = [-delta, -delta];
-                                        deltas[player_index_] = delta;
-                                        let term = TerminalState{
-                                            deltas,
-                                            previous: tstate.previous.clone()
-                                        };
-                                        *game_state = GameState {
-                                            bankroll: game_state.bankroll + delta as i64,
-                                            game_clock: game_state.game_clock,
-                                            round_num: game_state.round_num
-                                        };
-                                        match bot.handle_round_over(&*game_state, &term, player_index_) {
-                                            Ok(_) => {},
-                                            Err(e) => {
-                                                error!(target: "PBRunner", "Bot end round error {}", e);
-                                                return;
-                                            }
-                                        };
-                                        *terminal_state = Some(term);
-                                        *game_state = GameState {
-                                            bankroll: game_state.bankroll,
-                                            game_clock: game_state.game_clock,
-                                            round_num: game_state.round_num + 1
-                                        };
-                                        *round_state = None;
-                                    }
-                                },
-                                PreservedOrdering::StartRound(hand) => {
-                                    let player_index_ = player_index.load(PLAYER_INDEX_LOAD_ORDERING);
-                                    let mut hands = [None, None];
-                                    hands[player_index_] = Some(hand);
-                                    let pips = [SMALL_BLIND, BIG_BLIND];
-                                    let stacks = [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND];
-                                    let round = RoundState {
-                                        button: 0,
-                                        street: 0,
-                                        pips,
-                                        stacks,
-                                        hands,
-                                        deck: CardDeck(vec![]),
-                                        previous: None
-                                    };
-                                    match bot.handle_new_round(&*game_state, &round, player_index_) {
-                                        Ok(_) => {},
-                                        Err(e) => {
-                                            error!(target: "PBRunner", "Bot start round error {}", e);
-                                            return;
-                                        }
-                                    };
-                                    *round_state = Some(round);
-                                },
-                                PreservedOrdering::Reveal(hand) => {
-                                    let player_index_ = player_index.load(PLAYER_INDEX_LOAD_ORDERING);
-                                    if let Some(ref prs) = *round_state {
-                                        let mut revised_hands = prs.hands;
-                                        revised_hands[1 - player_index_] = Some(hand);
-                                        // rebuild history
-                                        let new_round_state = RoundState {
-                                            button: prs.button,
-                                            street: prs.street,
-                                            pips: prs.pips,
-                                            stacks: prs.stacks,
-                                            hands: revised_hands,
-                                            deck: prs.deck.clone(),
-                                            previous: prs.previous.clone()
-                                        };
-                                        *terminal_state = Some(TerminalState{
-                                            deltas: [0, 0],
-                                            previous: new_round_state
-                                        });
-                                    } else {
-                                        panic!("Round state must exists for reveal")
-                                    }
-                                },
-                                PreservedOrdering::UpdateDeck(deck) => {
-                                    if let Some(ref rs) = *round_state {
-                                        *round_state = Some(RoundState {
-                                            button: rs.button,
-                                            street: deck.0.len() as u32,
-                                            pips: rs.pips,
-                                            stacks: rs.stacks,
-                                            hands: rs.hands,
-                                            deck,
-                                            previous: rs.previous.clone()
-                                        })
-                                    } else {
-                                        panic!("Round state must exist for this action")
-                                    }
-                                },
-                                PreservedOrdering::SetPlayerIndex(index) => {
-                                    player_index.store(index, PLAYER_INDEX_STOR_ORDERING)
-                                },
-                            }
-                        }
-                    })
-                }
+    /// Plays the match, and sums it up once it is over
+    fn run<E: Error + 'static>(&mut self, bot: BoxedBot<E>, lines: Receiver<Incoming>, reader: JoinHandle<()>) -> Result<MatchSummary, RunnerError> {
+        let mut state = MatchState::new();
+        let mut bot = BotHandle::spawn(bot, self.config.clone());
 
-                {
-                    let socket = self.socket.clone();
-                    // let barrier = barrier.clone();
-                    let (game_state, round_state, bot, player_index) = (game_state.clone(), round_state.clone(), bot.clone(), player_index.clone());
-                    pool.execute(9, move || {
-                        // Acquire the round state if it is available, but DO NOT BLOCK ( but maybe block the socket for a bit... )
-                        let mut socket = Runner::lock_device(&socket, "socket");
-                        let round_state = Runner::poll_until_read(&round_state, "round");
-                        let game_state = Runner::poll_until_read(&game_state, "game");
-
-                        if let Some(ref round_state) = *round_state {
-                            let player_index = player_index.load(PLAYER_INDEX_LOAD_ORDERING);
-                            assert!(player_index == round_state.button as usize % 2);
-                            // if we can make an action, do so, unless we already have done so.
-                            if !socket.round_sent.load(Ordering::SeqCst) {
-                                socket.round_sent.store(true, Ordering::Relaxed);
-                                let mut bot = Runner::lock_device(&bot, "bot");
-                                let bot_action = match bot.get_action(&*game_state, round_state, player_index) {
-                                    Ok(action) => action,
-                                    Err(e) => {
-                                        error!(target: "PBRunner", "Bot error {}", e);
-                                        // Try again next time.
-                                        return;
-                                    }
-                                };
-
-                                let legal_actions = round_state.legal_actions();
-                                let action = match bot_action {
-                                    Action::Raise(raise) => if (legal_actions & ActionType::RAISE) == ActionType::RAISE {
-                                        let [rb_min, rb_max] = round_state.raise_bounds();
-                                        if raise > rb_min && raise < rb_max {
-                                            Action::Raise(raise)
-                                        } else {
-                                            if(legal_actions & ActionType::CHECK) == ActionType::CHECK {
-                                                Action::Check
-                                            } else {
-                                                Action::Call
-                                            }
-                                        }
-                                    } else {
-                                        if(legal_actions & ActionType::CHECK) == ActionType::CHECK {
-                                            Action::Check
-                                        } else {
-                                            Action::Call
-                                        }
-                                    },
-                                    Action::Check => if (legal_actions & ActionType::CHECK) == ActionType::CHECK {
-                                        Action::Check
-                                    } else {
-                                        Action::Fold
-                                    },
-                                    Action::Call => if (legal_actions & ActionType::CHECK) == ActionType::CHECK {
-                                        Action::Check
-                                    } else {
-                                        Action::Call
-                                    },
-                                    Action::Fold => if (legal_actions & ActionType::CHECK) == ActionType::CHECK {
-                                        Action::Check
-                                    } else {
-                                        Action::Fold
-                                    }
-                                };
-                                socket.send(action);
-                            }
-                        } else {
-                            if !socket.round_sent.load(Ordering::SeqCst) {
-                                socket.round_sent.store(true, Ordering::SeqCst);
-                                socket.ping();
-                            }
+        let played = self.play(&mut state, &mut bot, &lines);
+
+        self.close();
+        // The reader only ever panics on a bug, and then there is nothing left to do with it
+        let _ = reader.join();
+        let summary = played.map(|end_reason| state.summary(self.runner_start.elapsed(), end_reason));
+        let told = match summary {
+            Ok(ref summary) => bot.notify(Notification::GameEnd(state.game_state, Box::new(summary.clone())), &self.config),
+            Err(_) => Ok(()),
+        };
+        let finished = bot.finish(self.config.time_manager().reserve);
+        let summary = summary?;
+        told?;
+        finished?;
+
+        Ok(summary)
+    }
+
+    /// Waits for the next thing from the reader thread, as long as the time limit allows
+    fn next_line(&self, lines: &Receiver<Incoming>) -> Result<Option<Incoming>, RunnerError> {
+        let stopped = || RunnerError::Io(io::Error::new(ErrorKind::BrokenPipe, "engine reader stopped"));
+        match self.config.time_limit() {
+            Some(limit) => match limit.checked_sub(self.runner_start.elapsed()) {
+                None => Ok(None),
+                Some(remaining) => match lines.recv_timeout(remaining) {
+                    Ok(incoming) => Ok(Some(incoming)),
+                    Err(RecvTimeoutError::Timeout) => Ok(None),
+                    Err(RecvTimeoutError::Disconnected) => Err(stopped()),
+                },
+            },
+            None => lines.recv().map(Some).map_err(|_| stopped()),
+        }
+    }
+
+    /// Processes lines from the engine until the match is over
+    fn play(&mut self, state: &mut MatchState, bot: &mut BotHandle, lines: &Receiver<Incoming>) -> Result<EndReason, RunnerError> {
+        loop {
+            let clauses = match self.next_line(lines)? {
+                Some(Incoming::Line(clauses)) => clauses,
+                Some(Incoming::Fault(fault)) => return Ok(EndReason::Error(fault)),
+                Some(Incoming::Failed(e)) => return Err(e),
+                None => return Ok(EndReason::TimeLimit),
+            };
+
+            for clause in clauses {
+                match clause {
+                    ServerAction::SetGameClock(clock) => state.set_game_clock(clock),
+                    // End the game
+                    ServerAction::Quit => return Ok(EndReason::Quit),
+                    clause => if let Some(update) = clause.ordered() {
+                        for notification in state.apply(update, &self.config)? {
+                            bot.notify(notification, &self.config)?;
                         }
-                    });
+                    },
                 }
             }
 
-            state_change = false;
+            let config = self.config.clone();
+            let action = state.respond(&config, |game_state, round_state, player_index, deadline| {
+                bot.decide(game_state, round_state, player_index, deadline, &config)
+            })?;
+            self.socket.send(action)?;
+            bot.check()?;
 
-            {
-                let game_state = Runner::poll_until_read(&game_state, "game");
-                let round_state = Runner::poll_until_read(&round_state, "round");
-                if (relative_eq!(game_state.game_clock, 0.0, epsilon = 0.001)  && game_state.round_num > 1)
-                    || Instant::now() - self.runner_start > Duration::from_secs(COMP_TIME)
-                    || game_state.round_num == 1001 && round_state.is_none() {
-                    return; // Game is over.
-                }
+            if let Some(fault) = self.socket.fault.take() {
+                return Ok(EndReason::Error(fault));
+            }
+            if let Some(reason) = self.config.game_end().reason(&state.game_state, state.round_state.as_ref()) {
+                return Ok(reason); // Game is over.
             }
-
-            // Let the computer rest for a bit
-            thread::sleep(Duration::from_micros(SLEEP_DURATION));
         }
     }
 }
 
 impl Drop for Runner {
     fn drop(&mut self) {
-        let runtime = Instant::now() - self.runner_start;
-        println!("[Runner] Ran for {:?}", runtime);
+        // The runtime is also in the match summary, so this is only for the log
+        runner_log!(self.config.log_level(), Info, "Ran for {:?}", self.runner_start.elapsed());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bots::CheckCall;
+    use crate::clock::DecisionBudget;
+    use crate::sanitizer::{ClampToBounds, Strict, Violation};
+
+    fn replay(session: &str) -> ReplayOutcome {
+        let session: Session = session.parse().unwrap();
+        Runner::replay_bot(&mut CheckCall, &session, &RunnerConfig::default()).unwrap()
+    }
+
+    /// Suggests an action, then takes far longer than its budget to decide, and remembers its corrections
+    struct Slow {
+        suggestion: Option<Action>,
+        corrections: Arc<Mutex<Vec<Correction>>>,
+    }
+
+    impl Slow {
+        fn new(suggestion: Option<Action>) -> (Slow, Arc<Mutex<Vec<Correction>>>) {
+            let corrections = Arc::new(Mutex::new(vec![]));
+            (Slow { suggestion, corrections: corrections.clone() }, corrections)
+        }
+    }
+
+    impl PokerBot for Slow {
+        type Error = fmt::Error;
+
+        fn handle_new_round(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<(), fmt::Error> {
+            Ok(())
+        }
+
+        fn handle_round_over(&mut self, _gs: &GameState, _ts: &TerminalState, _player_index: usize) -> Result<(), fmt::Error> {
+            Ok(())
+        }
+
+        fn get_action(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize) -> Result<Action, fmt::Error> {
+            Ok(Action::Call)
+        }
+
+        fn get_action_by(&mut self, _gs: &GameState, _rs: &RoundState, _player_index: usize, deadline: &Deadline) -> Result<Action, fmt::Error> {
+            if let Some(suggestion) = self.suggestion {
+                deadline.suggest(suggestion);
+            }
+            thread::sleep(Duration::from_millis(200));
+            Ok(Action::Call)
+        }
+
+        fn handle_correction(&mut self, _gs: &GameState, _rs: &RoundState, correction: &Correction) -> Result<(), fmt::Error> {
+            self.corrections.lock().unwrap().push(*correction);
+            Ok(())
+        }
+    }
+
+    fn states() -> (GameState, RoundState) {
+        let gs = GameState { bankroll: 0, game_clock: 20.0, round_num: 1 };
+        let rs = RoundState {
+            button: 0,
+            street: 0,
+            pips: [SMALL_BLIND, BIG_BLIND],
+            stacks: [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [None, None],
+            deck: CardDeck(vec![]),
+            previous: None,
+        };
+        (gs, rs)
+    }
+
+    /// Asks the bot for a decision it will overrun, then waits for the bot thread to get through its queue
+    fn overrun(bot: Slow, config: RunnerConfig) -> Result<(Action, Option<Correction>), RunnerError> {
+        let config = Arc::new(config);
+        let (gs, rs) = states();
+        let mut handle = BotHandle::spawn(Box::new(bot), config.clone());
+        let deadline = Deadline::new(DecisionBudget::new(Duration::from_millis(20)));
+        let decision = handle.decide(gs, &rs, 0, deadline, &config);
+        handle.finish(Duration::from_secs(2)).unwrap();
+        decision
+    }
+
+    #[test]
+    fn overruns_play_the_latest_suggestion() {
+        let (bot, _) = Slow::new(Some(Action::Raise(6)));
+        assert_eq!(overrun(bot, RunnerConfig::default()).unwrap(), (Action::Raise(6), None));
+    }
+
+    #[test]
+    fn overruns_without_a_suggestion_play_safe() {
+        let (bot, _) = Slow::new(None);
+        // Facing the big blind, the safe action is to fold
+        assert_eq!(overrun(bot, RunnerConfig::default()).unwrap(), (Action::Fold, None));
+    }
+
+    #[test]
+    fn late_corrections_reach_the_bot() {
+        let (bot, corrections) = Slow::new(Some(Action::Raise(1000)));
+        let config = RunnerConfig::builder().sanitizer(ClampToBounds).build().unwrap();
+        let (action, correction) = overrun(bot, config).unwrap();
+        let [min, max] = states().1.raise_bounds();
+        assert_eq!(action, Action::Raise(max));
+        let correction = correction.unwrap();
+        assert_eq!(correction.violation, Violation::RaiseOutOfBounds { min, max });
+        assert_eq!(*corrections.lock().unwrap(), [correction]);
+    }
+
+    #[test]
+    fn late_illegal_actions_follow_the_error_policy() {
+        let (bot, corrections) = Slow::new(Some(Action::Check));
+        let config = RunnerConfig::builder().sanitizer(Strict).build().unwrap();
+        assert!(matches!(overrun(bot, config), Err(RunnerError::Illegal(_))));
+        assert!(corrections.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn replay_matches_the_recording() {
+        let outcome = replay("0.0 < T20.000 P0 H4s,Ah\n0.1 > C\n0.2 < T19.9 C K B2c,3d,Kh K\n0.3 > K\n0.4 < T19.8 K B2c,3d,Kh,5s K\n0.5 > K\n0.6 < T19.7 K B2c,3d,Kh,5s,9c K\n0.7 > K\n0.8 < T19.6 K O7d,7h D-2 Q\n");
+        assert_eq!(outcome, ReplayOutcome::Matched(4));
+    }
+
+    #[test]
+    fn replay_reports_the_first_divergence() {
+        let outcome = replay("0.0 < T20.000 P0 H4s,Ah\n0.1 > C\n0.2 < T19.9 C K B2c,3d,Kh K\n0.3 > R4\n");
+        match outcome {
+            ReplayOutcome::Diverged(divergence) => {
+                assert_eq!(divergence.entry, 3);
+                assert_eq!(divergence.received, "T19.9 C K B2c,3d,Kh K");
+                assert_eq!(divergence.expected, "R4");
+                assert_eq!(divergence.actual, "K");
+            },
+            other => panic!("expected a divergence, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn replay_stops_where_the_recording_does() {
+        assert_eq!(replay("0.0 < T20.000 P0 H4s,Ah\n"), ReplayOutcome::Matched(0));
+    }
+
+    /// Checks and calls like `CheckCall`, writing down every hook the runner calls
+    #[derive(Default)]
+    struct Recording(Vec<String>);
+
+    impl PokerBot for Recording {
+        type Error = fmt::Error;
+
+        fn handle_new_round(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<(), fmt::Error> {
+            self.0.push(format!("new round {} {} {}", gs.round_num, rs.hands[player_index].unwrap(), player_index));
+            Ok(())
+        }
+
+        fn handle_round_over(&mut self, gs: &GameState, ts: &TerminalState, player_index: usize) -> Result<(), fmt::Error> {
+            self.0.push(format!("round over {} {} {}", gs.bankroll, ts.deltas[player_index], player_index));
+            Ok(())
+        }
+
+        fn get_action(&mut self, gs: &GameState, rs: &RoundState, player_index: usize) -> Result<Action, fmt::Error> {
+            Ok(CheckCall.get_action(gs, rs, player_index).unwrap_or(Action::Fold))
+        }
+
+        fn handle_game_start(&mut self, player_index: usize, _config: &RunnerConfig) -> Result<(), fmt::Error> {
+            self.0.push(format!("start {}", player_index));
+            Ok(())
+        }
+
+        fn handle_game_end(&mut self, gs: &GameState, summary: &MatchSummary) -> Result<(), fmt::Error> {
+            self.0.push(format!("end {} {:?} {} {}", gs.bankroll, summary.deltas, summary.showdowns, summary.folds));
+            Ok(())
+        }
+
+        fn handle_opponent_action(&mut self, _gs: &GameState, rs: &RoundState, action: Action, player_index: usize) -> Result<(), fmt::Error> {
+            self.0.push(format!("opponent {:?} street {} {}", action, rs.street, player_index));
+            Ok(())
+        }
+
+        fn handle_reveal(&mut self, _gs: &GameState, hand: &HoleCards, player_index: usize) -> Result<(), fmt::Error> {
+            self.0.push(format!("reveal {} {}", hand, player_index));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn replay_calls_the_hooks_in_order() {
+        let session: Session = "0.0 < T20.000 P0 H4s,Ah\n0.1 > C\n0.2 < T19.9 C K B2c,3d,Kh K\n0.3 > K\n0.4 < T19.8 K B2c,3d,Kh,5s K\n0.5 > K\n0.6 < T19.7 K B2c,3d,Kh,5s,9c K\n0.7 > K\n0.8 < T19.6 K O7d,7h D-2 Q\n".parse().unwrap();
+        let mut bot = Recording::default();
+        assert_eq!(Runner::replay_bot(&mut bot, &session, &RunnerConfig::default()).unwrap(), ReplayOutcome::Matched(4));
+        assert_eq!(bot.0, [
+            "start 0",
+            "new round 1 [4s, Ah] 0",
+            "opponent Check street 0 0",
+            "opponent Check street 3 0",
+            "opponent Check street 4 0",
+            "opponent Check street 5 0",
+            "reveal [7d, 7h] 0",
+            "round over -2 -2 0",
+            "end -2 [-2] 1 0",
+        ]);
+    }
+
+    #[test]
+    fn hands_must_have_the_configured_size() {
+        let config = RunnerConfig::default();
+        assert_eq!(ServerAction::parse_line("T20.000 P0 H4s,Ah", &config).unwrap().len(), 3);
+        assert!(matches!(ServerAction::parse_line("H4s,Ah,Kd,Kc", &config), Err(RunnerError::Protocol(_))));
+        let omaha = RunnerConfig::builder().hole_cards(4).build().unwrap();
+        assert!(ServerAction::parse_line("H4s,Ah,Kd,Kc", &omaha).is_ok());
+        assert!(matches!(ServerAction::parse_line("H4s,Ah", &omaha), Err(RunnerError::Protocol(_))));
+    }
+
+    #[test]
+    fn protocol_fallback_skips_bad_clauses() {
+        let config = RunnerConfig::builder().error_policy(ErrorClass::Protocol, ErrorPolicy::Fallback).build().unwrap();
+        let clauses = ServerAction::parse_line("T20.000 X1 P0 Dabc", &config).unwrap();
+        assert_eq!(clauses.len(), 2);
+        assert!(matches!(ServerAction::parse_line("X1", &RunnerConfig::default()), Err(RunnerError::Protocol(_))));
+    }
+
+    /// Plays an engine on a local socket that sends each line and reads one response to it,
+    /// then sends the last line without waiting for one. Returns the address to connect to, and the responses.
+    fn engine(lines: &'static [&'static str], last: &'static str) -> (std::net::SocketAddr, JoinHandle<Vec<String>>) {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let engine = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut writer = stream;
+            let mut responses = vec![];
+            for line in lines {
+                writeln!(writer, "{}", line).unwrap();
+                let mut response = String::new();
+                reader.read_line(&mut response).unwrap();
+                responses.push(response.trim().to_string());
+            }
+            writeln!(writer, "{}", last).unwrap();
+            responses
+        });
+        (addr, engine)
+    }
+
+    #[test]
+    fn plays_a_match_against_an_engine() {
+        let (addr, engine) = engine(&["T20.000 P0 H4s,Ah", "T19.9 C K B2c,3d,Kh K", "T19.8 K B2c,3d,Kh,5s K", "T19.7 K B2c,3d,Kh,5s,9c K"], "T19.6 K O7d,7h D-2 Q");
+        let summary = Runner::run_bot_with(Box::new(CheckCall), addr, RunnerConfig::default()).unwrap();
+        assert_eq!(engine.join().unwrap(), ["C", "K", "K", "K"]);
+        assert_eq!(summary.end_reason, EndReason::Quit);
+        assert_eq!(summary.deltas, [-2]);
+        assert_eq!(summary.showdowns, 1);
+    }
+
+    #[test]
+    fn overruns_send_the_latest_suggestion() {
+        let (addr, engine) = engine(&["T20.000 P0 H4s,Ah"], "Q");
+        let (bot, _) = Slow::new(Some(Action::Raise(6)));
+        Runner::run_bot_with(Box::new(bot), addr, RunnerConfig::default()).unwrap();
+        assert_eq!(engine.join().unwrap(), ["R6"]);
     }
 }
@@ -0,0 +1,275 @@
+//! Turning whatever a bot wants to do into an action the engine accepts
+//!
+//! Sanitizers only depend on the `RoundState` the action is played in, so they can be used
+//! by anything that drives a bot, not just the runner.
+
+use super::actions::{Action, ActionType};
+use super::states::RoundState;
+use std::fmt;
+use std::error::Error;
+
+/// Describes what makes an action illegal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Violation {
+    /// Raising is not allowed right now
+    RaiseNotAllowed,
+    /// The raise is outside of the legal (inclusive) bounds
+    RaiseOutOfBounds { min: u32, max: u32 },
+    /// Checking while facing a bet
+    CheckFacingBet,
+    /// Calling when there is nothing to call
+    NothingToCall,
+    /// Folding when checking is free
+    NeedlessFold,
+}
+
+impl Violation {
+    /// Finds what is wrong with an action, if anything
+    pub fn find(action: Action, round_state: &RoundState) -> Option<Violation> {
+        let legal_actions = round_state.legal_actions();
+        match action {
+            Action::Raise(amount) => if !legal_actions.contains(ActionType::RAISE) {
+                Some(Violation::RaiseNotAllowed)
+            } else {
+                let [min, max] = round_state.raise_bounds();
+                if amount < min || amount > max {
+                    Some(Violation::RaiseOutOfBounds { min, max })
+                } else {
+                    None
+                }
+            },
+            Action::Check if !legal_actions.contains(ActionType::CHECK) => Some(Violation::CheckFacingBet),
+            Action::Call if !legal_actions.contains(ActionType::CALL) => Some(Violation::NothingToCall),
+            Action::Fold if !legal_actions.contains(ActionType::FOLD) => Some(Violation::NeedlessFold),
+            _ => None,
+        }
+    }
+}
+
+impl fmt::Display for Violation {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Violation::RaiseNotAllowed => write!(fmt, "raising is not allowed"),
+            Violation::RaiseOutOfBounds { min, max } => write!(fmt, "raise is outside of [{}, {}]", min, max),
+            Violation::CheckFacingBet => write!(fmt, "cannot check while facing a bet"),
+            Violation::NothingToCall => write!(fmt, "there is nothing to call"),
+            Violation::NeedlessFold => write!(fmt, "folding when checking is free"),
+        }
+    }
+}
+
+/// A change a sanitizer made to a bot's action
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Correction {
+    pub requested: Action,
+    pub applied: Action,
+    pub violation: Violation,
+}
+
+impl fmt::Display for Correction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{:?} became {:?} ({})", self.requested, self.applied, self.violation)
+    }
+}
+
+/// An illegal action that a sanitizer refused to correct
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IllegalAction {
+    pub action: Action,
+    pub violation: Violation,
+}
+
+impl Error for IllegalAction {}
+
+impl fmt::Display for IllegalAction {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "Illegal action {:?}: {}", self.action, self.violation)
+    }
+}
+
+/// A policy for dealing with illegal actions
+pub trait ActionSanitizer: Send + Sync {
+    /// Picks the action to play in place of one that is illegal because of `violation`
+    fn correct(&self, action: Action, violation: Violation, round_state: &RoundState) -> Result<Action, IllegalAction>;
+
+    /// Passes legal actions through and corrects illegal ones.
+    /// Returns the action to play, along with the correction that was made, if any.
+    fn sanitize(&self, action: Action, round_state: &RoundState) -> Result<(Action, Option<Correction>), IllegalAction> {
+        match Violation::find(action, round_state) {
+            None => Ok((action, None)),
+            Some(violation) => {
+                let applied = self.correct(action, violation, round_state)?;
+                Ok((applied, Some(Correction { requested: action, applied, violation })))
+            }
+        }
+    }
+}
+
+fn check_or(round_state: &RoundState, otherwise: Action) -> Action {
+    if round_state.legal_actions().contains(ActionType::CHECK) {
+        Action::Check
+    } else {
+        otherwise
+    }
+}
+
+/// Backs off to the most passive legal action: any illegal raise becomes a check (or call),
+/// and checking into a bet becomes a fold. This is what the runner has always done.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PassiveFallback;
+
+impl ActionSanitizer for PassiveFallback {
+    fn correct(&self, _action: Action, violation: Violation, round_state: &RoundState) -> Result<Action, IllegalAction> {
+        Ok(match violation {
+            Violation::RaiseNotAllowed | Violation::RaiseOutOfBounds { .. } => check_or(round_state, Action::Call),
+            Violation::CheckFacingBet => Action::Fold,
+            Violation::NothingToCall | Violation::NeedlessFold => Action::Check,
+        })
+    }
+}
+
+/// Moves out of bounds raises to the nearest legal amount, and otherwise behaves like `PassiveFallback`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ClampToBounds;
+
+impl ActionSanitizer for ClampToBounds {
+    fn correct(&self, action: Action, violation: Violation, round_state: &RoundState) -> Result<Action, IllegalAction> {
+        match violation {
+            Violation::RaiseOutOfBounds { min, max } => Ok(Action::Raise(action.amount().max(min).min(max))),
+            violation => PassiveFallback.correct(action, violation, round_state),
+        }
+    }
+}
+
+/// Stays as close to what the bot meant as the rules allow: raises are clamped into bounds,
+/// a raise that cannot be made becomes a call (or check), and checking into a bet becomes a call
+#[derive(Debug, Clone, Copy, Default)]
+pub struct NearestLegal;
+
+impl ActionSanitizer for NearestLegal {
+    fn correct(&self, action: Action, violation: Violation, round_state: &RoundState) -> Result<Action, IllegalAction> {
+        Ok(match violation {
+            Violation::RaiseOutOfBounds { min, max } => Action::Raise(action.amount().max(min).min(max)),
+            Violation::RaiseNotAllowed | Violation::CheckFacingBet => check_or(round_state, Action::Call),
+            Violation::NothingToCall | Violation::NeedlessFold => Action::Check,
+        })
+    }
+}
+
+/// Refuses to correct anything
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Strict;
+
+impl ActionSanitizer for Strict {
+    fn correct(&self, action: Action, violation: Violation, _round_state: &RoundState) -> Result<Action, IllegalAction> {
+        Err(IllegalAction { action, violation })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::CardDeck;
+    use crate::states::{BIG_BLIND, SMALL_BLIND, STARTING_STACK};
+
+    /// The small blind facing the big blind before the flop
+    fn facing_bet() -> RoundState {
+        RoundState {
+            button: 0,
+            street: 0,
+            pips: [SMALL_BLIND, BIG_BLIND],
+            stacks: [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [None, None],
+            deck: CardDeck(vec![]),
+            previous: None,
+        }
+    }
+
+    /// First to act on the flop, with nothing to call
+    fn checked_to() -> RoundState {
+        RoundState {
+            button: 1,
+            street: 3,
+            pips: [0, 0],
+            stacks: [STARTING_STACK - BIG_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [None, None],
+            deck: CardDeck(vec![]),
+            previous: None,
+        }
+    }
+
+    /// All in before the flop, so the only options are to fold or call
+    fn facing_all_in() -> RoundState {
+        RoundState {
+            button: 1,
+            street: 0,
+            pips: [STARTING_STACK, BIG_BLIND],
+            stacks: [0, STARTING_STACK - BIG_BLIND],
+            hands: [None, None],
+            deck: CardDeck(vec![]),
+            previous: None,
+        }
+    }
+
+    #[test]
+    fn finds_violations() {
+        let rs = facing_bet();
+        let [min, max] = rs.raise_bounds();
+        assert_eq!(Violation::find(Action::Raise(min), &rs), None);
+        assert_eq!(Violation::find(Action::Raise(max), &rs), None);
+        assert_eq!(Violation::find(Action::Raise(min - 1), &rs), Some(Violation::RaiseOutOfBounds { min, max }));
+        assert_eq!(Violation::find(Action::Raise(max + 1), &rs), Some(Violation::RaiseOutOfBounds { min, max }));
+        assert_eq!(Violation::find(Action::Check, &rs), Some(Violation::CheckFacingBet));
+        assert_eq!(Violation::find(Action::Call, &rs), None);
+
+        let rs = checked_to();
+        assert_eq!(Violation::find(Action::Call, &rs), Some(Violation::NothingToCall));
+        assert_eq!(Violation::find(Action::Fold, &rs), Some(Violation::NeedlessFold));
+        assert_eq!(Violation::find(Action::Check, &rs), None);
+
+        assert_eq!(Violation::find(Action::Raise(10), &facing_all_in()), Some(Violation::RaiseNotAllowed));
+    }
+
+    #[test]
+    fn legal_actions_pass_through() {
+        let rs = facing_bet();
+        for action in [Action::Fold, Action::Call, Action::Raise(rs.raise_bounds()[0])] {
+            assert_eq!(PassiveFallback.sanitize(action, &rs).unwrap(), (action, None));
+            assert_eq!(Strict.sanitize(action, &rs).unwrap(), (action, None));
+        }
+    }
+
+    #[test]
+    fn passive_fallback_backs_off() {
+        let rs = facing_bet();
+        let (applied, correction) = PassiveFallback.sanitize(Action::Raise(1000), &rs).unwrap();
+        assert_eq!(applied, Action::Call);
+        assert_eq!(correction.unwrap().requested, Action::Raise(1000));
+        assert_eq!(PassiveFallback.sanitize(Action::Check, &rs).unwrap().0, Action::Fold);
+
+        let rs = checked_to();
+        assert_eq!(PassiveFallback.sanitize(Action::Raise(1000), &rs).unwrap().0, Action::Check);
+        assert_eq!(PassiveFallback.sanitize(Action::Call, &rs).unwrap().0, Action::Check);
+        assert_eq!(PassiveFallback.sanitize(Action::Fold, &rs).unwrap().0, Action::Check);
+
+        assert_eq!(PassiveFallback.sanitize(Action::Raise(10), &facing_all_in()).unwrap().0, Action::Call);
+    }
+
+    #[test]
+    fn clamping_keeps_raises_in_bounds() {
+        let rs = facing_bet();
+        let [min, max] = rs.raise_bounds();
+        assert_eq!(ClampToBounds.sanitize(Action::Raise(1000), &rs).unwrap().0, Action::Raise(max));
+        assert_eq!(ClampToBounds.sanitize(Action::Raise(0), &rs).unwrap().0, Action::Raise(min));
+        assert_eq!(ClampToBounds.sanitize(Action::Check, &rs).unwrap().0, Action::Fold);
+        assert_eq!(NearestLegal.sanitize(Action::Raise(1000), &rs).unwrap().0, Action::Raise(max));
+        assert_eq!(NearestLegal.sanitize(Action::Check, &rs).unwrap().0, Action::Call);
+        assert_eq!(NearestLegal.sanitize(Action::Raise(10), &facing_all_in()).unwrap().0, Action::Call);
+    }
+
+    #[test]
+    fn strict_refuses() {
+        let err = Strict.sanitize(Action::Check, &facing_bet()).unwrap_err();
+        assert_eq!(err, IllegalAction { action: Action::Check, violation: Violation::CheckFacingBet });
+    }
+}
@@ -0,0 +1,270 @@
+//! Recording and replaying of raw engine sessions
+//!
+//! A session file holds one event per line, in the order it happened:
+//!
+//! ```text
+//! 0.000041 < T20.000 P0 H4s,Ah
+//! 0.000187 > C
+//! ```
+//!
+//! The first column is the time in seconds since the recording started, the second
+//! is `<` for a line received from the engine and `>` for a line we sent back.
+//! Everything after that is the raw line, exactly as it went over the wire, less its line ending.
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+use std::error::Error;
+
+const RECEIVED_MARKER: &str = "<";
+const SENT_MARKER: &str = ">";
+
+/// A single line that went over the wire
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SessionEvent {
+    Received(String),
+    Sent(String),
+}
+
+/// A timestamped session event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionEntry {
+    pub elapsed: Duration,
+    pub event: SessionEvent,
+}
+
+impl fmt::Display for SessionEntry {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let (marker, line) = match self.event {
+            SessionEvent::Received(ref line) => (RECEIVED_MARKER, line),
+            SessionEvent::Sent(ref line) => (SENT_MARKER, line),
+        };
+        write!(fmt, "{:.6} {} {}", self.elapsed.as_secs_f64(), marker, line)
+    }
+}
+
+impl FromStr for SessionEntry {
+    type Err = SessionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let malformed = || SessionError::Malformed(s.to_string());
+        // Only the timestamp is trimmed, since the line itself is kept as it was
+        let mut parts = s.trim_start().splitn(3, ' ');
+        let elapsed = parts.next()
+            .and_then(|x| x.parse::<f64>().ok())
+            .and_then(|x| Duration::try_from_secs_f64(x).ok())
+            .ok_or_else(malformed)?;
+        let marker = parts.next().ok_or_else(malformed)?;
+        let line = parts.next().unwrap_or("").to_string();
+        let event = match marker {
+            RECEIVED_MARKER => SessionEvent::Received(line),
+            SENT_MARKER => SessionEvent::Sent(line),
+            _ => return Err(malformed()),
+        };
+        Ok(SessionEntry { elapsed, event })
+    }
+}
+
+/// Tees every line the runner receives and sends into a session file
+pub struct SessionRecorder {
+    writer: Box<dyn Write + Send>,
+    start: Instant,
+}
+
+impl SessionRecorder {
+    /// Creates (or truncates) a session file at the given path
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<SessionRecorder> {
+        Ok(SessionRecorder::new(BufWriter::new(File::create(path)?)))
+    }
+
+    /// Records into an arbitrary writer
+    pub fn new<W: Write + Send + 'static>(writer: W) -> SessionRecorder {
+        SessionRecorder {
+            writer: Box::new(writer),
+            start: Instant::now(),
+        }
+    }
+
+    /// Records a line received from the engine. Its line ending, if any, is left out.
+    pub fn received(&mut self, line: &str) -> io::Result<()> {
+        self.record(SessionEvent::Received(line.trim_end_matches(&['\r', '\n'][..]).to_string()))
+    }
+
+    /// Records a line sent to the engine
+    pub fn sent(&mut self, line: &str) -> io::Result<()> {
+        self.record(SessionEvent::Sent(line.to_string()))
+    }
+
+    fn record(&mut self, event: SessionEvent) -> io::Result<()> {
+        let entry = SessionEntry { elapsed: self.start.elapsed(), event };
+        writeln!(self.writer, "{}", entry)?;
+        // Flush every line, as the recording is most useful exactly when we crash
+        self.writer.flush()
+    }
+}
+
+impl fmt::Debug for SessionRecorder {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        fmt.debug_struct("SessionRecorder").field("start", &self.start).finish()
+    }
+}
+
+/// A recorded session
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Session(pub Vec<SessionEntry>);
+
+impl Session {
+    /// Loads a session file
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Session, SessionError> {
+        Session::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads a session from any buffered reader. Blank lines are skipped.
+    pub fn read<R: BufRead>(reader: R) -> Result<Session, SessionError> {
+        let mut entries = vec![];
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                entries.push(line.parse::<SessionEntry>()?);
+            }
+        }
+        Ok(Session(entries))
+    }
+}
+
+impl FromStr for Session {
+    type Err = SessionError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Session::read(s.as_bytes())
+    }
+}
+
+/// Where a replayed bot stopped agreeing with the recording
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the recorded entry that did not match
+    pub entry: usize,
+    /// When the entry was recorded
+    pub elapsed: Duration,
+    /// The engine line the bot was responding to
+    pub received: String,
+    /// What the bot sent in the recording
+    pub expected: String,
+    /// What the bot sent during the replay
+    pub actual: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "entry {} ({:.6}s) after \"{}\": expected \"{}\", got \"{}\"",
+            self.entry, self.elapsed.as_secs_f64(), self.received, self.expected, self.actual)
+    }
+}
+
+/// Result of replaying a session into a bot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReplayOutcome {
+    /// Every response matched the recording. Holds the number of responses compared.
+    Matched(usize),
+    /// The first response that did not match the recording
+    Diverged(Divergence),
+}
+
+/// Describes errors that can occur when reading a session
+#[derive(Debug)]
+pub enum SessionError {
+    Io(io::Error),
+    Malformed(String),
+}
+
+impl From<io::Error> for SessionError {
+    fn from(e: io::Error) -> SessionError {
+        SessionError::Io(e)
+    }
+}
+
+impl Error for SessionError {}
+
+impl fmt::Display for SessionError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SessionError::Io(e) => write!(fmt, "Session I/O error: {}", e),
+            SessionError::Malformed(s) => write!(fmt, "Malformed session line: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// A writer that can still be read once the recorder owns it
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn entries_round_trip() {
+        let entry = SessionEntry {
+            elapsed: Duration::from_micros(187),
+            event: SessionEvent::Sent("R12".into()),
+        };
+        assert_eq!(entry.to_string(), "0.000187 > R12");
+        assert_eq!(entry.to_string().parse::<SessionEntry>().unwrap(), entry);
+    }
+
+    #[test]
+    fn lines_are_kept_as_they_were() {
+        let entry: SessionEntry = "0.5 < T20.000  P0 H4s,Ah ".parse().unwrap();
+        assert_eq!(entry.event, SessionEvent::Received("T20.000  P0 H4s,Ah ".into()));
+        let entry: SessionEntry = "0.5 >".parse().unwrap();
+        assert_eq!(entry.event, SessionEvent::Sent("".into()));
+    }
+
+    #[test]
+    fn bad_timestamps_are_malformed() {
+        for line in &["inf < T20.000", "1e30 < T20.000", "-1 < T20.000", "NaN < T20.000", "x < T20.000"] {
+            match line.parse::<SessionEntry>() {
+                Err(SessionError::Malformed(_)) => {},
+                other => panic!("{:?} parsed as {:?}", line, other),
+            }
+        }
+    }
+
+    #[test]
+    fn unknown_markers_are_malformed() {
+        assert!(matches!("0.1 ? C".parse::<SessionEntry>(), Err(SessionError::Malformed(_))));
+        assert!(matches!("0.1".parse::<SessionEntry>(), Err(SessionError::Malformed(_))));
+    }
+
+    #[test]
+    fn recorder_keeps_raw_lines() {
+        let buffer = Shared::default();
+        let mut recorder = SessionRecorder::new(buffer.clone());
+        recorder.received(" T20.000 P0 H4s,Ah \r\n").unwrap();
+        recorder.sent("C").unwrap();
+
+        let written = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+        let session: Session = written.parse().unwrap();
+        let events: Vec<SessionEvent> = session.0.into_iter().map(|entry| entry.event).collect();
+        assert_eq!(events, vec![SessionEvent::Received(" T20.000 P0 H4s,Ah ".into()), SessionEvent::Sent("C".into())]);
+    }
+
+    #[test]
+    fn blank_lines_are_skipped() {
+        let session: Session = "0.1 < T20.000 P0 H4s,Ah\n\n0.2 > C\n".parse().unwrap();
+        assert_eq!(session.0.len(), 2);
+    }
+}
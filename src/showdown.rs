@@ -1,4 +1,5 @@
-use crate::cards::{CardValue, Card, CardSuit};
+use crate::cards::{CardValue, Card, CardDeck, CardSuit, Variant, MAX_HOLE_CARDS};
+use crate::texture::BoardTexture;
 
 use std::cmp::{PartialEq, Eq, PartialOrd, Ord, Ordering};
 use std::fmt;
@@ -113,7 +114,6 @@ pub enum StraightDrawType {
 pub enum PotentialHand {
     Hand(Hand), // An actual hand that can win
     StraightDraw(HashSet<Card>, StraightDrawType), // A potential straight with a hole inside
-    // Don't mess with backhand straight draws, so don't even detect them
     FlushDraw(HashSet<Card>), // A potential flush with 1 missing card.
     StraightFlushDraw(HashSet<Card>, StraightDrawType), // A straight
     RoyalFlushDraw(HashSet<Card>, StraightDrawType),
@@ -157,12 +157,89 @@ impl PotentialHand {
     }
 }
 
+/// A draw that needs both of the next two board cards, as reported by `ShowdownEngine::backdoor_draws`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BackdoorDraw {
+    Flush(HashSet<Card>), // Three of a suit
+    Straight(HashSet<Card>, StraightDrawType), // Three values that fit in a straight, open ended if they are in a row
+}
+
+impl fmt::Display for BackdoorDraw {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BackdoorDraw::Flush(a) => write!(fmt, "[BackdoorFlushDraw {}]", a.iter().format(" ")),
+            BackdoorDraw::Straight(a, typ) => write!(fmt, "[BackdoorStraightDraw {} ({:?})]", a.iter().format(" "), typ),
+        }
+    }
+}
+
+impl BackdoorDraw {
+    pub fn cards(&self) -> HashSet<Card> {
+        match self {
+            BackdoorDraw::Flush(draw) | BackdoorDraw::Straight(draw, _) => draw.clone(),
+        }
+    }
+}
+
+/// Which value, if any, can also play low in a straight, below the bottom of the ordering
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub enum WheelRule {
+    /// The top value of the ordering, whatever it is
+    #[default]
+    ByOrdering,
+    /// The Ace, wherever the ordering puts it. When it is already one of the lowest four values, there's nothing to wrap.
+    Ace,
+    /// Nothing wraps around
+    NoWrap,
+}
+
+impl WheelRule {
+    pub const ALL: [WheelRule; 3] = [WheelRule::ByOrdering, WheelRule::Ace, WheelRule::NoWrap];
+}
+
+impl fmt::Display for WheelRule {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            WheelRule::ByOrdering => write!(fmt, "byordering"),
+            WheelRule::Ace => write!(fmt, "ace"),
+            WheelRule::NoWrap => write!(fmt, "nowrap"),
+        }
+    }
+}
+
+/// How players make their hands out of their hole cards and the board
+#[derive(Debug, Clone, Copy, Default, Hash, PartialEq, Eq)]
+pub enum HoleRule {
+    /// Any five of the hole cards and the board together, as in hold'em
+    #[default]
+    AnyFive,
+    /// Exactly two hole cards and three board cards, as in Omaha
+    TwoPlusThree,
+}
+
+impl HoleRule {
+    pub const ALL: [HoleRule; 2] = [HoleRule::AnyFive, HoleRule::TwoPlusThree];
+}
+
+impl fmt::Display for HoleRule {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HoleRule::AnyFive => write!(fmt, "anyfive"),
+            HoleRule::TwoPlusThree => write!(fmt, "twoplusthree"),
+        }
+    }
+}
+
 /// Detects possible and best hands out of a given set of cards
 /// NOTE: Behavior for `potential_hands` or `all_possible_hands` is undefined if passed hand contains duplicate cards, so be sure to call
 /// ShowdownEngine::make_hand_unique on any potential hands you try to pass in if you can't guarantee that
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ShowdownEngine {
-    ordering: [CardValue; 13]
+    ordering: [CardValue; 13],
+    wheel: WheelRule,
+    variant: Variant,
+    hole_cards: usize,
+    hole_rule: HoleRule,
 }
 
 /* Poker hands are (high to low):
@@ -172,6 +249,7 @@ Four-of-a-kind - 4 cards same value
 Full house - Three-of-a-kind and a pair
 Flush - 5 cards of any suit
 Straight - 5 in order, not same suit
+(Short deck swaps flush and full house)
 Three-of-a-kind - 3 cards same value
 Two-pair - 2 different pairs
 Pair - 2 cards same value
@@ -183,11 +261,10 @@ macro_rules! detect_hands {
         let detected_straights = $slf.detect_straights($hand);
         let detected_flushes = $slf.detect_flushes($hand);
 
-        let detected_straight_flushes = detected_straights.clone().into_iter().filter_map(|(straight, typ)| if detected_flushes.contains(&straight) {
-            Some((straight, typ))
-        } else {
-            None
-        }).collect::<Vec<_>>();
+        // Going by the suits of each straight rather than the flushes finds the wheel too, which plays its Ace low
+        let detected_straight_flushes = detected_straights.iter().filter(|(straight, _)| {
+            straight.iter().map(|card| card.suit()).all_equal()
+        }).cloned().collect::<Vec<_>>();
 
         let detected_four_of_a_kind = $slf.detect_of_a_kind($hand, 4);
         let detected_three_of_a_kind = $slf.detect_of_a_kind($hand, 3);
@@ -255,14 +332,100 @@ macro_rules! process_hands {
 impl ShowdownEngine {
     pub fn new(ordering: [CardValue; 13]) -> ShowdownEngine {
         ShowdownEngine {
-            ordering
+            ordering,
+            wheel: WheelRule::default(),
+            variant: Variant::default(),
+            hole_cards: 2,
+            hole_rule: HoleRule::default(),
+        }
+    }
+
+    /// Deals each player the given number of hole cards, to be used under the given rule. PLO is 4 (or 5) with `TwoPlusThree`.
+    /// This should match `RunnerConfig::hole_cards`, which is what the engine actually deals.
+    ///
+    /// # Panics
+    /// If the count is 0 or above `MAX_HOLE_CARDS`, or below 2 with `TwoPlusThree`.
+    pub fn with_hole_cards(mut self, count: usize, rule: HoleRule) -> ShowdownEngine {
+        assert!((1..=MAX_HOLE_CARDS).contains(&count), "Players can't be dealt {} hole cards (at most {})", count, MAX_HOLE_CARDS);
+        assert!(rule != HoleRule::TwoPlusThree || count >= 2, "Two hole cards have to play, so {} isn't enough", count);
+        self.hole_cards = count;
+        self.hole_rule = rule;
+        self
+    }
+
+    /// How many hole cards each player gets
+    pub fn hole_cards(&self) -> usize {
+        self.hole_cards
+    }
+
+    pub fn hole_rule(&self) -> HoleRule {
+        self.hole_rule
+    }
+
+    /// Plays the given variant. Short deck takes the four lowest values out of the deck and ranks a flush above a full house.
+    pub fn with_variant(mut self, variant: Variant) -> ShowdownEngine {
+        self.variant = variant;
+        self
+    }
+
+    pub fn variant(&self) -> Variant {
+        self.variant
+    }
+
+    /// Every card in play
+    pub fn deck(&self) -> CardDeck {
+        CardDeck::of(self.variant, &self.ordering)
+    }
+
+    /// Every card in play except the given ones
+    pub fn deck_without(&self, excluded: &[Card]) -> CardDeck {
+        let CardDeck(cards) = self.deck();
+        CardDeck(cards.into_iter().filter(|card| !excluded.contains(card)).collect())
+    }
+
+    /// The cards the variant takes out of the deck
+    pub fn removed_cards(&self) -> Vec<Card> {
+        let values = self.variant.values(&self.ordering);
+        let CardDeck(cards) = CardDeck::full();
+        cards.into_iter().filter(|card| !values.contains(&card.value())).collect()
+    }
+
+    /// Uses the given rule for which value wraps around to play low in a straight
+    pub fn with_wheel(mut self, wheel: WheelRule) -> ShowdownEngine {
+        self.wheel = wheel;
+        self
+    }
+
+    pub fn wheel(&self) -> WheelRule {
+        self.wheel
+    }
+
+    /// The value that can also play low in a straight under the wheel rule, if any
+    pub fn wheel_value(&self) -> Option<CardValue> {
+        let values = self.variant.values(&self.ordering);
+        match self.wheel {
+            WheelRule::ByOrdering => Some(self.ordering[12]),
+            // Down in the bottom four, a low Ace would only fill out a straight with itself
+            WheelRule::Ace if !values[4..].contains(&CardValue::Ace) => None,
+            WheelRule::Ace => Some(CardValue::Ace),
+            WheelRule::NoWrap => None,
         }
     }
 
+    /// The values that make up straights, lowest first: the wheel value (if any), then the values in play
+    pub fn straight_order(&self) -> Vec<CardValue> {
+        self.wheel_value().into_iter().chain(self.variant.values(&self.ordering).iter().copied()).collect()
+    }
+
+    /// Looks at a board the way the engine sees straights
+    pub fn board_texture(&self, board: &[Card]) -> BoardTexture {
+        BoardTexture::new(board, &self.straight_order())
+    }
+
     pub fn make_hand_unique<'a, H, C: Borrow<Card>>(hand: H) -> Vec<Card> where H: 'a + Iterator<Item=C> {
         hand.fold(vec![], |mut acc, card| {
             if !acc.contains(card.borrow()) {
-                acc.push(card.borrow().clone());
+                acc.push(*card.borrow());
             }
             acc
         })
@@ -335,13 +498,13 @@ impl ShowdownEngine {
             }))
             .chain(straight_flushes.iter().filter_map(|(x, typ)| if x.is_subset(&hand.iter().copied().collect()) {
                 if typ == &StraightDrawType::Complete {
-                    if self.highest_card_value(x.iter()) == self.ordering[12] {
+                    if self.is_royal(x) {
                         Some(PotentialHand::Hand(Hand::RoyalFlush(x.clone())))
                     } else {
                         Some(PotentialHand::Hand(Hand::StraightFlush(x.clone())))
                     }
                 } else {
-                    if self.highest_card_value(x.iter()) == self.ordering[12] || self.highest_card_value(x.iter()) == self.ordering[11] {
+                    if self.draws_to_royal(x) {
                         Some(PotentialHand::RoyalFlushDraw(x.clone(), *typ))
                     } else {
                         Some(PotentialHand::StraightFlushDraw(x.clone(), *typ))
@@ -375,7 +538,7 @@ impl ShowdownEngine {
                     &seen | &hand
                 })
             };
-        };
+        }
 
         macro_rules! hands {
             ($hand:expr, $subset_array:expr) => {
@@ -388,15 +551,15 @@ impl ShowdownEngine {
         }
 
         let hand = best_hand!(hands);
-        if hand.len() > 0 {
+        if !hand.is_empty() {
             // Start from the bottom and go up!
             let pairs: Vec<_> = hands!(hand, pairs).collect();
             let straight_flushes: Vec<_> = hands!(straight hand, straight_flushes).collect();
             let mut straights: Vec<(HashSet<_>, _)> = hands!(straight hand, straights).collect();
-            straights = straights.into_iter().filter(|(straight, _)| !straight_flushes.iter().any(|(sf, _)| sf.is_superset(straight))).collect();
-            straights.sort_unstable_by(|(a, _), (b, _)| b.len().cmp(&a.len()));
+            straights.retain(|(straight, _)| !straight_flushes.iter().any(|(sf, _)| sf.is_superset(straight)));
+            straights.sort_unstable_by_key(|(straight, _)| std::cmp::Reverse(straight.len()));
             let flushes: Vec<_> = hands!(hand, flushes).cloned().filter(|flush| !straight_flushes.iter().any(|(sf, _)| sf.is_superset(flush))).collect();
-            let not_straight_flush_winning_hand: Vec<Hand> = if pairs.len() == 0 {
+            let not_straight_flush_winning_hand: Vec<Hand> = if pairs.is_empty() {
                 // There are no pairs, and thus no 3K, 2P, or 4K
                 // The only possibilities are HC STRAIGHT FLUSH STRAIGHT_FLUSH ROYAL_FLUSH and draws
                 vec![].into_iter().collect()
@@ -412,7 +575,7 @@ impl ShowdownEngine {
                             .chain(toaks.into_iter().flat_map(|toak| {
                                 let toak_value = toak.iter().map(|x| x.value()).collect::<Vec<_>>()[0];
                                 let possible_full_house_pairs: Vec<_> = pairs.iter().filter(|x| !x.iter().any(|card| card.value() == toak_value)).collect();
-                                if possible_full_house_pairs.len() > 0 {
+                                if !possible_full_house_pairs.is_empty() {
                                     // We have a Full House
                                     possible_full_house_pairs.into_iter().map(|pair| Hand::FullHouse(toak | pair)).collect()
                                 } else {
@@ -420,7 +583,7 @@ impl ShowdownEngine {
                                     vec![Hand::ThreeOfAKind(toak.clone())]
                                 }
                             }))
-                            .chain(vec![Hand::TwoPair(pairs[0] | pairs[1]), Hand::Pair(pairs[0].clone()), Hand::Pair(pairs[1].clone())].into_iter()).collect::<Vec<_>>()
+                            .chain(vec![Hand::TwoPair(pairs[0] | pairs[1]), Hand::Pair(pairs[0].clone()), Hand::Pair(pairs[1].clone())]).collect::<Vec<_>>()
                     }).collect()
                 } else {
                     // We only have 1 pair
@@ -428,7 +591,7 @@ impl ShowdownEngine {
                 }
             };
             straight_flushes.iter().filter_map(|(sf, _)| if sf.len() == 5 {
-                if self.highest_card_value(sf) == self.ordering[12] {
+                if self.is_royal(sf) {
                     Some(PotentialHand::Hand(Hand::RoyalFlush(sf.clone())))
                 } else {
                     Some(PotentialHand::Hand(Hand::StraightFlush(sf.clone())))
@@ -448,8 +611,7 @@ impl ShowdownEngine {
                 None
             })).chain(not_straight_flush_winning_hand.iter().cloned().map(PotentialHand::Hand))
             .chain(straight_flushes.iter().cloned().filter_map(|(sf, typ)| if typ != StraightDrawType::Complete {
-                let hv = self.highest_card_value(&sf);
-                if hv == self.ordering[12] || hv == self.ordering[11] {
+                if self.draws_to_royal(&sf) {
                     Some(PotentialHand::RoyalFlushDraw(sf.clone(), typ))
                 } else {
                     Some(PotentialHand::StraightFlushDraw(sf.clone(), typ))
@@ -457,7 +619,8 @@ impl ShowdownEngine {
             } else {
                 None
             }))
-            .chain(flushes.iter().cloned().filter_map(|flush| if flush.len() > 5 {
+            // Five of a suit is a flush, so anything else is a draw to one
+            .chain(flushes.iter().cloned().filter_map(|flush| if flush.len() != 5 {
                 Some(PotentialHand::FlushDraw(flush))
             } else {
                 None
@@ -480,6 +643,41 @@ impl ShowdownEngine {
         }
     }
 
+    /// Like `potential_hands`, with `to_come` board cards still to be dealt: draws that can't come in anymore
+    /// (with nothing to come) are left out
+    pub fn potential_hands_with_to_come(&self, hand: &[Card], straights: bool, to_come: usize) -> Vec<PotentialHand> {
+        self.potential_hands(hand, straights).into_iter().filter(|hand| to_come > 0 || hand.showdown().is_some()).collect()
+    }
+
+    /// Backdoor draws in the cards, which need both of the next two board cards, so there are only any
+    /// with at least two cards to come (on the flop). Draws already covered by a bigger draw or a made hand
+    /// of the same kind are left out. Flushes come first, then straights, open ended before inside ones.
+    pub fn backdoor_draws(&self, hand: &[Card], straights: bool, to_come: usize) -> Vec<BackdoorDraw> {
+        if to_come < 2 {
+            return vec![];
+        }
+        let backdoor_straights = if straights { self.detect_backdoor_straights(hand) } else { vec![] };
+        let found = self.potential_hands(hand, straights);
+        let mut draws: Vec<BackdoorDraw> = self.detect_backdoor_flushes(hand).into_iter().map(BackdoorDraw::Flush)
+            .chain(backdoor_straights.into_iter().map(|(draw, typ)| BackdoorDraw::Straight(draw, typ)))
+            .filter(|backdoor| !found.iter().any(|other| ShowdownEngine::covers_backdoor(other, backdoor)))
+            .collect();
+        draws.sort_by(|a, b| self.compare_backdoor_draws(b, a));
+        draws
+    }
+
+    fn covers_backdoor(other: &PotentialHand, backdoor: &BackdoorDraw) -> bool {
+        let same_kind = match backdoor {
+            BackdoorDraw::Flush(_) => matches!(other,
+                PotentialHand::Hand(Hand::Flush(_)) | PotentialHand::Hand(Hand::StraightFlush(_)) | PotentialHand::Hand(Hand::RoyalFlush(_))
+                | PotentialHand::FlushDraw(_) | PotentialHand::StraightFlushDraw(..) | PotentialHand::RoyalFlushDraw(..)),
+            BackdoorDraw::Straight(..) => matches!(other,
+                PotentialHand::Hand(Hand::Straight(_)) | PotentialHand::Hand(Hand::StraightFlush(_)) | PotentialHand::Hand(Hand::RoyalFlush(_))
+                | PotentialHand::StraightDraw(..) | PotentialHand::StraightFlushDraw(..) | PotentialHand::RoyalFlushDraw(..)),
+        };
+        same_kind && other.cards().is_superset(&backdoor.cards())
+    }
+
     /// Don't use this in practice. Only used for consistency checking of the engine
     pub fn process_hand_no_straight_all(&self, hand: &[Card]) -> PotentialHand {
         let hand = ShowdownEngine::make_hand_unique(hand.iter());
@@ -499,6 +697,16 @@ impl ShowdownEngine {
         }
     }
 
+    /// Like `process_hand_with_to_come`, without straights
+    pub fn process_hand_no_straight_with_to_come(&self, hand: &[Card], to_come: usize) -> PotentialHand {
+        let hand = ShowdownEngine::make_hand_unique(hand.iter());
+        let hands = self.potential_hands_with_to_come(&hand, false, to_come);
+        match process_hands!(self, hands) {
+            Some(hand) => hand,
+            None => PotentialHand::HighCard(self.highest_card(hand))
+        }
+    }
+
     /// Don't use this in practice. Only used for consistency checking of the engine
     pub fn process_hand_all(&self, hand: &[Card]) -> PotentialHand {
         let hand = ShowdownEngine::make_hand_unique(hand.iter());
@@ -518,12 +726,50 @@ impl ShowdownEngine {
         }
     }
 
-    fn detect_straights(&self, hand: &[Card]) -> Vec<(HashSet<Card>, StraightDrawType)> {
-        let mut sorted_bins = [vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![]];
-        for i in 1..14 {
-            sorted_bins[i] = hand.iter().filter(|x| (i - 1) == self.ordering.iter().position(|y| *y == x.value()).unwrap()).collect();
+    /// The best hand or draw in the cards, with `to_come` board cards still to be dealt (0 at showdown)
+    pub fn process_hand_with_to_come(&self, hand: &[Card], to_come: usize) -> PotentialHand {
+        let hand = ShowdownEngine::make_hand_unique(hand.iter());
+        let hands = self.potential_hands_with_to_come(&hand, true, to_come);
+        match process_hands!(self, hands) {
+            Some(hand) => hand,
+            None => PotentialHand::HighCard(self.highest_card(hand))
+        }
+    }
+
+    /// The sets of cards the hole rule lets a player make a hand out of: all their cards together with `AnyFive`,
+    /// and every two hole cards with every three board cards with `TwoPlusThree`.
+    /// With `TwoPlusThree` and fewer than three board cards, every board card plays.
+    pub fn holdings(&self, hole: &[Card], board: &[Card]) -> Vec<Vec<Card>> {
+        match self.hole_rule {
+            HoleRule::AnyFive => vec![hole.iter().chain(board).copied().collect()],
+            HoleRule::TwoPlusThree => hole.iter().copied().combinations(2.min(hole.len()))
+                .cartesian_product(board.iter().copied().combinations(3.min(board.len())).collect::<Vec<_>>())
+                .map(|(hole, board)| hole.into_iter().chain(board).collect())
+                .collect(),
         }
-        sorted_bins[0] = sorted_bins[13].clone();
+    }
+
+    /// The best hand a player makes out of their hole cards and the board, under the hole rule.
+    /// Draws are only reported while there are board cards to come.
+    pub fn process_holding(&self, hole: &[Card], board: &[Card]) -> PotentialHand {
+        let to_come = 5 - board.len().min(5);
+        match self.hole_rule {
+            HoleRule::AnyFive => self.process_hand_with_to_come(&self.holdings(hole, board)[0], to_come),
+            HoleRule::TwoPlusThree => {
+                let hands = self.holdings(hole, board).into_iter().map(|cards| self.process_hand_with_to_come(&cards, to_come));
+                match process_hands!(self, hands) {
+                    Some(hand) => hand,
+                    None => PotentialHand::HighCard(self.highest_card(hole.iter().chain(board))),
+                }
+            },
+        }
+    }
+
+    fn detect_straights(&self, hand: &[Card]) -> Vec<(HashSet<Card>, StraightDrawType)> {
+        // One bin per value in straight order, so the wheel (if any) and the variant's missing values are taken care of
+        let sorted_bins: Vec<Vec<&Card>> = self.straight_order().iter()
+            .map(|&value| hand.iter().filter(|x| x.value() == value).collect())
+            .collect();
         sorted_bins.windows(5).flat_map(|x| {
             let holes = x.iter().filter(|x| x.is_empty()).count();
             // All 5 bins in a row are full, we have at least one straight
@@ -531,10 +777,10 @@ impl ShowdownEngine {
                 // Start with the last bin and go up from there
                 let straights = x[4].iter().copied()
                     .map(|ele| vec![*ele].into_iter().collect::<HashSet<Card>>()).collect::<Vec<_>>().into_iter()
-                    .flat_map(|set| x[3].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
-                    .flat_map(|set| x[2].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
-                    .flat_map(|set| x[1].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
-                    .flat_map(|set| x[0].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
+                    .flat_map(|set| x[3].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele]).collect::<HashSet<_>>() ))
+                    .flat_map(|set| x[2].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele]).collect::<HashSet<_>>() ))
+                    .flat_map(|set| x[1].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele]).collect::<HashSet<_>>() ))
+                    .flat_map(|set| x[0].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele]).collect::<HashSet<_>>() ))
                     .fold(vec![], |mut acc, set| {
                         if !acc.contains(&set) {
                             acc.push(set);
@@ -545,13 +791,13 @@ impl ShowdownEngine {
             } else if holes == 1 {
                 // We have exactly 1 hole, and it is at the edge
                 let open_ended = x[0].is_empty() || x[4].is_empty();
-                let adjacent_x = x.into_iter().filter(|x| !x.is_empty()).collect::<Vec<_>>();
+                let adjacent_x = x.iter().filter(|x| !x.is_empty()).collect::<Vec<_>>();
                 // We know the length of adjacent_x is 4
                 let straight_draws = adjacent_x[3].iter().copied()
                     .map(|ele| vec![*ele].into_iter().collect::<HashSet<Card>>()).collect::<Vec<_>>().into_iter()
-                    .flat_map(|set| adjacent_x[2].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
-                    .flat_map(|set| adjacent_x[1].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
-                    .flat_map(|set| adjacent_x[0].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
+                    .flat_map(|set| adjacent_x[2].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele]).collect::<HashSet<_>>() ))
+                    .flat_map(|set| adjacent_x[1].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele]).collect::<HashSet<_>>() ))
+                    .flat_map(|set| adjacent_x[0].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele]).collect::<HashSet<_>>() ))
                     .fold(vec![], |mut acc, set| {
                         if !acc.contains(&set) {
                             acc.push(set);
@@ -559,31 +805,8 @@ impl ShowdownEngine {
                         acc
                     }).into_iter().map(|x| (x, if open_ended { StraightDrawType::OpenEnded } else { StraightDrawType::Inside })).collect();
                 straight_draws
-            } else if holes == 2 {
-                let mut x = x.to_vec();
-                x.dedup();
-                // We check if we can count the 3 cards as remaining side by side
-                if x.iter().fold(0, |adj_count, bin| if !bin.is_empty() { adj_count + 1 } else { 0 }) == 3 {
-                    // This is a potential open ended straight draw. remove holes and create the sets
-                    let adjacent_x = x.into_iter().filter(|x| !x.is_empty()).collect::<Vec<_>>();
-                    // We know the length of adjacent_x is 3
-                    let straight_draws = adjacent_x[2].iter().copied()
-                        .map(|ele| vec![*ele].into_iter().collect::<HashSet<Card>>()).collect::<Vec<_>>().into_iter()
-                        .flat_map(|set| adjacent_x[1].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
-                        .flat_map(|set| adjacent_x[0].iter().copied().map(move |ele| set.iter().copied().chain(vec![*ele].into_iter()).collect::<HashSet<_>>() ))
-                        .fold(vec![], |mut acc, set| {
-                            if !acc.contains(&set) {
-                                acc.push(set);
-                            }
-                            acc
-                        }).into_iter().map(|x| (x, StraightDrawType::OpenEnded)).collect();
-                    straight_draws
-                } else {
-                    // This is defintely a backdoor hand, so we ignore it
-                    vec![]
-                }
             } else {
-                // We don't detect any potential hands
+                // We don't detect any potential hands here. Backdoor draws (two holes) are left to detect_backdoor_straights
                 vec![]
             }
         }).collect()
@@ -591,20 +814,22 @@ impl ShowdownEngine {
 
     fn detect_flushes(&self, hand: &[Card]) -> Vec<HashSet<Card>> {
         let mut sorted_bins = [vec![], vec![], vec![], vec![]];
-        for i in 0..4 {
-            sorted_bins[i] = hand.iter().filter(|x| i == match x.suit() {
+        for (i, bin) in sorted_bins.iter_mut().enumerate() {
+            *bin = hand.iter().filter(|x| i == match x.suit() {
                 CardSuit::Spades => 0,
                 CardSuit::Hearts => 1,
                 CardSuit::Clubs => 2,
                 CardSuit::Diamonds => 3,
             }).copied().collect();
+            bin.sort_by(|a, b| self.value_order(&a.value(), &b.value()));
         }
 
-        sorted_bins.iter().cloned().flat_map(|x| x.windows(5).filter_map(|x| if x.len() >= 3 {
-            Some(x.into_iter().copied().collect::<HashSet<_>>())
+        // Four of a suit is a flush draw, and five or more make flushes, any five in a row by value
+        sorted_bins.iter().cloned().flat_map(|x| if x.len() == 4 {
+            vec![x.iter().copied().collect::<HashSet<_>>()]
         } else {
-            None
-        }).collect::<Vec<_>>()).fold(vec![], |mut acc, set| {
+            x.windows(5).map(|x| x.iter().copied().collect::<HashSet<_>>()).collect::<Vec<_>>()
+        }).fold(vec![], |mut acc, set| {
             if !acc.contains(&set) {
                 acc.push(set);
             }
@@ -612,11 +837,51 @@ impl ShowdownEngine {
         })
     }
 
+    /// Three values that fit in a window of five (two holes) make a backdoor straight draw.
+    /// It's open ended when the three are in a row with room on both sides.
+    fn detect_backdoor_straights(&self, hand: &[Card]) -> Vec<(HashSet<Card>, StraightDrawType)> {
+        let sorted_bins: Vec<Vec<Card>> = self.straight_order().iter()
+            .map(|&value| hand.iter().filter(|x| x.value() == value).copied().collect())
+            .collect();
+        let mut draws: Vec<(HashSet<Card>, StraightDrawType)> = vec![];
+        for start in 0..sorted_bins.len() - 4 {
+            let filled: Vec<usize> = (start..start + 5).filter(|&i| !sorted_bins[i].is_empty()).collect();
+            if filled.len() != 3 {
+                continue;
+            }
+            let in_a_row = filled[2] - filled[0] == 2;
+            let typ = if in_a_row && filled[0] > 0 && filled[2] < sorted_bins.len() - 1 {
+                StraightDrawType::OpenEnded
+            } else {
+                StraightDrawType::Inside
+            };
+            for &a in sorted_bins[filled[0]].iter() {
+                for &b in sorted_bins[filled[1]].iter() {
+                    for &c in sorted_bins[filled[2]].iter() {
+                        let set: HashSet<Card> = vec![a, b, c].into_iter().collect();
+                        if !draws.iter().any(|(seen, _)| *seen == set) {
+                            draws.push((set, typ));
+                        }
+                    }
+                }
+            }
+        }
+        draws
+    }
+
+    /// Exactly three of a suit make a backdoor flush draw
+    fn detect_backdoor_flushes(&self, hand: &[Card]) -> Vec<HashSet<Card>> {
+        CardSuit::ALL.iter()
+            .map(|&suit| hand.iter().filter(|x| x.suit() == suit).copied().collect::<HashSet<_>>())
+            .filter(|x| x.len() == 3)
+            .collect()
+    }
+
     /// Detect all sets of cards with <number> or more cards in the hand
     fn detect_of_a_kind(&self, hand: &[Card], number: usize) -> Vec<HashSet<Card>> {
         let mut sorted_bins = [vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![], vec![]];
-        for i in 0..13 {
-            sorted_bins[i] = hand.iter().filter(|x| i == self.ordering.iter().position(|y| *y == x.value()).unwrap()).collect();
+        for (i, bin) in sorted_bins.iter_mut().enumerate() {
+            *bin = hand.iter().filter(|x| i == self.ordering.iter().position(|y| *y == x.value()).unwrap()).collect();
         }
         let potential_oak: Vec<HashSet<_>> = sorted_bins.iter().cloned().filter_map(|x| if x.len() >= number {
             Some(x.into_iter().cloned().collect())
@@ -627,12 +892,17 @@ impl ShowdownEngine {
         for set in potential_oak.into_iter() {
             let set: Vec<Card> = set.into_iter().collect();
             for window in set.windows(number) {
-                sets.push(window.into_iter().copied().collect());
+                sets.push(window.iter().copied().collect());
             }
         }
         sets
     }
 
+    /// The card values this engine uses, lowest first
+    pub fn ordering(&self) -> &[CardValue; 13] {
+        &self.ordering
+    }
+
     pub fn value_order(&self, a: &CardValue, b: &CardValue) -> Ordering {
         let oa = self.ordering.iter().position(|x| x == a).unwrap();
         let ob = self.ordering.iter().position(|x| x == b).unwrap();
@@ -653,7 +923,7 @@ impl ShowdownEngine {
     pub fn compare_potential_hands(&self, a: &PotentialHand, b: &PotentialHand) -> Ordering {
         match a {
             PotentialHand::Hand(hand) => match b {
-                PotentialHand::Hand(best_hand) => self.compare_hands(&hand, &best_hand),
+                PotentialHand::Hand(best_hand) => self.compare_hands(hand, best_hand),
                 _ => Ordering::Greater,
             },
             PotentialHand::RoyalFlushDraw(draw, typ) => match b {
@@ -698,20 +968,70 @@ impl ShowdownEngine {
         }
     }
 
+    /// Ranks backdoor draws: flushes above straights, open ended straights above inside ones, then the highest card
+    pub fn compare_backdoor_draws(&self, a: &BackdoorDraw, b: &BackdoorDraw) -> Ordering {
+        match (a, b) {
+            (BackdoorDraw::Flush(draw), BackdoorDraw::Flush(best_draw)) =>
+                self.value_order(&self.highest_card_value(draw), &self.highest_card_value(best_draw)),
+            (BackdoorDraw::Flush(_), BackdoorDraw::Straight(..)) => Ordering::Greater,
+            (BackdoorDraw::Straight(..), BackdoorDraw::Flush(_)) => Ordering::Less,
+            // OpenEnded sorts before Inside, so this is flipped to rank the open ended ones higher
+            (BackdoorDraw::Straight(draw, typ), BackdoorDraw::Straight(best_draw, best_typ)) => match best_typ.cmp(typ) {
+                Ordering::Equal => self.value_order(&self.highest_card_value(draw), &self.highest_card_value(best_draw)),
+                other => other,
+            },
+        }
+    }
+
+    /// Where each card sits in the ordering, in the order ties are broken in: values that show up more often
+    /// come first (the trips of a full house before its pair), and higher values before lower ones
+    fn tiebreak(&self, cards: &HashSet<Card>) -> Vec<usize> {
+        let positions: Vec<usize> = cards.iter().map(|card| self.ordering.iter().position(|x| *x == card.value()).unwrap()).collect();
+        let mut keyed: Vec<(usize, usize)> = positions.iter().map(|&p| (positions.iter().filter(|&&x| x == p).count(), p)).collect();
+        keyed.sort_unstable_by(|a, b| b.cmp(a));
+        keyed.into_iter().map(|(_, p)| p).collect()
+    }
+
+    /// Where the top card of the highest straight the cards make sits in `straight_order`.
+    /// A wheel goes by the card just below the wheel value, so it is the lowest straight there is.
+    fn straight_high(&self, cards: &HashSet<Card>) -> Option<usize> {
+        let order = self.straight_order();
+        (4..order.len()).rev().find(|&top| order[top - 4..=top].iter().all(|value| cards.iter().any(|card| card.value() == *value)))
+    }
+
+    /// Whether a straight flush is a royal one: the highest straight there is, and so never the wheel
+    fn is_royal(&self, cards: &HashSet<Card>) -> bool {
+        self.straight_high(cards) == Some(self.straight_order().len() - 1)
+    }
+
+    /// Whether a straight flush draw only needs the rest of the top five values in straight order
+    fn draws_to_royal(&self, cards: &HashSet<Card>) -> bool {
+        let order = self.straight_order();
+        let top = &order[order.len() - 5..];
+        cards.iter().all(|card| top.contains(&card.value()))
+    }
+
+    /// Compares two hands of the same five cards or fewer. Cards that are not part of either hand (kickers) are left
+    /// out, so compare those separately when the hands are equal.
     pub fn compare_hands(&self, a: &Hand, b: &Hand) -> Ordering {
-        let resolve_conflict = |a: &HashSet<Card>, b: &HashSet<Card>| {
-            let ahc = self.highest_card_value(a.iter());
-            let bhc = self.highest_card_value(b.iter());
-            self.value_order(&ahc, &bhc)
-        };
+        // A flush is harder to make than a full house with fewer cards of each suit
+        if self.variant == Variant::ShortDeck {
+            match (a, b) {
+                (Hand::Flush(..), Hand::FullHouse(..)) => return Ordering::Greater,
+                (Hand::FullHouse(..), Hand::Flush(..)) => return Ordering::Less,
+                _ => {},
+            }
+        }
+        let resolve_conflict = |a: &HashSet<Card>, b: &HashSet<Card>| self.tiebreak(a).cmp(&self.tiebreak(b));
+        let resolve_straights = |a: &HashSet<Card>, b: &HashSet<Card>| self.straight_high(a).cmp(&self.straight_high(b));
         match a {
             Hand::RoyalFlush(ref a) => match b {
-                Hand::RoyalFlush(ref b) => resolve_conflict(a, b),
+                Hand::RoyalFlush(ref b) => resolve_straights(a, b),
                 _ => Ordering::Greater
             },
             Hand::StraightFlush(ref a) => match b {
                 Hand::RoyalFlush(..) => Ordering::Less,
-                Hand::StraightFlush(ref b) => resolve_conflict(a, b),
+                Hand::StraightFlush(ref b) => resolve_straights(a, b),
                 _ => Ordering::Greater,
             },
             Hand::FourOfAKind(ref a) => match b {
@@ -732,7 +1052,7 @@ impl ShowdownEngine {
             Hand::Straight(ref a) => match b {
                 Hand::RoyalFlush(..) | Hand::StraightFlush(..) | Hand::FourOfAKind(..) | Hand::FullHouse(..) | Hand::Flush(..)
                     => Ordering::Less,
-                Hand::Straight(ref b) => resolve_conflict(a, b),
+                Hand::Straight(ref b) => resolve_straights(a, b),
                 _ => Ordering::Greater
             },
             Hand::ThreeOfAKind(ref a) =>  match b {
@@ -755,3 +1075,142 @@ impl ShowdownEngine {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn engine() -> ShowdownEngine {
+        ShowdownEngine::new(CardValue::ALL)
+    }
+
+    fn made(engine: &ShowdownEngine, s: &str) -> Hand {
+        engine.process_hand(&cards(s)).showdown().expect("Expected a made hand")
+    }
+
+    fn compare(engine: &ShowdownEngine, a: &str, b: &str) -> Ordering {
+        engine.compare_hands(&made(engine, a), &made(engine, b))
+    }
+
+    #[test]
+    fn full_houses_rank_by_their_trips() {
+        let engine = engine();
+        assert_eq!(compare(&engine, "Qs Qh Qd As Ah", "Ks Kh Kd 2s 2h"), Ordering::Less);
+        assert_eq!(compare(&engine, "Qs Qh Qd As Ah", "Qc Qh Qd 2s 2h"), Ordering::Greater);
+    }
+
+    #[test]
+    fn two_pair_ranks_by_the_top_pair() {
+        let engine = engine();
+        assert_eq!(compare(&engine, "As Ah 2d 2s 7c", "Ks Kh Qd Qs 7c"), Ordering::Greater);
+        assert_eq!(compare(&engine, "As Ah 3d 3s 7c", "Ac Ad 2d 2s 7c"), Ordering::Greater);
+    }
+
+    #[test]
+    fn flushes_compare_every_card() {
+        let engine = engine();
+        assert_eq!(compare(&engine, "Ah Kh 9h 5h 3h", "As Ks 9s 5s 2s"), Ordering::Greater);
+        // Out of six hearts, the top five play
+        assert_eq!(compare(&engine, "2h Ah Kh 9h 5h 3h", "As Ks 9s 5s 3s"), Ordering::Equal);
+    }
+
+    fn draws(engine: &ShowdownEngine, s: &str, to_come: usize) -> (bool, bool) {
+        let hands = engine.potential_hands_with_to_come(&cards(s), true, to_come);
+        let flush_draw = hands.iter().any(|hand| matches!(hand, PotentialHand::FlushDraw(_)));
+        (flush_draw, !engine.backdoor_draws(&cards(s), true, to_come).is_empty())
+    }
+
+    #[test]
+    fn draws_on_the_flop() {
+        let engine = engine();
+        assert_eq!(draws(&engine, "Ah 9h Qh 7h 2c", 2), (true, false));
+        assert_eq!(draws(&engine, "Ah Kd Qh 7h 2c", 2), (false, true));
+        let backdoors = engine.backdoor_draws(&cards("Ah Kd Qh 7h 2c"), true, 2);
+        assert!(matches!(backdoors[0], BackdoorDraw::Flush(_)), "{}", backdoors[0]);
+        assert!(backdoors[1..].iter().all(|draw| matches!(draw, BackdoorDraw::Straight(..))));
+    }
+
+    #[test]
+    fn draws_on_the_turn() {
+        let engine = engine();
+        assert_eq!(draws(&engine, "Ah Kh Qh 7h 2c 3d", 1), (true, false));
+        assert_eq!(draws(&engine, "Ah Kd Qh 7h 2c 3d", 1), (false, false));
+        // A board alone has as many cards as a flop and a hand, but only one to come
+        assert_eq!(draws(&engine, "Qh 7h 2c 9h", 1), (false, false));
+    }
+
+    #[test]
+    fn no_draws_on_the_river() {
+        let engine = engine();
+        assert_eq!(draws(&engine, "Ah Kh Qh 7h 2c 3d 8s", 0), (false, false));
+        assert_eq!(engine.process_hand_with_to_come(&cards("Ah Kh Qh 7h 2c 3d 8s"), 0), PotentialHand::HighCard(cards("Ah")[0]));
+        // Without the cards to come, the draw is still reported
+        assert!(matches!(engine.process_hand(&cards("Ah Kh Qh 7h 2c 3d 8s")), PotentialHand::FlushDraw(_)));
+    }
+
+    #[test]
+    fn the_wheel_is_the_lowest_straight() {
+        for &wheel in WheelRule::ALL.iter().take(2) {
+            let engine = engine().with_wheel(wheel);
+            assert_eq!(compare(&engine, "Ah 2c 3d 4s 5h", "2h 3c 4d 5s 6h"), Ordering::Less);
+            assert_eq!(compare(&engine, "Ah 2c 3d 4s 5h", "Kh Kc 4d 4s 6h"), Ordering::Greater);
+        }
+        let engine = engine().with_wheel(WheelRule::NoWrap);
+        assert_eq!(engine.process_hand_with_to_come(&cards("Ah 2c 3d 4s 5h"), 0), PotentialHand::HighCard(cards("Ah")[0]));
+    }
+
+    #[test]
+    fn the_short_deck_wheel_is_the_lowest_straight() {
+        let engine = engine().with_variant(Variant::ShortDeck);
+        assert_eq!(compare(&engine, "Ah 6c 7d 8s 9h", "6h 7c 8d 9s Th"), Ordering::Less);
+        assert_eq!(compare(&engine, "Ah 6c 7d 8s 9h", "9h Tc Jd Qs Kh"), Ordering::Less);
+    }
+
+    #[test]
+    fn only_the_top_straight_flush_is_royal() {
+        let engine = engine();
+        assert!(matches!(made(&engine, "Th Jh Qh Kh Ah"), Hand::RoyalFlush(_)));
+        assert!(matches!(made(&engine, "Ah 2h 3h 4h 5h"), Hand::StraightFlush(_)));
+        // The other heart doesn't hide the straight flush behind a flush
+        assert!(matches!(made(&engine, "Ah 2h 3h 4h 5h Kh 9c"), Hand::StraightFlush(_)));
+        assert_eq!(compare(&engine, "Ah 2h 3h 4h 5h", "2s 3s 4s 5s 6s"), Ordering::Less);
+        let short = engine.with_variant(Variant::ShortDeck);
+        assert!(matches!(made(&short, "Ah 6h 7h 8h 9h"), Hand::StraightFlush(_)));
+        assert!(matches!(made(&short, "Th Jh Qh Kh Ah"), Hand::RoyalFlush(_)));
+    }
+
+    #[test]
+    fn wheel_draws_are_not_royal_draws() {
+        let engine = engine();
+        let draw = engine.process_hand(&cards("Ah 2h 3h 4h 9c"));
+        assert!(matches!(draw, PotentialHand::StraightFlushDraw(..)), "{}", draw);
+        let draw = engine.process_hand(&cards("Ah Kh Qh Jh 2c"));
+        assert!(matches!(draw, PotentialHand::RoyalFlushDraw(..)), "{}", draw);
+    }
+
+    #[test]
+    fn omaha_draws_stop_on_the_river() {
+        let engine = engine().with_hole_cards(4, HoleRule::TwoPlusThree);
+        let hole = cards("Ah Kh 9c 8d");
+        let turn = engine.process_holding(&hole, &cards("Qh 7h 2c 3s"));
+        assert!(matches!(turn, PotentialHand::FlushDraw(_)), "{}", turn);
+        let river = engine.process_holding(&hole, &cards("Qh 7h 2c 3s 5d"));
+        assert!(matches!(river, PotentialHand::HighCard(_)), "{}", river);
+    }
+
+    #[test]
+    #[should_panic]
+    fn too_many_hole_cards() {
+        engine().with_hole_cards(MAX_HOLE_CARDS + 1, HoleRule::AnyFive);
+    }
+
+    #[test]
+    #[should_panic]
+    fn omaha_needs_two_hole_cards() {
+        engine().with_hole_cards(1, HoleRule::TwoPlusThree);
+    }
+}
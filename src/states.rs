@@ -1,6 +1,6 @@
 use super::{
     actions::{ActionType, Action},
-    cards::{CardDeck, CardHand}
+    cards::{CardDeck, CardHand, HoleCards}
 };
 use std::cmp::{min, max};
 
@@ -18,7 +18,7 @@ pub struct GameState {
 }
 
 /// Final state of a poker round corresponding to payoffs
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct TerminalState {
     pub deltas: [i32; 2],
     pub previous: RoundState,
@@ -31,7 +31,8 @@ pub struct RoundState {
     pub street: u32,
     pub pips: [u32; 2],
     pub stacks: [u32; 2],
-    pub hands: [Option<CardHand>; 2],
+    /// Each player's hole cards, when known. These used to be `CardHand`s; see `hand_pair` for the two card view.
+    pub hands: [Option<HoleCards>; 2],
     pub deck: CardDeck,
     pub previous: Option<Box<RoundState>>,
 }
@@ -44,6 +45,12 @@ pub enum StateResult {
 }
 
 impl RoundState {
+    /// A player's hand as a `CardHand`, like `hands` held before hands could have more than two cards.
+    /// `None` if the hand isn't known, or doesn't have exactly two cards.
+    pub fn hand_pair(&self, player: usize) -> Option<CardHand> {
+        self.hands[player].and_then(|hand| hand.pair())
+    }
+
     /// Compares the players' hands and computes payoffs.
     pub fn showdown(&self) -> TerminalState {
         TerminalState { previous: self.clone(), deltas: [0, 0] }
@@ -63,7 +70,7 @@ impl RoundState {
         // similarly, re-raising is only allowed if both players can afford it
         let raises_forbidden = (continue_cost == self.stacks[active]) || (self.stacks[1 - active] == 0);
         if raises_forbidden { return ActionType::FOLD | ActionType::CALL; }
-        return ActionType::FOLD | ActionType::CALL | ActionType::RAISE;
+        ActionType::FOLD | ActionType::CALL | ActionType::RAISE
     }
 
     /// Returns an array of the minimum and maximum legal raises
@@ -99,12 +106,11 @@ impl RoundState {
         let active: usize = self.button as usize % 2;
         match action {
             Action::Fold => {
-                let delta;
-                if active == 0 {
-                    delta = self.stacks[0].saturating_sub(STARTING_STACK)
+                let delta = if active == 0 {
+                    self.stacks[0].saturating_sub(STARTING_STACK)
                 } else {
-                    delta = STARTING_STACK - self.stacks[1]
-                }
+                    STARTING_STACK - self.stacks[1]
+                };
                 StateResult::Terminal(TerminalState{
                     deltas: [delta as i32, -(delta as i32)],
                     previous: self.clone()
@@ -173,3 +179,33 @@ impl RoundState {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::Card;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn two_card_hands_read_as_pairs() {
+        let hold_em = HoleCards::new(&cards("Ah Kd")).unwrap();
+        let omaha = HoleCards::new(&cards("Ah Kd 9c 8s")).unwrap();
+        let rs = RoundState {
+            button: 0,
+            street: 0,
+            pips: [SMALL_BLIND, BIG_BLIND],
+            stacks: [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [Some(hold_em), Some(omaha)],
+            deck: CardDeck(vec![]),
+            previous: None,
+        };
+        let expected = cards("Ah Kd");
+        assert_eq!(rs.hand_pair(0), Some(CardHand([expected[0], expected[1]])));
+        assert_eq!(rs.hand_pair(1), None);
+        let rs = RoundState { hands: [None, None], ..rs };
+        assert_eq!(rs.hand_pair(0), None);
+    }
+}
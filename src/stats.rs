@@ -0,0 +1,516 @@
+//! Running statistics on the opponent, built from finished rounds
+//!
+//! Feed every `TerminalState` from `handle_round_over` into `OpponentStats::record`.
+//! The counts are split by the opponent's seat (the button acts first preflop and last after it),
+//! and can be saved as plain text between matches:
+//!
+//! ```text
+//! button vpip 31 50
+//! big_blind street.flop 4 9 12 3
+//! ```
+//!
+//! Frequencies are `<hits> <chances>`, street lines are `<raises> <calls> <checks> <folds>`.
+
+use crate::actions::Action;
+use crate::history::{RoundHistory, Street};
+use crate::states::TerminalState;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, prelude::*, BufReader, BufWriter};
+use std::ops::AddAssign;
+use std::path::Path;
+use std::str::FromStr;
+use std::error::Error;
+
+/// z for a 95% confidence interval
+pub const Z_95: f64 = 1.96;
+
+/// How often something happened, out of the times it could have
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Frequency {
+    pub hits: u32,
+    pub chances: u32,
+}
+
+impl Frequency {
+    pub fn add(&mut self, hit: bool) {
+        self.chances += 1;
+        if hit {
+            self.hits += 1;
+        }
+    }
+
+    /// The observed rate, if there was a chance at all
+    pub fn rate(&self) -> Option<f64> {
+        if self.chances == 0 {
+            None
+        } else {
+            Some(self.hits as f64 / self.chances as f64)
+        }
+    }
+
+    /// Wilson score interval for the true rate, which behaves even with few samples.
+    /// With no samples, anything goes.
+    pub fn interval(&self, z: f64) -> (f64, f64) {
+        if self.chances == 0 {
+            return (0.0, 1.0);
+        }
+        let n = self.chances as f64;
+        let p = self.hits as f64 / n;
+        let z2 = z * z;
+        let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+        let spread = z * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt() / (1.0 + z2 / n);
+        ((center - spread).max(0.0), (center + spread).min(1.0))
+    }
+}
+
+impl AddAssign for Frequency {
+    fn add_assign(&mut self, other: Frequency) {
+        self.hits += other.hits;
+        self.chances += other.chances;
+    }
+}
+
+impl fmt::Display for Frequency {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.rate() {
+            Some(rate) => {
+                let (low, high) = self.interval(Z_95);
+                write!(fmt, "{:.1}% [{:.1}, {:.1}] of {}", rate * 100.0, low * 100.0, high * 100.0, self.chances)
+            },
+            None => write!(fmt, "n/a"),
+        }
+    }
+}
+
+/// What the opponent did on one street
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct StreetStats {
+    /// Bets and raises
+    pub raises: u32,
+    pub calls: u32,
+    pub checks: u32,
+    pub folds: u32,
+}
+
+impl StreetStats {
+    fn add(&mut self, action: Action) {
+        match action {
+            Action::Raise(_) => self.raises += 1,
+            Action::Call => self.calls += 1,
+            Action::Check => self.checks += 1,
+            Action::Fold => self.folds += 1,
+        }
+    }
+
+    /// Bets and raises per call. Infinite if they never call but do bet, and unknown if they do neither.
+    pub fn aggression_factor(&self) -> Option<f64> {
+        match (self.raises, self.calls) {
+            (0, 0) => None,
+            (raises, calls) => Some(raises as f64 / calls as f64),
+        }
+    }
+
+    /// Share of their actions that were bets or raises, which (unlike the factor) has an interval
+    pub fn aggression(&self) -> Frequency {
+        Frequency {
+            hits: self.raises,
+            chances: self.raises + self.calls + self.checks + self.folds,
+        }
+    }
+}
+
+impl AddAssign for StreetStats {
+    fn add_assign(&mut self, other: StreetStats) {
+        self.raises += other.raises;
+        self.calls += other.calls;
+        self.checks += other.checks;
+        self.folds += other.folds;
+    }
+}
+
+/// The opponent's seat for a round
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Position {
+    /// Seat 0, which posts the small blind, acts first preflop and last after that
+    Button,
+    /// Seat 1, which posts the big blind
+    BigBlind,
+}
+
+impl Position {
+    pub const ALL: [Position; 2] = [Position::Button, Position::BigBlind];
+
+    pub fn of(seat: usize) -> Position {
+        if seat == 0 { Position::Button } else { Position::BigBlind }
+    }
+
+    fn key(self) -> &'static str {
+        match self {
+            Position::Button => "button",
+            Position::BigBlind => "big_blind",
+        }
+    }
+}
+
+/// The opponent's numbers from one seat
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PositionStats {
+    pub rounds: u32,
+    /// Voluntarily put chips in preflop (checking the big blind doesn't count)
+    pub vpip: Frequency,
+    /// Raised preflop
+    pub pfr: Frequency,
+    /// Bet the flop after raising last preflop, when it was checked to them (or they were first)
+    pub cbet: Frequency,
+    /// Folded to our flop bet after we raised last preflop
+    pub fold_to_cbet: Frequency,
+    /// Went to showdown, out of the rounds where they saw the flop
+    pub went_to_showdown: Frequency,
+    /// Won the pot, out of the showdowns they went to
+    pub won_at_showdown: Frequency,
+    pub streets: [StreetStats; 4],
+}
+
+impl PositionStats {
+    pub fn street(&self, street: Street) -> &StreetStats {
+        &self.streets[street as usize]
+    }
+
+    /// Aggression factor over all streets after the flop
+    pub fn postflop_aggression_factor(&self) -> Option<f64> {
+        let mut postflop = StreetStats::default();
+        for stats in &self.streets[1..] {
+            postflop += *stats;
+        }
+        postflop.aggression_factor()
+    }
+
+    fn record(&mut self, history: &RoundHistory, opponent: usize) {
+        let us = 1 - opponent;
+        self.rounds += 1;
+
+        let preflop: Vec<Action> = history.on(Street::Preflop).filter(|played| played.player == opponent).map(|played| played.action).collect();
+        self.vpip.add(preflop.iter().any(|action| matches!(action, Action::Call | Action::Raise(_))));
+        self.pfr.add(preflop.iter().any(Action::is_raise));
+
+        for played in history.by(opponent) {
+            self.streets[played.street as usize].add(played.action);
+        }
+
+        // The first bet on the flop, if the preflop aggressor gets to make it
+        let aggressor = history.preflop_aggressor();
+        let flop: Vec<_> = history.on(Street::Flop).collect();
+        let first_bet = flop.iter().position(|played| played.action.is_raise());
+        if aggressor == Some(opponent) {
+            // They had the chance if they acted on the flop before anyone bet
+            if let Some(first) = flop.iter().position(|played| played.player == opponent) {
+                if first_bet.map_or(true, |bet| bet >= first) {
+                    self.cbet.add(first_bet == Some(first));
+                }
+            }
+        } else if aggressor == Some(us) {
+            if let Some(bet) = first_bet.filter(|&bet| flop[bet].player == us) {
+                if let Some(response) = flop[bet + 1..].iter().find(|played| played.player == opponent) {
+                    self.fold_to_cbet.add(response.action == Action::Fold);
+                }
+            }
+        }
+
+        if history.street >= Street::Flop {
+            self.went_to_showdown.add(history.showdown);
+        }
+        if history.showdown {
+            self.won_at_showdown.add(history.deltas[opponent] > 0);
+        }
+    }
+
+    fn frequencies(&mut self) -> [(&'static str, &mut Frequency); 6] {
+        [
+            ("vpip", &mut self.vpip),
+            ("pfr", &mut self.pfr),
+            ("cbet", &mut self.cbet),
+            ("fold_to_cbet", &mut self.fold_to_cbet),
+            ("went_to_showdown", &mut self.went_to_showdown),
+            ("won_at_showdown", &mut self.won_at_showdown),
+        ]
+    }
+}
+
+impl AddAssign for PositionStats {
+    fn add_assign(&mut self, mut other: PositionStats) {
+        self.rounds += other.rounds;
+        for ((_, ours), (_, theirs)) in self.frequencies().iter_mut().zip(other.frequencies().iter()) {
+            **ours += **theirs;
+        }
+        for (ours, theirs) in self.streets.iter_mut().zip(other.streets.iter()) {
+            *ours += *theirs;
+        }
+    }
+}
+
+/// Running counts of what the opponent does
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct OpponentStats {
+    positions: [PositionStats; 2],
+}
+
+impl OpponentStats {
+    pub fn new() -> OpponentStats {
+        OpponentStats::default()
+    }
+
+    /// Adds a finished round, seen from the given seat, and hands back its history
+    pub fn record(&mut self, ts: &TerminalState, player_index: usize) -> RoundHistory {
+        let history = RoundHistory::from_terminal(ts);
+        self.record_history(&history, player_index);
+        history
+    }
+
+    /// Adds a finished round that was already reconstructed
+    pub fn record_history(&mut self, history: &RoundHistory, player_index: usize) {
+        let opponent = 1 - player_index;
+        self.positions[opponent].record(history, opponent);
+    }
+
+    pub fn position(&self, position: Position) -> &PositionStats {
+        &self.positions[position as usize]
+    }
+
+    /// Both seats added up
+    pub fn overall(&self) -> PositionStats {
+        let mut overall = self.positions[0];
+        overall += self.positions[1];
+        overall
+    }
+
+    pub fn rounds(&self) -> u32 {
+        self.positions[0].rounds + self.positions[1].rounds
+    }
+
+    /// Adds up stats from another match, e.g. ones loaded from a file
+    pub fn merge(&mut self, other: &OpponentStats) {
+        for (ours, theirs) in self.positions.iter_mut().zip(other.positions.iter()) {
+            *ours += *theirs;
+        }
+    }
+
+    /// Saves the stats to a file, replacing what was there
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> io::Result<()> {
+        let mut writer = BufWriter::new(File::create(path)?);
+        write!(writer, "{}", self)?;
+        writer.flush()
+    }
+
+    /// Loads stats saved with `save`
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<OpponentStats, StatsError> {
+        OpponentStats::read(BufReader::new(File::open(path)?))
+    }
+
+    /// Reads stats from any buffered reader. Blank lines are skipped.
+    pub fn read<R: BufRead>(reader: R) -> Result<OpponentStats, StatsError> {
+        let mut stats = OpponentStats::default();
+        for line in reader.lines() {
+            let line = line?;
+            if !line.trim().is_empty() {
+                stats.read_line(&line)?;
+            }
+        }
+        Ok(stats)
+    }
+
+    fn read_line(&mut self, line: &str) -> Result<(), StatsError> {
+        let malformed = || StatsError::Malformed(line.to_string());
+        let mut parts = line.split_whitespace();
+        let position = match parts.next() {
+            Some(key) => Position::ALL.iter().copied().find(|position| position.key() == key).ok_or_else(malformed)?,
+            None => return Err(malformed()),
+        };
+        let stat = parts.next().ok_or_else(malformed)?;
+        let numbers = parts.map(|x| x.parse::<u32>().map_err(|_| malformed())).collect::<Result<Vec<_>, _>>()?;
+        let stats = &mut self.positions[position as usize];
+
+        if let Some(street) = stat.strip_prefix("street.") {
+            let street = Street::ALL.iter().copied().find(|x| x.to_string() == street).ok_or_else(malformed)?;
+            match numbers.as_slice() {
+                &[raises, calls, checks, folds] => stats.streets[street as usize] = StreetStats { raises, calls, checks, folds },
+                _ => return Err(malformed()),
+            }
+        } else if stat == "rounds" {
+            match numbers.as_slice() {
+                &[rounds] => stats.rounds = rounds,
+                _ => return Err(malformed()),
+            }
+        } else {
+            let mut frequencies = stats.frequencies();
+            let (_, frequency) = frequencies.iter_mut().find(|(key, _)| *key == stat).ok_or_else(malformed)?;
+            match numbers.as_slice() {
+                &[hits, chances] if hits <= chances => **frequency = Frequency { hits, chances },
+                _ => return Err(malformed()),
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for OpponentStats {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        for position in Position::ALL.iter() {
+            let mut stats = self.positions[*position as usize];
+            let key = position.key();
+            writeln!(fmt, "{} rounds {}", key, stats.rounds)?;
+            for (stat, frequency) in stats.frequencies().iter() {
+                writeln!(fmt, "{} {} {} {}", key, stat, frequency.hits, frequency.chances)?;
+            }
+            for street in Street::ALL.iter() {
+                let s = stats.street(*street);
+                writeln!(fmt, "{} street.{} {} {} {} {}", key, street, s.raises, s.calls, s.checks, s.folds)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for OpponentStats {
+    type Err = StatsError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        OpponentStats::read(s.as_bytes())
+    }
+}
+
+/// Describes errors that can occur when loading stats
+#[derive(Debug)]
+pub enum StatsError {
+    Io(io::Error),
+    Malformed(String),
+}
+
+impl From<io::Error> for StatsError {
+    fn from(e: io::Error) -> StatsError {
+        StatsError::Io(e)
+    }
+}
+
+impl Error for StatsError {}
+
+impl fmt::Display for StatsError {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StatsError::Io(e) => write!(fmt, "Stats I/O error: {}", e),
+            StatsError::Malformed(s) => write!(fmt, "Malformed stats line: {}", s),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::{Card, CardDeck};
+    use crate::states::{RoundState, StateResult, BIG_BLIND, SMALL_BLIND, STARTING_STACK};
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn round(result: StateResult) -> RoundState {
+        match result {
+            StateResult::Round(rs) => rs,
+            StateResult::Terminal(_) => panic!("round ended early"),
+        }
+    }
+
+    /// The button raises, the big blind calls, checks the flop and folds to the continuation bet
+    fn folded_to_cbet() -> TerminalState {
+        let rs = RoundState {
+            button: 0,
+            street: 0,
+            pips: [SMALL_BLIND, BIG_BLIND],
+            stacks: [STARTING_STACK - SMALL_BLIND, STARTING_STACK - BIG_BLIND],
+            hands: [None, None],
+            deck: CardDeck(vec![]),
+            previous: None,
+        };
+        let rs = round(rs.proceed(Action::Raise(6)));
+        let mut rs = round(rs.proceed(Action::Call));
+        // Dealing the flop, the way the runner does
+        rs.deck = CardDeck(cards("Ah 7c 2d"));
+        rs.street = 3;
+        let rs = round(rs.proceed(Action::Check));
+        let rs = round(rs.proceed(Action::Raise(4)));
+        match rs.proceed(Action::Fold) {
+            StateResult::Terminal(ts) => ts,
+            StateResult::Round(_) => panic!("fold did not end the round"),
+        }
+    }
+
+    #[test]
+    fn frequencies_and_intervals() {
+        let mut frequency = Frequency::default();
+        assert_eq!(frequency.rate(), None);
+        assert_eq!(frequency.interval(Z_95), (0.0, 1.0));
+        for hit in [true, false, false, true] {
+            frequency.add(hit);
+        }
+        assert_eq!(frequency, Frequency { hits: 2, chances: 4 });
+        assert_eq!(frequency.rate(), Some(0.5));
+        let (low, high) = frequency.interval(Z_95);
+        assert!(low < 0.5 && high > 0.5 && (0.5 - low - (high - 0.5)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn records_the_aggressor() {
+        let ts = folded_to_cbet();
+        let mut stats = OpponentStats::new();
+        // We are the big blind, so the opponent is on the button
+        stats.record(&ts, 1);
+        let button = stats.position(Position::Button);
+        assert_eq!(button.rounds, 1);
+        assert_eq!(button.vpip, Frequency { hits: 1, chances: 1 });
+        assert_eq!(button.pfr, Frequency { hits: 1, chances: 1 });
+        assert_eq!(button.cbet, Frequency { hits: 1, chances: 1 });
+        assert_eq!(button.went_to_showdown, Frequency { hits: 0, chances: 1 });
+        assert_eq!(button.street(Street::Flop).raises, 1);
+        assert_eq!(stats.position(Position::BigBlind).rounds, 0);
+    }
+
+    #[test]
+    fn records_the_caller() {
+        let ts = folded_to_cbet();
+        let mut stats = OpponentStats::new();
+        stats.record(&ts, 0);
+        let big_blind = stats.position(Position::BigBlind);
+        assert_eq!(big_blind.vpip, Frequency { hits: 1, chances: 1 });
+        assert_eq!(big_blind.pfr, Frequency { hits: 0, chances: 1 });
+        assert_eq!(big_blind.fold_to_cbet, Frequency { hits: 1, chances: 1 });
+        assert_eq!(*big_blind.street(Street::Flop), StreetStats { raises: 0, calls: 0, checks: 1, folds: 1 });
+        assert_eq!(big_blind.postflop_aggression_factor(), None);
+    }
+
+    #[test]
+    fn saved_stats_read_back() {
+        let ts = folded_to_cbet();
+        let mut stats = OpponentStats::new();
+        stats.record(&ts, 0);
+        stats.record(&ts, 1);
+        assert_eq!(stats.rounds(), 2);
+        assert_eq!(stats.to_string().parse::<OpponentStats>().unwrap(), stats);
+
+        let path = std::env::temp_dir().join(format!("pbrust_stats_{}", std::process::id()));
+        stats.save(&path).unwrap();
+        assert_eq!(OpponentStats::load(&path).unwrap(), stats);
+        let _ = std::fs::remove_file(&path);
+
+        let mut merged = stats.clone();
+        merged.merge(&stats);
+        assert_eq!(merged.rounds(), 4);
+        assert_eq!(merged.overall().vpip, Frequency { hits: 4, chances: 4 });
+    }
+
+    #[test]
+    fn malformed_lines_are_rejected() {
+        assert_eq!("\nbutton vpip 3 5\n\n".parse::<OpponentStats>().unwrap().position(Position::Button).vpip, Frequency { hits: 3, chances: 5 });
+        for line in ["dealer vpip 3 5", "button vpip 6 5", "button vpip 3", "button street.showdown 1 2 3 4", "button rounds x", "button"] {
+            assert!(matches!(line.parse::<OpponentStats>(), Err(StatsError::Malformed(_))), "{}", line);
+        }
+    }
+}
@@ -0,0 +1,210 @@
+//! Hand strength and potential, after Billings et al.
+//!
+//! Hand strength (HS) is how often we are ahead of the opponent's range right now. Positive potential (PPot)
+//! is how often we end up ahead when we are behind now, and negative potential (NPot) the other way around.
+//! Effective hand strength (EHS) puts them together: `HS * (1 - NPot) + (1 - HS) * PPot`.
+//! Ties count as half everywhere. Potential looks all the way to the river.
+
+use crate::cards::{Card, CardDeck};
+use crate::equity::MadeHand;
+use crate::range::{self, Range};
+use crate::rng::SeededRng;
+use crate::showdown::ShowdownEngine;
+use itertools::Itertools;
+use std::cmp::Ordering;
+use std::fmt;
+
+/// How to go through the opponent's hands and the runouts
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Method {
+    /// Every opponent hand against every runout. Fine on the turn and river, takes ten seconds or so on the flop,
+    /// and is out of the question before the flop.
+    Exact,
+    /// The given number of opponent hands (drawn by weight) with a random runout each
+    Sampled(u32),
+}
+
+const AHEAD: usize = 0;
+const TIED: usize = 1;
+const BEHIND: usize = 2;
+
+fn slot(result: Ordering) -> usize {
+    match result {
+        Ordering::Greater => AHEAD,
+        Ordering::Equal => TIED,
+        Ordering::Less => BEHIND,
+    }
+}
+
+/// Weighted counts of where we stand now against where we end up, from which all the metrics are read
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Potential {
+    /// Indexed by where we stand now and then at the river: ahead, tied, then behind
+    pub table: [[f64; 3]; 3],
+    /// Where we stand now, for every opponent hand and runout counted
+    pub totals: [f64; 3],
+}
+
+impl Potential {
+    /// Counts an opponent hand with the given weight, against one runout
+    pub fn add(&mut self, now: Ordering, river: Ordering, weight: f64) {
+        self.table[slot(now)][slot(river)] += weight;
+        self.totals[slot(now)] += weight;
+    }
+
+    /// HS: share of the opponent's range we beat right now
+    pub fn strength(&self) -> f64 {
+        let total: f64 = self.totals.iter().sum();
+        if total > 0.0 {
+            (self.totals[AHEAD] + self.totals[TIED] / 2.0) / total
+        } else {
+            0.0
+        }
+    }
+
+    /// PPot: chance of ending up ahead when we aren't ahead now
+    pub fn positive(&self) -> f64 {
+        let t = &self.table;
+        let chances = self.totals[BEHIND] + self.totals[TIED] / 2.0;
+        if chances > 0.0 {
+            (t[BEHIND][AHEAD] + t[BEHIND][TIED] / 2.0 + t[TIED][AHEAD] / 2.0) / chances
+        } else {
+            0.0
+        }
+    }
+
+    /// NPot: chance of ending up behind when we aren't behind now
+    pub fn negative(&self) -> f64 {
+        let t = &self.table;
+        let chances = self.totals[AHEAD] + self.totals[TIED] / 2.0;
+        if chances > 0.0 {
+            (t[AHEAD][BEHIND] + t[TIED][BEHIND] / 2.0 + t[AHEAD][TIED] / 2.0) / chances
+        } else {
+            0.0
+        }
+    }
+
+    /// EHS: strength now, allowing for the cards to come
+    pub fn effective(&self) -> f64 {
+        let strength = self.strength();
+        strength * (1.0 - self.negative()) + (1.0 - strength) * self.positive()
+    }
+}
+
+impl fmt::Display for Potential {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "HS {:.3} PPot {:.3} NPot {:.3} EHS {:.3}", self.strength(), self.positive(), self.negative(), self.effective())
+    }
+}
+
+/// HS of a hand on a board against the opponent's range (use `Range::full` for a random hand).
+/// This only looks at what is out now, so it is always exact.
+pub fn hand_strength(engine: &ShowdownEngine, hand: &[Card], board: &[Card], range: &Range) -> f64 {
+    let known: Vec<Card> = hand.iter().chain(board).copied().collect();
+    let dead: Vec<Card> = known.iter().copied().chain(engine.removed_cards()).collect();
+    let ours = MadeHand::of(engine, hand, board);
+    let mut totals = [0.0; 3];
+    for (theirs, weight) in range.iter() {
+        if range::blocked(&theirs, &dead) {
+            continue;
+        }
+        totals[slot(ours.compare(engine, &MadeHand::of(engine, &theirs.0, board)))] += weight;
+    }
+    Potential { table: Default::default(), totals }.strength()
+}
+
+/// Works out how a hand on a board does against the opponent's range (use `Range::full` for a random hand),
+/// now and once the board runs out, and so HS, PPot, NPot and EHS.
+/// Sampling needs an rng; going through everything doesn't touch it.
+pub fn potential(engine: &ShowdownEngine, hand: &[Card], board: &[Card], range: &Range, method: Method, rng: &mut SeededRng) -> Potential {
+    let known: Vec<Card> = hand.iter().chain(board).copied().collect();
+    // Hands with cards the variant doesn't play with can't be dealt, so they go with the ones we block
+    let dead: Vec<Card> = known.iter().copied().chain(engine.removed_cards()).collect();
+    let runout = 5 - board.len().min(5);
+    let made = |hole: &[Card], extra: &[Card]| -> MadeHand {
+        let board: Vec<Card> = board.iter().chain(extra).copied().collect();
+        MadeHand::of(engine, hole, &board)
+    };
+    let ours_now = made(hand, &[]);
+    let mut potential = Potential::default();
+
+    match method {
+        Method::Exact => {
+            let opponents: Vec<_> = range.iter()
+                .filter(|(theirs, _)| !range::blocked(theirs, &dead))
+                .map(|(theirs, weight)| (theirs, weight, ours_now.compare(engine, &made(&theirs.0, &[]))))
+                .collect();
+            let CardDeck(deck) = engine.deck_without(&known);
+            // Going runout by runout means our hand only gets worked out once for each
+            for extra in deck.iter().copied().combinations(runout) {
+                let ours = made(hand, &extra);
+                for &(theirs, weight, now) in opponents.iter() {
+                    if range::blocked(&theirs, &extra) {
+                        continue;
+                    }
+                    potential.add(now, ours.compare(engine, &made(&theirs.0, &extra)), weight);
+                }
+            }
+        },
+        Method::Sampled(samples) => {
+            let sampler = match range.sampler(&dead) {
+                Some(sampler) => sampler,
+                None => return potential,
+            };
+            for _ in 0..samples {
+                let theirs = sampler.sample(rng);
+                let dealt: Vec<Card> = known.iter().chain(&theirs.0).copied().collect();
+                let CardDeck(mut deck) = engine.deck_without(&dealt);
+                rng.partial_shuffle(&mut deck, runout);
+                let extra = &deck[..runout];
+                let now = ours_now.compare(engine, &made(&theirs.0, &[]));
+                potential.add(now, made(hand, extra).compare(engine, &made(&theirs.0, extra)), 1.0);
+            }
+        },
+    }
+    potential
+}
+
+/// EHS of a hand on a board against the opponent's range
+pub fn effective_strength(engine: &ShowdownEngine, hand: &[Card], board: &[Card], range: &Range, method: Method, rng: &mut SeededRng) -> f64 {
+    potential(engine, hand, board, range, method, rng).effective()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cards::CardValue;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    #[test]
+    fn ace_king_before_the_flop() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        // Of the 1225 hands left, 72 pairs are ahead and the 9 other ace kings tie
+        let strength = hand_strength(&engine, &cards("As Ks"), &[], &Range::full());
+        assert!((strength - (1144.0 + 9.0 / 2.0) / 1225.0).abs() < 1e-9, "{}", strength);
+    }
+
+    #[test]
+    fn kickers_count_on_the_flop() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let board = cards("Ad 7c 2h");
+        let king = hand_strength(&engine, &cards("As Kh"), &board, &Range::full());
+        let queen = hand_strength(&engine, &cards("As Qh"), &board, &Range::full());
+        assert!(king > queen, "{} vs {}", king, queen);
+    }
+
+    #[test]
+    fn sampled_potential_follows_the_exact_one() {
+        let engine = ShowdownEngine::new(CardValue::ALL);
+        let (hand, board) = (cards("Ah Kh"), cards("Qh 7h 2c 3d"));
+        let range: Range = "22+, A2s+, KTs+, QTs+, ATo+, KJo+".parse().unwrap();
+        let exact = potential(&engine, &hand, &board, &range, Method::Exact, &mut SeededRng::new(1));
+        let sampled = potential(&engine, &hand, &board, &range, Method::Sampled(4000), &mut SeededRng::new(1));
+        assert!((exact.effective() - sampled.effective()).abs() < 0.03, "{} vs {}", exact, sampled);
+        // With a flush draw and two overcards, the river helps more than it hurts
+        assert!(exact.positive() > exact.negative(), "{}", exact);
+    }
+}
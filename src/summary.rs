@@ -0,0 +1,76 @@
+//! What happened over a whole match
+
+use std::fmt;
+use std::time::Duration;
+
+/// Why the runner stopped playing
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum EndReason {
+    /// The engine sent `Q`
+    Quit,
+    /// The game clock ran out
+    GameClock,
+    /// The configured number of rounds were played
+    RoundCap,
+    /// A custom `GameEnd` predicate ended the match
+    Custom,
+    /// The runner's own wall clock limit was hit
+    TimeLimit,
+    /// An I/O fault cut the match short, and the error policy said to keep what we had
+    Error(String),
+}
+
+impl fmt::Display for EndReason {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EndReason::Quit => write!(fmt, "engine quit"),
+            EndReason::GameClock => write!(fmt, "game clock ran out"),
+            EndReason::RoundCap => write!(fmt, "round cap reached"),
+            EndReason::Custom => write!(fmt, "custom end condition"),
+            EndReason::TimeLimit => write!(fmt, "runner time limit reached"),
+            EndReason::Error(e) => write!(fmt, "error: {}", e),
+        }
+    }
+}
+
+/// Results of a match, as seen by the runner
+#[derive(Debug, Clone, PartialEq)]
+pub struct MatchSummary {
+    /// Our bankroll at the end of the match
+    pub bankroll: i64,
+    /// Our delta for every finished round, in order
+    pub deltas: Vec<i32>,
+    /// Rounds that went to showdown (including all in run outs)
+    pub showdowns: u32,
+    /// Rounds that ended with someone folding
+    pub folds: u32,
+    /// Game clock at the start of the match, in seconds, as the engine first reported it
+    pub clock_start: f32,
+    /// Game clock left at the end of the match, in seconds
+    pub clock_remaining: f32,
+    /// Wall clock time the runner was playing for
+    pub runtime: Duration,
+    /// Number of bot actions the sanitizer had to correct
+    pub sanitized: u32,
+    pub end_reason: EndReason,
+}
+
+impl MatchSummary {
+    /// Number of rounds that were played to the end
+    pub fn rounds_played(&self) -> usize {
+        self.deltas.len()
+    }
+
+    /// Game clock used over the match, in seconds
+    pub fn clock_used(&self) -> f32 {
+        (self.clock_start - self.clock_remaining).max(0.0)
+    }
+}
+
+impl fmt::Display for MatchSummary {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "bankroll {} over {} rounds ({} showdowns, {} folds), used {:.3}s of {:.3}s game clock in {:?}, {} sanitized actions, ended by {}",
+            self.bankroll, self.rounds_played(), self.showdowns, self.folds, self.clock_used(), self.clock_start,
+            self.runtime, self.sanitized, self.end_reason)
+    }
+}
@@ -0,0 +1,227 @@
+//! What the board looks like: pairs, suits and how connected it is
+//!
+//! Connectedness goes by the values in straight order, lowest first, the same way the `ShowdownEngine` sees
+//! straights (see `ShowdownEngine::straight_order`,
+//! and `ShowdownEngine::board_texture` to look at a board the way the engine does).
+
+use crate::cards::{Card, CardSuit, CardValue};
+use std::fmt;
+
+/// How many board cards share a value
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq, PartialOrd, Ord)]
+pub enum Pairing {
+    Unpaired,
+    Paired,
+    TwoPair,
+    Trips,
+    FullHouse,
+    Quads,
+}
+
+impl fmt::Display for Pairing {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", match self {
+            Pairing::Unpaired => "unpaired",
+            Pairing::Paired => "paired",
+            Pairing::TwoPair => "two pair",
+            Pairing::Trips => "trips",
+            Pairing::FullHouse => "full house",
+            Pairing::Quads => "quads",
+        })
+    }
+}
+
+/// How the board's suits are spread
+#[derive(Debug, Copy, Clone, Hash, Eq, PartialEq)]
+pub enum Suits {
+    /// No two cards share a suit
+    Rainbow,
+    /// Some cards share a suit, but not all of them
+    TwoTone,
+    /// Every card has the same suit
+    Monotone,
+}
+
+impl fmt::Display for Suits {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}", match self {
+            Suits::Rainbow => "rainbow",
+            Suits::TwoTone => "two tone",
+            Suits::Monotone => "monotone",
+        })
+    }
+}
+
+/// A straight that someone could have with this board
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+pub struct PossibleStraight {
+    /// The top value of the straight
+    pub high: CardValue,
+    /// Values the board is missing, that a player has to hold. Empty if the board is a straight itself.
+    pub needs: Vec<CardValue>,
+}
+
+/// Everything about the board that postflop play cares about
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BoardTexture {
+    pub pairing: Pairing,
+    pub suits: Suits,
+    /// Cards in the most common suit
+    pub suited: usize,
+    /// The suit someone could have a flush in, if there are at least three of it
+    pub flush_suit: Option<CardSuit>,
+    /// Most distinct board values that fit in one straight
+    pub connected: usize,
+    /// Every straight a player could make, highest first
+    pub straights: Vec<PossibleStraight>,
+}
+
+impl BoardTexture {
+    /// Looks at the board, with straights going by the given values, lowest first
+    pub fn new(board: &[Card], straight_order: &[CardValue]) -> BoardTexture {
+        let mut counts: Vec<usize> = CardValue::ALL.iter()
+            .map(|&value| board.iter().filter(|card| card.value() == value).count())
+            .filter(|&count| count > 0)
+            .collect();
+        counts.sort_unstable_by(|a, b| b.cmp(a));
+        let pairing = match (counts.first().copied().unwrap_or(0), counts.get(1).copied().unwrap_or(0)) {
+            (4, _) => Pairing::Quads,
+            (3, 2) => Pairing::FullHouse,
+            (3, _) => Pairing::Trips,
+            (2, 2) => Pairing::TwoPair,
+            (2, _) => Pairing::Paired,
+            _ => Pairing::Unpaired,
+        };
+
+        let (suit, suited) = CardSuit::ALL.iter()
+            .map(|&suit| (suit, board.iter().filter(|card| card.suit() == suit).count()))
+            .max_by_key(|&(_, count)| count)
+            .unwrap_or((CardSuit::ALL[0], 0));
+        let suits = if suited == board.len() && suited > 1 {
+            Suits::Monotone
+        } else if suited > 1 {
+            Suits::TwoTone
+        } else {
+            Suits::Rainbow
+        };
+
+        let present = |value: &CardValue| board.iter().any(|card| card.value() == *value);
+        let mut connected = 0;
+        let mut straights = vec![];
+        for window in straight_order.windows(5).rev() {
+            let filled = window.iter().filter(|value| present(value)).count();
+            connected = connected.max(filled);
+            // Players hold two cards, so the board has to give the other three
+            if filled >= 3 {
+                straights.push(PossibleStraight {
+                    high: window[4],
+                    needs: window.iter().copied().filter(|value| !present(value)).collect(),
+                });
+            }
+        }
+
+        BoardTexture {
+            pairing,
+            suits,
+            suited,
+            flush_suit: if suited >= 3 { Some(suit) } else { None },
+            connected,
+            straights,
+        }
+    }
+
+    /// Whether at least two board cards share a value
+    pub fn paired(&self) -> bool {
+        self.pairing != Pairing::Unpaired
+    }
+
+    /// Whether a player could have a flush
+    pub fn flush_possible(&self) -> bool {
+        self.flush_suit.is_some()
+    }
+
+    /// Whether one card of the right suit makes a flush
+    pub fn four_flush(&self) -> bool {
+        self.suited >= 4
+    }
+
+    /// Whether a player could have a straight
+    pub fn straight_possible(&self) -> bool {
+        !self.straights.is_empty()
+    }
+
+    /// Whether one card of the right value makes a straight
+    pub fn four_straight(&self) -> bool {
+        self.connected >= 4
+    }
+}
+
+impl fmt::Display for BoardTexture {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}, {}, {} connected", self.pairing, self.suits, self.connected)?;
+        if self.four_flush() {
+            write!(fmt, ", four to a flush")?;
+        } else if self.flush_possible() {
+            write!(fmt, ", flush possible")?;
+        }
+        if self.four_straight() {
+            write!(fmt, ", four to a straight")?;
+        } else if self.straight_possible() {
+            write!(fmt, ", straight possible")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cards(s: &str) -> Vec<Card> {
+        s.split_whitespace().map(|card| card.parse().unwrap()).collect()
+    }
+
+    fn texture(s: &str) -> BoardTexture {
+        BoardTexture::new(&cards(s), &CardValue::ALL)
+    }
+
+    #[test]
+    fn pairing_and_suits() {
+        assert_eq!(texture("7c 7d 2s").pairing, Pairing::Paired);
+        assert_eq!(texture("7c 7d 2s 2h").pairing, Pairing::TwoPair);
+        assert_eq!(texture("7c 7d 7s 2h 2c").pairing, Pairing::FullHouse);
+        assert_eq!(texture("7c 7d 7s 7h").pairing, Pairing::Quads);
+        assert_eq!(texture("7c 8d 2s").suits, Suits::Rainbow);
+        assert_eq!(texture("7c 8c 2s").suits, Suits::TwoTone);
+        let monotone = texture("7c 8c 2c");
+        assert_eq!(monotone.suits, Suits::Monotone);
+        assert_eq!(monotone.flush_suit, Some(CardSuit::Clubs));
+        assert!(!monotone.four_flush());
+        assert!(texture("7c 8c 2c 4c Ad").four_flush());
+        assert!(!texture("7c 8c 2d").flush_possible());
+    }
+
+    #[test]
+    fn straights_need_three_board_cards() {
+        let board = texture("Ah Kd Qc");
+        assert_eq!(board.connected, 3);
+        assert_eq!(board.straights, [PossibleStraight { high: CardValue::Ace, needs: vec![CardValue::Ten, CardValue::Jack] }]);
+        assert!(!texture("Ah 7d 2c").straight_possible());
+
+        let board = texture("9h 8d 7c 6s");
+        assert!(board.four_straight());
+        let highs: Vec<CardValue> = board.straights.iter().map(|straight| straight.high).collect();
+        assert_eq!(highs, [CardValue::Jack, CardValue::Ten, CardValue::Nine, CardValue::Eight]);
+        assert_eq!(board.straights[1].needs, [CardValue::Ten]);
+    }
+
+    #[test]
+    fn straights_follow_the_order() {
+        assert!(!texture("Ah 2d 3c").straight_possible());
+        let mut order = vec![CardValue::Ace];
+        order.extend_from_slice(&CardValue::ALL);
+        let board = BoardTexture::new(&cards("Ah 2d 3c"), &order);
+        assert_eq!(board.straights, [PossibleStraight { high: CardValue::Five, needs: vec![CardValue::Four, CardValue::Five] }]);
+        assert_eq!(board.to_string(), "unpaired, rainbow, 3 connected, straight possible");
+    }
+}
@@ -77,16 +77,14 @@ impl ThreadPool {
         // If a worker crashes, crash the system.
         for (i, flag) in self.flags.iter().enumerate() {
             let flag_ = flag.load(Ordering::SeqCst);
-            if !flag_ {
-                if let Some(_) = self.workers[i].thread.take() {
-                    panic!("[ThreadPool] Worker {} panicked. Killing all workers...", i);
-                }
+            if !flag_ && self.workers[i].thread.take().is_some() {
+                panic!("[ThreadPool] Worker {} panicked. Killing all workers...", i);
             }
         }
 
         // If this panics, we have no workers left,
         // so shutdown and panic
-        if let Err(_) = self.sender.send(Message::NewJob(new_job)) {
+        if self.sender.send(Message::NewJob(new_job)).is_err() {
             panic!("All workers panicked or closed. Unrecoverable errors.");
         }
     }
@@ -97,9 +95,7 @@ impl ThreadPool {
             // If we send, great.
             // If not, we don't care, cause that means everyone is dead.
             // We just want to end and merge all threads
-            if let Ok(_) = self.sender.send(Message::Terminate) {
-                // do nothing
-            }
+            let _ = self.sender.send(Message::Terminate);
         }
 
         let mut count = 0;